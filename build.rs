@@ -14,12 +14,11 @@ fn main() {
 
     let release_dir = src_dir.join("target").join(profile);
     if release_dir.exists() {
-        let config = release_dir
-            .join("config.txt")
-            .as_path()
-            .display()
-            .to_string();
-        let src_config = src_dir.join("config.txt").as_path().display().to_string();
-        fs::copy(src_config, config).unwrap();
+        for name in ["config.toml", "config.txt"] {
+            let src_config = src_dir.join(name);
+            if src_config.exists() {
+                fs::copy(src_config, release_dir.join(name)).unwrap();
+            }
+        }
     }
 }
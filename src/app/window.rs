@@ -0,0 +1,326 @@
+use crate::constants::{
+    APP_NAME, IDM_EXIT, IDM_RELOAD, IDM_STARTUP, S_U_TASKBAR_RESTART, WM_USER_RELOADED,
+    WM_USER_TRAYICON,
+};
+use crate::reload;
+use crate::shortcuts::{ActionOutput, Shortcut, job, shortcuts};
+use crate::startup::Startup;
+use crate::trayicon::TrayIcon;
+use crate::{alert, inform, log};
+
+use crate::utils::errors::{CheckError, check_error};
+use crate::utils::others::{get_window_ptr, set_window_ptr};
+use std::collections::HashMap;
+use std::thread;
+
+use windows::Win32::Foundation::{GetLastError, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::Graphics::Gdi::HBRUSH;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, RegisterHotKey, UnregisterHotKey,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CW_USEDEFAULT, CreateWindowExW, DefWindowProcW, DispatchMessageW, GWL_STYLE, GetMessageW,
+    GetWindowLongPtrW, MSG, PostMessageW, PostQuitMessage, RegisterClassW, RegisterWindowMessageW,
+    SetWindowLongPtrW, TranslateMessage, WINDOW_STYLE, WM_COMMAND, WM_HOTKEY, WM_LBUTTONUP,
+    WM_RBUTTONUP, WNDCLASSW, WS_CAPTION, WS_EX_TOOLWINDOW,
+};
+use windows::core::{PCWSTR, w};
+
+pub struct App {
+    hwnd: HWND,
+    trayicon: TrayIcon,
+    startup: Startup,
+    menu_shortcuts: HashMap<usize, Shortcut>,
+    /// Registered global hotkeys, by hotkey id.
+    hotkeys: HashMap<i32, Shortcut>,
+}
+
+impl App {
+    pub fn start() -> Result<(), String> {
+        let _ = S_U_TASKBAR_RESTART
+            .get_or_init(|| unsafe { RegisterWindowMessageW(w!("TaskbarCreated")) });
+        let hwnd = Self::create_window()?;
+
+        let trayicon = TrayIcon::create();
+
+        let startup = Startup::init().map_err(|err| err.to_string())?;
+
+        let mut app = App {
+            hwnd,
+            trayicon,
+            startup,
+            menu_shortcuts: menu_shortcuts(),
+            hotkeys: HashMap::new(),
+        };
+
+        app.set_trayicon()?;
+        app.register_hotkeys();
+
+        // HWND is not Send, the listener keeps the raw handle to post to.
+        let hwnd_raw = hwnd.0 as isize;
+        reload::on_reload(move || unsafe {
+            let _ = PostMessageW(
+                Some(HWND(hwnd_raw as _)),
+                WM_USER_RELOADED,
+                WPARAM(0),
+                LPARAM(0),
+            );
+        });
+
+        let app_ptr = Box::into_raw(Box::new(app)) as _;
+        check_error(|| set_window_ptr(hwnd, app_ptr))
+            .map_err(|err| format!("Failed to set window ptr, {err}"))?;
+
+        Self::eventloop()
+    }
+
+    fn eventloop() -> Result<(), String> {
+        let mut message = MSG::default();
+        loop {
+            let ret = unsafe { GetMessageW(&mut message, Some(HWND(std::ptr::null_mut())), 0, 0) };
+            match ret.0 {
+                -1 => {
+                    let _ = unsafe { GetLastError() };
+                }
+                0 => break,
+                _ => unsafe {
+                    let _ = TranslateMessage(&message);
+                    DispatchMessageW(&message);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_window() -> Result<HWND, String> {
+        let _hinstance = unsafe { GetModuleHandleW(None) }
+            .map_err(|err| format!("Failed to get current module handle, {err}"))?;
+
+        let hinstance = HINSTANCE::from(_hinstance);
+
+        let window_class = WNDCLASSW {
+            hInstance: hinstance.into(),
+            lpszClassName: APP_NAME,
+            hbrBackground: HBRUSH(std::ptr::null_mut()),
+            lpfnWndProc: Some(App::window_proc),
+            ..Default::default()
+        };
+
+        let atom = unsafe { RegisterClassW(&window_class) }
+            .check_error()
+            .map_err(|err| format!("Failed to register class, {err}"))?;
+
+        let hwnd = unsafe {
+            CreateWindowExW(
+                WS_EX_TOOLWINDOW,
+                PCWSTR(atom as *mut u16),
+                APP_NAME,
+                WINDOW_STYLE(0),
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                None,
+                None,
+                Some(hinstance),
+                None,
+            )
+        }
+        .unwrap()
+        .check_error()
+        .map_err(|err| format!("Failed to create windows, {err}"))?;
+
+        // hide caption
+        let mut style = unsafe { GetWindowLongPtrW(hwnd, GWL_STYLE) } as u32;
+        style &= !WS_CAPTION.0;
+        unsafe { SetWindowLongPtrW(hwnd, GWL_STYLE, style as _) };
+
+        Ok(hwnd)
+    }
+
+    fn set_trayicon(&mut self) -> Result<(), String> {
+        self.trayicon.register(self.hwnd)?;
+        Ok(())
+    }
+
+    /// Replaces the registered hotkeys with those of the shortcuts in effect.
+    /// Hotkeys another app already took are reported and skipped.
+    fn register_hotkeys(&mut self) {
+        self.unregister_hotkeys();
+        let mut taken = vec![];
+        for (id, shortcut) in (1..).zip(shortcuts().iter().filter(|x| x.hotkey.is_some())) {
+            let hotkey = shortcut.hotkey.unwrap();
+            let mut modifiers = MOD_NOREPEAT;
+            for (held, modifier) in [
+                (hotkey.ctrl, MOD_CONTROL),
+                (hotkey.alt, MOD_ALT),
+                (hotkey.shift, MOD_SHIFT),
+                (hotkey.win, MOD_WIN),
+            ] {
+                if held {
+                    modifiers |= modifier;
+                }
+            }
+            match unsafe { RegisterHotKey(Some(self.hwnd), id, modifiers, hotkey.vk as u32) } {
+                Ok(()) => {
+                    self.hotkeys.insert(id, shortcut.clone());
+                }
+                Err(err) => {
+                    log!(
+                        "Fail to register hotkey {hotkey} for {}, {err}",
+                        shortcut.name
+                    );
+                    taken.push(format!("{hotkey} ({})", shortcut.name));
+                }
+            }
+        }
+        if !taken.is_empty() {
+            thread::spawn(move || {
+                alert!(
+                    "These hotkeys are already taken by another app: {}",
+                    taken.join(", ")
+                )
+            });
+        }
+    }
+
+    fn unregister_hotkeys(&mut self) {
+        for id in self.hotkeys.keys() {
+            let _ = unsafe { UnregisterHotKey(Some(self.hwnd), *id) };
+        }
+        self.hotkeys.clear();
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        match Self::handle_message(hwnd, msg, wparam, lparam) {
+            Ok(ret) => ret,
+            Err(err) => {
+                log!("Fail to handle window message {msg}, {err}");
+                unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+            }
+        }
+    }
+
+    fn handle_message(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> Result<LRESULT, String> {
+        match msg {
+            WM_USER_TRAYICON => {
+                let app = get_app(hwnd)?;
+
+                let keycode = lparam.0 as u32;
+                if keycode == WM_RBUTTONUP {
+                    app.trayicon.show(app.startup.is_enable)?;
+                }
+                if keycode == WM_LBUTTONUP {
+                    for ele in shortcuts().iter().filter(|x| x.is_left_click) {
+                        run_shortcut(ele.clone());
+                    }
+                }
+
+                return Ok(LRESULT(0));
+            }
+            WM_COMMAND => {
+                let value = wparam.0 as u32;
+                let kind = ((value >> 16) & 0xffff) as u16;
+                let id = value & 0xffff;
+                if kind == 0 {
+                    let app = get_app(hwnd)?;
+                    let id_usize = usize::try_from(id).unwrap();
+                    if let Some(shortcut) = app.menu_shortcuts.get(&id_usize) {
+                        run_shortcut(shortcut.clone());
+                    }
+                    match id {
+                        IDM_EXIT => {
+                            // Messages still queued must not reach the freed app
+                            app.unregister_hotkeys();
+                            set_window_ptr(hwnd, 0);
+                            unsafe { drop(Box::from_raw(app as *mut App)) }
+                            unsafe { PostQuitMessage(0) }
+                            return Ok(LRESULT(0));
+                        }
+                        IDM_STARTUP => {
+                            app.startup.toggle().map_err(|err| err.to_string())?;
+                        }
+                        IDM_RELOAD => {
+                            thread::spawn(|| match reload::reload() {
+                                Ok(()) => inform!("Config reloaded"),
+                                Err(err) => {
+                                    log!("Fail to reload config, {err}");
+                                    alert!("Fail to reload config, {err}");
+                                }
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            WM_USER_RELOADED => {
+                let app = get_app(hwnd)?;
+                app.menu_shortcuts = menu_shortcuts();
+                app.register_hotkeys();
+                return Ok(LRESULT(0));
+            }
+            WM_HOTKEY => {
+                let app = get_app(hwnd)?;
+                if let Some(shortcut) = app.hotkeys.get(&(wparam.0 as i32)) {
+                    run_shortcut(shortcut.clone());
+                }
+                return Ok(LRESULT(0));
+            }
+            _ if msg == *S_U_TASKBAR_RESTART.get().unwrap() => {
+                let app = get_app(hwnd)?;
+                app.set_trayicon()?;
+            }
+            _ => {}
+        }
+        Ok(unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) })
+    }
+}
+
+/// Shortcuts shown in the tray menu, by menu id.
+fn menu_shortcuts() -> HashMap<usize, Shortcut> {
+    shortcuts()
+        .iter()
+        .filter(|x| x.menu_name.is_some())
+        .filter_map(|x| Some((x.id?, x.clone())))
+        .collect()
+}
+
+/// Runs a shortcut as a job and reports the outcome off the UI thread.
+fn run_shortcut(shortcut: Shortcut) {
+    let job = match job::submit(&shortcut, None) {
+        Ok(job) => job,
+        Err(running) => {
+            alert!("{} is already running as job {}", shortcut.name, running.id);
+            return;
+        }
+    };
+    thread::spawn(move || match job.wait() {
+        Ok(ActionOutput::Text(text)) => inform!("{text}"),
+        Ok(_) => {}
+        Err(err) => alert!("{} failed, {err}", shortcut.name),
+    });
+}
+
+fn get_app(hwnd: HWND) -> Result<&'static mut App, String> {
+    unsafe {
+        let ptr = check_error(|| get_window_ptr(hwnd))
+            .map_err(|err| format!("Failed to get window ptr, {err}"))?;
+        if ptr == 0 {
+            return Err("The app already exited".to_string());
+        }
+        let tx: &mut App = &mut *(ptr as *mut _);
+        Ok(tx)
+    }
+}
@@ -0,0 +1,463 @@
+use std::path::PathBuf;
+
+use crate::app::start_app;
+use crate::config::{CONFIG_FILE, Config, LEGACY_CONFIG_FILE, app_config};
+use crate::server::client::stream_events;
+use crate::shortcuts::{ActionOutput, find_shortcut, job, shortcuts};
+use crate::utils::macros::{has_console, use_console};
+use crate::utils::others::get_exe_folder;
+use crate::{alert, inform, ipc, reload};
+
+#[cfg(windows)]
+use crate::utils::instance::SingleInstance;
+#[cfg(windows)]
+use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole, GetConsoleWindow};
+
+pub const EXIT_OK: i32 = 0;
+/// The app, the shortcut or the config check failed.
+pub const EXIT_FAILED: i32 = 1;
+/// Bad arguments, or a shortcut or device that doesn't exist.
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: windows-shortcuts [command]
+
+Without a command, runs the tray icon, hotkeys, HTTP server and schedule.
+
+Commands:
+  --headless                          run the HTTP server and schedule only, until stopped
+  run <shortcut> [--device <device>]  run a shortcut and print its output
+  list                                list the shortcuts and their actions
+  check-config [<dir>]                check the config next to the executable, or in <dir>
+  events                              print the events of the running app as they happen
+  help                                show this help";
+
+/// What the command line asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// The tray app, the default on Windows.
+    App,
+    /// Everything but the tray icon and hotkeys, the default elsewhere.
+    Headless,
+    Run {
+        shortcut: String,
+        device: Option<String>,
+    },
+    List,
+    CheckConfig(Option<PathBuf>),
+    /// Follows the event stream of the running app's server.
+    Events,
+    Help,
+}
+
+impl Command {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        let command = match args.as_slice() {
+            [] if cfg!(windows) => Command::App,
+            [] | ["--headless"] => Command::Headless,
+            ["run", shortcut] => Command::Run {
+                shortcut: shortcut.to_string(),
+                device: None,
+            },
+            ["run", shortcut, "--device", device] | ["run", "--device", device, shortcut] => {
+                Command::Run {
+                    shortcut: shortcut.to_string(),
+                    device: Some(device.to_string()),
+                }
+            }
+            ["list"] => Command::List,
+            ["check-config"] => Command::CheckConfig(None),
+            ["check-config", dir] => Command::CheckConfig(Some(PathBuf::from(dir))),
+            ["events"] => Command::Events,
+            ["help" | "--help" | "-h"] => Command::Help,
+            [
+                name @ ("--headless" | "run" | "list" | "check-config" | "events" | "help"),
+                ..,
+            ] => {
+                return Err(format!("Invalid arguments for '{name}'"));
+            }
+            [name, ..] => return Err(format!("Unknown command '{name}'")),
+        };
+        Ok(command)
+    }
+}
+
+/// Where a command prints, the console or the instance that passed it on.
+pub trait Output {
+    fn out(&mut self, text: &str);
+    fn err(&mut self, text: &str);
+}
+
+/// Prints to the console, or shows message boxes when there is none.
+pub struct Console;
+
+impl Output for Console {
+    fn out(&mut self, text: &str) {
+        if has_console() {
+            println!("{text}");
+        } else {
+            inform!("{text}");
+        }
+    }
+
+    fn err(&mut self, text: &str) {
+        if has_console() {
+            eprintln!("{text}");
+        } else {
+            alert!("{text}");
+        }
+    }
+}
+
+/// Runs what the command line asks for and returns the exit code.
+pub fn main(args: impl IntoIterator<Item = String>) -> i32 {
+    let args = args.into_iter().collect::<Vec<_>>();
+    let command = match Command::parse(args.clone()) {
+        Ok(command) => command,
+        Err(err) => {
+            attach_console();
+            Console.err(&format!("{err}\n\n{USAGE}"));
+            return EXIT_USAGE;
+        }
+    };
+    match command {
+        Command::App => {}
+        Command::Headless => {
+            attach_console();
+            // A service has no desktop to show message boxes on
+            use_console();
+        }
+        _ => attach_console(),
+    }
+    let res = match command {
+        Command::App => serve(false),
+        Command::Headless => serve(true),
+        Command::Run { .. } | Command::List => return run_anywhere(&args, &command),
+        Command::CheckConfig(dir) => check_config(dir),
+        Command::Events => watch_events(),
+        Command::Help => {
+            Console.out(USAGE);
+            Ok(())
+        }
+    };
+    match res {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            Console.err(&err);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Runs `run` and `list` commands with the config and shortcuts in effect.
+pub fn execute(command: &Command, output: &mut dyn Output) -> i32 {
+    match command {
+        Command::Run { shortcut, device } => run(shortcut, device.as_deref(), output),
+        Command::List => {
+            output.out(&list());
+            EXIT_OK
+        }
+        _ => {
+            output.err("Only run and list are passed to the running instance");
+            EXIT_USAGE
+        }
+    }
+}
+
+/// Sends output and messages to the console the app was started from, if any.
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+        if GetConsoleWindow().is_invalid() {
+            return;
+        }
+    }
+    use_console();
+}
+
+fn serve(headless: bool) -> Result<(), String> {
+    #[cfg(windows)]
+    let instance = SingleInstance::create("WindowSwitcherMutex")?;
+    #[cfg(windows)]
+    if !instance.is_single() {
+        return Err("Another instance is running. This instance will abort.".to_string());
+    }
+    start_app(headless)
+}
+
+/// Passes the command line to the running instance, or runs it here when there is none.
+fn run_anywhere(args: &[String], command: &Command) -> i32 {
+    match ipc::forward(args, &mut Console) {
+        Ok(Some(code)) => return code,
+        Ok(None) => {}
+        Err(err) => {
+            Console.err(&err);
+            return EXIT_FAILED;
+        }
+    }
+    if let Err(err) = load_config() {
+        Console.err(&err);
+        return EXIT_FAILED;
+    }
+    execute(command, &mut Console)
+}
+
+fn run(name: &str, device: Option<&str>, output: &mut dyn Output) -> i32 {
+    let Some(shortcut) = find_shortcut(name) else {
+        output.err(&format!("Unknown shortcut '{name}', `list` shows them"));
+        return EXIT_USAGE;
+    };
+    if let Some(device) = device
+        && app_config().device(device).is_none()
+    {
+        output.err(&format!("Unknown device '{device}'"));
+        return EXIT_USAGE;
+    }
+    let job = match job::submit(&shortcut, device) {
+        Ok(job) => job,
+        Err(running) => {
+            output.err(&format!("{name} is already running as job {}", running.id));
+            return EXIT_FAILED;
+        }
+    };
+    match job.wait() {
+        Ok(ActionOutput::None) => EXIT_OK,
+        Ok(ActionOutput::Text(text)) => {
+            output.out(&text);
+            EXIT_OK
+        }
+        Ok(ActionOutput::File(file)) => {
+            output.out(&file.display().to_string());
+            EXIT_OK
+        }
+        Err(err) => {
+            output.err(&format!("{name} failed, {err}"));
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Every shortcut on a line with its details, followed by its actions.
+fn list() -> String {
+    let mut res = vec![];
+    for shortcut in shortcuts().iter() {
+        let mut details = vec![];
+        if let Some(device) = &shortcut.device {
+            details.push(format!("device {device}"));
+        }
+        if let Some(menu) = &shortcut.menu_name {
+            details.push(format!("menu \"{menu}\""));
+        }
+        if let Some(route) = &shortcut.web_req_url {
+            details.push(format!("route {route}"));
+        }
+        if let Some(hotkey) = &shortcut.hotkey {
+            details.push(format!("hotkey {hotkey}"));
+        }
+        if shortcut.is_left_click {
+            details.push("left click".to_string());
+        }
+        if details.is_empty() {
+            res.push(shortcut.name.clone());
+        } else {
+            res.push(format!("{} ({})", shortcut.name, details.join(", ")));
+        }
+        for step in &shortcut.steps {
+            res.push(format!("    {step}"));
+        }
+    }
+    res.join("\n")
+}
+
+fn check_config(dir: Option<PathBuf>) -> Result<(), String> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => get_exe_folder()?,
+    };
+    let file = [CONFIG_FILE, LEGACY_CONFIG_FILE]
+        .into_iter()
+        .map(|x| dir.join(x))
+        .find(|x| x.exists())
+        .ok_or_else(|| format!("No {CONFIG_FILE} in {}", dir.display()))?;
+    // A check leaves config.txt alone, the app converts it on start
+    let config = Config::load_without_migrating(&dir).map_err(|err| err.to_string())?;
+    Console.out(&format!(
+        "{} is valid, {} shortcuts, {} devices, {} schedule entries",
+        file.display(),
+        config.shortcuts.len(),
+        config.devices.len(),
+        config.schedule.len()
+    ));
+    Ok(())
+}
+
+fn watch_events() -> Result<(), String> {
+    load_config()?;
+    stream_events(&app_config(), |name, data| {
+        Console.out(&format!("{name} {data}"))
+    })
+}
+
+fn load_config() -> Result<(), String> {
+    reload::init(get_exe_folder()?).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::platform::mock;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(args.iter().map(ToString::to_string))
+    }
+
+    /// Keeps what a command printed.
+    #[derive(Default)]
+    pub struct Recorded {
+        pub out: Vec<String>,
+        pub err: Vec<String>,
+    }
+
+    impl Output for Recorded {
+        fn out(&mut self, text: &str) {
+            self.out.push(text.to_string());
+        }
+
+        fn err(&mut self, text: &str) {
+            self.err.push(text.to_string());
+        }
+    }
+
+    fn run_command(shortcut: &str, device: Option<&str>) -> (i32, Recorded) {
+        let command = Command::Run {
+            shortcut: shortcut.to_string(),
+            device: device.map(ToString::to_string),
+        };
+        let mut output = Recorded::default();
+        (execute(&command, &mut output), output)
+    }
+
+    const CONFIG: &str = r#"
+[[devices]]
+name = "projector"
+ip = "192.168.1.30"
+
+[[shortcuts]]
+name = "where"
+actions = ["echo {device} at {device_ip}"]
+
+[[shortcuts]]
+name = "desk"
+actions = ["send_keys ctrl+alt+t"]
+"#;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse(&["--headless"]), Ok(Command::Headless));
+        assert_eq!(
+            parse(&["run", "desk"]),
+            Ok(Command::Run {
+                shortcut: "desk".to_string(),
+                device: None
+            })
+        );
+        let on_projector = Command::Run {
+            shortcut: "desk".to_string(),
+            device: Some("projector".to_string()),
+        };
+        assert_eq!(
+            parse(&["run", "desk", "--device", "projector"]),
+            Ok(on_projector.clone())
+        );
+        assert_eq!(
+            parse(&["run", "--device", "projector", "desk"]),
+            Ok(on_projector)
+        );
+        assert_eq!(parse(&["list"]), Ok(Command::List));
+        assert_eq!(parse(&["check-config"]), Ok(Command::CheckConfig(None)));
+        assert_eq!(
+            parse(&["check-config", "/etc/shortcuts"]),
+            Ok(Command::CheckConfig(Some(PathBuf::from("/etc/shortcuts"))))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let cases = [
+            (&["run"][..], "Invalid arguments for 'run'"),
+            (&["run", "desk", "--device"], "Invalid arguments for 'run'"),
+            (
+                &["run", "desk", "--on", "tv"],
+                "Invalid arguments for 'run'",
+            ),
+            (&["list", "all"], "Invalid arguments for 'list'"),
+            (&["--headless", "now"], "Invalid arguments for '--headless'"),
+            (
+                &["check-config", "a", "b"],
+                "Invalid arguments for 'check-config'",
+            ),
+            (&["start"], "Unknown command 'start'"),
+            (&["--verbose"], "Unknown command '--verbose'"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Err(expected.to_string()), "{args:?}");
+            let args = args.iter().map(ToString::to_string);
+            assert_eq!(main(args), EXIT_USAGE);
+        }
+    }
+
+    #[test]
+    fn run_reports_through_exit_codes() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let (code, output) = run_command("where", Some("projector"));
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(output.out, ["projector at 192.168.1.30"]);
+
+        let (code, output) = run_command("nope", None);
+        assert_eq!(code, EXIT_USAGE);
+        assert_eq!(output.err, ["Unknown shortcut 'nope', `list` shows them"]);
+        let (code, output) = run_command("where", Some("nope"));
+        assert_eq!(code, EXIT_USAGE);
+        assert_eq!(output.err, ["Unknown device 'nope'"]);
+
+        mock.update(|x| x.fail_next = Some("no desktop".to_string()));
+        let (code, output) = run_command("desk", None);
+        assert_eq!(code, EXIT_FAILED);
+        assert_eq!(output.err, ["desk failed, Fail to send input, no desktop"]);
+        assert!(output.out.is_empty());
+    }
+
+    #[test]
+    fn only_run_and_list_are_executed() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let mut output = Recorded::default();
+        assert_eq!(execute(&Command::List, &mut output), EXIT_OK);
+        assert!(output.out[0].contains("where"), "{:?}", output.out);
+        let mut output = Recorded::default();
+        assert_eq!(execute(&Command::Headless, &mut output), EXIT_USAGE);
+        assert_eq!(
+            output.err,
+            ["Only run and list are passed to the running instance"]
+        );
+    }
+
+    #[test]
+    fn check_config_leaves_legacy_config_alone() {
+        let dir =
+            std::env::temp_dir().join(format!("windows-shortcuts-{}-cli", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(check_config(Some(dir.clone())).is_err());
+        fs::write(dir.join(LEGACY_CONFIG_FILE), "TV_IP::192.168.1.40\n").unwrap();
+        check_config(Some(dir.clone())).unwrap();
+        assert!(!dir.join(CONFIG_FILE).exists());
+        fs::write(dir.join(LEGACY_CONFIG_FILE), "PORT::nope\n").unwrap();
+        assert!(check_config(Some(dir.clone())).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,64 +1,1062 @@
+mod legacy;
+pub mod toml;
+
+use std::fmt::Write;
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::constants::APP_CONFIG;
+use crate::error::Error;
+use crate::log;
+use crate::schedule::When;
+use crate::server::auth::Cidr;
+use crate::shortcuts::{job::Concurrency, step::Step};
+use crate::utils::adb::AndroidKey;
+use crate::utils::keys::Hotkey;
+use crate::utils::magic_packet::{Interface, format_password, parse_password};
+use crate::utils::others::{format_mac_addr, parse_ip_addr, parse_mac_addr};
+
+use toml::{Entry, Table, Value, quote};
+
+pub const CONFIG_FILE: &str = "config.toml";
+/// The `KEY::value` format used before config.toml, still read when config.toml is missing.
+pub const LEGACY_CONFIG_FILE: &str = "config.txt";
+/// The device configured by `[tv]`, used when a shortcut names none.
+pub const DEFAULT_DEVICE: &str = "tv";
+
+/// Shortcuts available without any configuration. A `[[shortcuts]]` entry in
+/// config.toml replaces the one with the same name, or adds a new one.
+const DEFAULT_SHORTCUTS: &str = r#"
+[[shortcuts]]
+name = "test_connection"
+menu = "Test"
+route = "/test_connection"
+actions = ["echo {screen_dir}"]
+
+[[shortcuts]]
+name = "capture_screen"
+menu = "Capture Screen"
+route = "/capture_screen"
+actions = ["adb_screenshot"]
+
+[[shortcuts]]
+name = "capture_windows_screen"
+menu = "Capture Windows Screen"
+route = "/capture_windows_screen"
+actions = ["screenshot primary"]
+
+[[shortcuts]]
+name = "switch_to_tv"
+menu = "Switch to TV"
+route = "/switch_to_tv"
+actions = [
+    "wake_on_lan",
+    "wait_for_device",
+    "adb_connect | retry 3",
+    "adb_ensure_on",
+    "adb_input",
+    "display external",
+    "night_light off | on_error continue",
+]
 
-use crate::utils::others::{parse_ip_addr, parse_mac_addr};
+[[shortcuts]]
+name = "switch_to_monitor"
+menu = "Switch to Monitor"
+route = "/switch_to_monitor"
+actions = [
+    "display internal",
+    "adb_connect",
+    "adb_key KEYCODE_HOME",
+    "adb_ensure_off",
+]
 
-const KEY_TV_IP: &str = "TV_IP";
-const KEY_TV_MAC: &str = "TV_MAC";
-const KEY_SERVER_IP: &str = "SERVER_IP";
-const KEY_SERVER_PORT: &str = "PORT";
-const KEY_SCREEN_DIR: &str = "SCREEN_DIR";
+[[shortcuts]]
+name = "wake"
+actions = ["wake_on_lan", "wait_for_device", "adb_connect | retry 3", "adb_ensure_on"]
+
+[[shortcuts]]
+name = "sleep"
+actions = ["adb_connect", "adb_ensure_off"]
+
+[[shortcuts]]
+name = "restart_explorer"
+left_click = true
+actions = ["clear_clipboard", "kill_explorer"]
+
+[[shortcuts]]
+name = "close_top_window"
+route = "/close_top_window"
+actions = ["send_keys alt+f4"]
+"#;
+
+/// The config in effect. Callers keep the snapshot they got even if a reload happens meanwhile.
+pub fn app_config() -> Arc<Config> {
+    APP_CONFIG
+        .read()
+        .unwrap()
+        .clone()
+        .expect("config is loaded")
+}
+
+pub fn set_app_config(config: Config) {
+    *APP_CONFIG.write().unwrap() = Some(Arc::new(config));
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub tv_ip_addr: String,
-    pub tv_mac_addr: [u8; 6],
+    /// The `[tv]` device first, then the `[[devices]]` ones.
+    pub devices: Vec<DeviceConfig>,
+    /// This PC's LAN address, the source of magic packets and where the server listens by default.
     pub server_addr: [u8; 4],
-    pub server_port: String,
+    pub server_port: u16,
+    /// Addresses the server listens on, overriding `server_addr`.
+    pub listen_addrs: Vec<IpAddr>,
     pub screen_dir: String,
+    pub shortcuts: Vec<ShortcutConfig>,
+    pub schedule: Vec<ScheduleConfig>,
+    /// Required as `Authorization: Bearer` or `?token=` when set.
+    pub api_token: Option<String>,
+    /// Clients outside these blocks are refused, empty allows everyone.
+    pub allowed_clients: Vec<Cidr>,
+    /// Requests must carry a valid signature when set.
+    pub hmac_secret: Option<String>,
+    /// Seconds a signed request stays valid.
+    pub hmac_max_skew: u64,
+    pub wol: WolConfig,
 }
 
-impl Default for Config {
+/// How magic packets are sent, from `[wol]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WolConfig {
+    /// Packets go to this interface's subnet broadcast, from its address.
+    pub interface: Option<Interface>,
+    /// Overrides the destination address.
+    pub broadcast: Option<Ipv4Addr>,
+    pub ports: Vec<u16>,
+    /// How many times each packet is sent.
+    pub repeat: u32,
+    /// UDP ports watched for magic packets, which are logged.
+    pub listen_ports: Vec<u16>,
+    /// Sends the magic packets seen on `listen_ports` again, as configured here.
+    pub relay: bool,
+}
+
+impl Default for WolConfig {
     fn default() -> Self {
         Self {
-            tv_ip_addr: String::from("192.168.1.20"),
-            tv_mac_addr: [1, 1, 1, 1, 1, 1],
-            server_addr: [192, 168, 1, 10],
-            server_port: String::from("9111"),
-            screen_dir: String::from("D:\\"),
+            interface: None,
+            broadcast: None,
+            ports: vec![9],
+            repeat: 1,
+            listen_ports: vec![],
+            relay: false,
         }
     }
 }
 
+/// A TV or Android box the adb and wake-on-lan actions target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceConfig {
+    pub name: String,
+    pub ip: String,
+    pub mac: Option<[u8; 6]>,
+    /// SecureOn password appended to magic packets, 4 or 6 bytes.
+    pub wol_password: Option<Vec<u8>>,
+    /// The adb serial, `ip:port`, when adb does not listen on the ip's port 5555.
+    pub serial: Option<String>,
+    /// Keycode switching the device to this PC's HDMI input.
+    pub input: Option<AndroidKey>,
+    pub wake_keys: Vec<AndroidKey>,
+    pub sleep_keys: Vec<AndroidKey>,
+    /// How long `wait_for_device` waits for adb to answer.
+    pub ready_timeout: Duration,
+    /// How often `wait_for_device` resends the magic packet, zero to send none.
+    pub wol_interval: Duration,
+}
+
+impl DeviceConfig {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ip: String::new(),
+            mac: None,
+            wol_password: None,
+            serial: None,
+            input: None,
+            wake_keys: vec![AndroidKey::Wakeup],
+            sleep_keys: vec![AndroidKey::Sleep],
+            ready_timeout: Duration::from_secs(60),
+            wol_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Where adb connects to.
+    pub fn adb_serial(&self) -> &str {
+        self.serial.as_deref().unwrap_or(&self.ip)
+    }
+}
+
+/// A shortcut run on a schedule, from `[[schedule]]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleConfig {
+    pub shortcut: String,
+    /// The device to run on instead of the shortcut's own.
+    pub device: Option<String>,
+    pub when: When,
+}
+
+/// A named sequence of steps and where it is exposed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortcutConfig {
+    pub name: String,
+    /// The device its actions target unless the caller picks one.
+    pub device: Option<String>,
+    pub menu: Option<String>,
+    pub route: Option<String>,
+    pub left_click: bool,
+    /// Runs the shortcut from anywhere, e.g. `ctrl+alt+t`.
+    pub hotkey: Option<Hotkey>,
+    pub enabled: bool,
+    pub steps: Vec<Step>,
+    /// What a second run on the same device does while one is queued or running.
+    pub concurrency: Concurrency,
+}
+
+impl ShortcutConfig {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            device: None,
+            menu: None,
+            route: None,
+            left_click: false,
+            hotkey: None,
+            enabled: true,
+            steps: vec![],
+            concurrency: Concurrency::Queue,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let tv = DeviceConfig {
+            ip: String::from("192.168.1.20"),
+            mac: Some([1, 1, 1, 1, 1, 1]),
+            input: Some(AndroidKey::TvInputHdmi4),
+            ..DeviceConfig::new(DEFAULT_DEVICE)
+        };
+        let mut res = Self {
+            devices: vec![tv],
+            server_addr: [0, 0, 0, 0],
+            server_port: 9111,
+            listen_addrs: vec![],
+            screen_dir: String::from("D:\\"),
+            shortcuts: vec![],
+            schedule: vec![],
+            api_token: None,
+            allowed_clients: vec![],
+            hmac_secret: None,
+            hmac_max_skew: 300,
+            wol: WolConfig::default(),
+        };
+        res.parse_toml("default shortcuts", DEFAULT_SHORTCUTS)
+            .expect("default shortcuts are valid");
+        res
+    }
+}
+
 impl Config {
-    pub fn load(file: &str) -> Result<Self, String> {
+    pub fn tv(&self) -> &DeviceConfig {
+        &self.devices[0]
+    }
+
+    fn tv_mut(&mut self) -> &mut DeviceConfig {
+        &mut self.devices[0]
+    }
+
+    pub fn device(&self, name: &str) -> Option<&DeviceConfig> {
+        self.devices.iter().find(|x| x.name == name)
+    }
+
+    /// Every address the server binds, `listen` if given, else the server ip.
+    pub fn bind_addrs(&self) -> Vec<SocketAddr> {
+        let ips = if self.listen_addrs.is_empty() {
+            vec![IpAddr::V4(Ipv4Addr::from(self.server_addr))]
+        } else {
+            self.listen_addrs.clone()
+        };
+        ips.into_iter()
+            .map(|ip| SocketAddr::new(ip, self.server_port))
+            .collect()
+    }
+
+    /// Reads config.toml from `dir`. Without one, a legacy config.txt is read
+    /// and converted to config.toml, and without either the defaults apply.
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let res = Self::load_without_migrating(dir)?;
+        let path = dir.join(CONFIG_FILE);
+        let legacy_path = dir.join(LEGACY_CONFIG_FILE);
+        if !path.exists() && legacy_path.exists() {
+            match fs::write(&path, res.to_toml()) {
+                Ok(()) => log!("Converted {} to {}", legacy_path.display(), path.display()),
+                Err(err) => log!("Fail to write {}, {err}", path.display()),
+            }
+        }
+        Ok(res)
+    }
+
+    /// Reads the config like `load` without writing anything, a legacy config.txt stays as is.
+    pub fn load_without_migrating(dir: &Path) -> Result<Self, Error> {
         let mut res = Config::default();
-        let file_content = fs::read_to_string(file);
-        match file_content {
-            Ok(val) => {
-                let lines = val.lines();
-                for line in lines {
-                    let arr = line.split("::").collect::<Vec<&str>>();
-                    if arr.len() == 2 {
-                        match arr[0] {
-                            KEY_TV_IP => res.tv_ip_addr = arr[1].to_owned(),
-                            KEY_TV_MAC => {
-                                let mac_addr = parse_mac_addr(arr[1])?;
-                                res.tv_mac_addr = mac_addr;
-                            }
-                            KEY_SERVER_IP => {
-                                let server_ip = parse_ip_addr(arr[1])?;
-                                res.server_addr = server_ip;
-                            }
-                            KEY_SERVER_PORT => res.server_port = arr[1].to_owned(),
-                            KEY_SCREEN_DIR => res.screen_dir = arr[1].to_owned(),
-                            _ => {}
+        let path = dir.join(CONFIG_FILE);
+        let legacy_path = dir.join(LEGACY_CONFIG_FILE);
+        if path.exists() {
+            res.parse_toml(&path.display().to_string(), &read(&path)?)?;
+        } else if legacy_path.exists() {
+            let file = legacy_path.display().to_string();
+            legacy::parse(&mut res, &file, &read(&legacy_path)?)?;
+        } else {
+            log!("No {CONFIG_FILE} in {}, using defaults", dir.display());
+        }
+        Ok(res)
+    }
+
+    /// Reads config.toml content over the defaults, as `load` does.
+    #[cfg(test)]
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        let mut res = Config::default();
+        res.parse_toml(CONFIG_FILE, content)?;
+        Ok(res)
+    }
+
+    fn parse_toml(&mut self, file: &str, content: &str) -> Result<(), Error> {
+        let tables = toml::parse(content)
+            .map_err(|err| Error::config(format!("{file}:{}: {}", err.line, err.message)))?;
+        for table in &tables {
+            match (table.name.as_str(), table.array) {
+                ("", false) => {
+                    if let Some(entry) = table.entries.first() {
+                        return Err(located(
+                            file,
+                            entry.line,
+                            &entry.key,
+                            "Unknown key, settings belong in [tv], [server], [capture] or [wol]",
+                        ));
+                    }
+                }
+                ("tv", false) => parse_device(file, table, self.tv_mut())?,
+                ("devices", true) => {
+                    let device = parse_device_table(file, table)?;
+                    if self.device(&device.name).is_some() {
+                        return Err(located(
+                            file,
+                            table.line,
+                            "devices",
+                            format!("Device '{}' is defined twice", device.name),
+                        ));
+                    }
+                    self.devices.push(device);
+                }
+                ("server", false) => self.parse_server(file, table)?,
+                ("capture", false) => self.parse_capture(file, table)?,
+                ("wol", false) => self.parse_wol(file, table)?,
+                ("schedule", true) => self.schedule.push(parse_schedule(file, table)?),
+                ("shortcuts", true) => {
+                    let shortcut = parse_shortcut(file, table)?;
+                    self.merge_shortcut(shortcut)
+                        .map_err(|err| located(file, table.line, "shortcuts", err))?;
+                }
+                (name, _) => {
+                    return Err(Error::config(format!(
+                        "{file}:{}: Unknown table [{name}], expected [tv], [[devices]], [server], [capture], [wol], [[shortcuts]] or [[schedule]]",
+                        table.line
+                    )));
+                }
+            }
+        }
+        // Devices may be declared after the shortcuts using them.
+        for table in tables.iter().filter(|x| x.array && x.name == "shortcuts") {
+            for entry in table.entries.iter().filter(|x| x.key == "device") {
+                let field = Field::new(file, table, entry);
+                let name = field.string()?;
+                if self.device(name).is_none() {
+                    return Err(field.error(format!("Unknown device '{name}'")));
+                }
+            }
+        }
+        for table in tables.iter().filter(|x| x.array && x.name == "schedule") {
+            for entry in &table.entries {
+                let field = Field::new(file, table, entry);
+                match entry.key.as_str() {
+                    "shortcut" => {
+                        let name = field.string()?;
+                        if !self.shortcuts.iter().any(|x| x.name == name) {
+                            return Err(field.error(format!("Unknown shortcut '{name}'")));
+                        }
+                    }
+                    "device" => {
+                        let name = field.string()?;
+                        if self.device(name).is_none() {
+                            return Err(field.error(format!("Unknown device '{name}'")));
                         }
                     }
+                    _ => {}
                 }
             }
-            Err(err) => println!("{}", err),
         }
+        Ok(())
+    }
 
-        Ok(res)
+    fn parse_server(&mut self, file: &str, table: &Table) -> Result<(), Error> {
+        for entry in &table.entries {
+            let field = Field::new(file, table, entry);
+            match entry.key.as_str() {
+                "ip" => self.server_addr = field.check(parse_ip_addr(field.string()?))?,
+                "port" => self.server_port = field.check(port(field.integer()?))?,
+                "listen" => {
+                    self.listen_addrs = field
+                        .strings()?
+                        .into_iter()
+                        .map(|x| field.check(listen_addr(x)))
+                        .collect::<Result<_, _>>()?;
+                }
+                "token" => self.api_token = non_empty(field.string()?),
+                "allowed_clients" => {
+                    self.allowed_clients = field
+                        .strings()?
+                        .into_iter()
+                        .map(|x| field.check(x.parse::<Cidr>()))
+                        .collect::<Result<_, _>>()?;
+                }
+                "hmac_secret" => self.hmac_secret = non_empty(field.string()?),
+                "hmac_max_skew" => {
+                    self.hmac_max_skew = field.check(seconds(field.integer()?))?;
+                }
+                _ => return Err(field.unknown()),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_capture(&mut self, file: &str, table: &Table) -> Result<(), Error> {
+        for entry in &table.entries {
+            let field = Field::new(file, table, entry);
+            match entry.key.as_str() {
+                "dir" => self.screen_dir = field.string()?.to_owned(),
+                _ => return Err(field.unknown()),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_wol(&mut self, file: &str, table: &Table) -> Result<(), Error> {
+        for entry in &table.entries {
+            let field = Field::new(file, table, entry);
+            match entry.key.as_str() {
+                "interface" => {
+                    self.wol.interface = Some(field.check(field.string()?.parse())?);
+                }
+                "broadcast" => {
+                    let value = field.string()?;
+                    let addr = value
+                        .trim()
+                        .parse::<Ipv4Addr>()
+                        .map_err(|_| field.error(format!("Invalid IPv4 address '{value}'")))?;
+                    self.wol.broadcast = Some(addr);
+                }
+                "ports" => {
+                    self.wol.ports = ports(&field)?;
+                    if self.wol.ports.is_empty() {
+                        return Err(field.error("Must not be empty"));
+                    }
+                }
+                "listen_ports" => self.wol.listen_ports = ports(&field)?,
+                "relay" => self.wol.relay = field.bool()?,
+                "repeat" => {
+                    let value = field.integer()?;
+                    self.wol.repeat = u32::try_from(value)
+                        .ok()
+                        .filter(|x| (1..=10).contains(x))
+                        .ok_or_else(|| {
+                            field.error(format!("Invalid repeat {value}, expected 1 to 10"))
+                        })?;
+                }
+                _ => return Err(field.unknown()),
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_shortcut(&mut self, shortcut: ShortcutConfig) -> Result<(), String> {
+        if shortcut.enabled && shortcut.steps.is_empty() {
+            return Err(format!("Shortcut '{}' has no actions", shortcut.name));
+        }
+        if let Some(hotkey) = shortcut.hotkey
+            && shortcut.enabled
+            && let Some(other) = self
+                .shortcuts
+                .iter()
+                .find(|x| x.name != shortcut.name && x.hotkey == Some(hotkey))
+        {
+            return Err(format!(
+                "Hotkey {hotkey} is already used by shortcut '{}'",
+                other.name
+            ));
+        }
+        let existing = self.shortcuts.iter().position(|x| x.name == shortcut.name);
+        match (existing, shortcut.enabled) {
+            (Some(index), true) => self.shortcuts[index] = shortcut,
+            (Some(index), false) => {
+                self.shortcuts.remove(index);
+            }
+            (None, true) => self.shortcuts.push(shortcut),
+            (None, false) => {}
+        }
+        Ok(())
+    }
+
+    /// Writes the config as TOML. Shortcuts equal to a default are left out,
+    /// and removed defaults are written as disabled.
+    pub fn to_toml(&self) -> String {
+        let defaults = Config::default().shortcuts;
+        let mut res = String::new();
+        for device in &self.devices {
+            if device.name == DEFAULT_DEVICE {
+                let _ = writeln!(res, "[tv]");
+            } else {
+                let _ = writeln!(res, "\n[[devices]]");
+                let _ = writeln!(res, "name = {}", quote(&device.name));
+            }
+            let _ = writeln!(res, "ip = {}", quote(&device.ip));
+            if let Some(mac) = &device.mac {
+                let _ = writeln!(res, "mac = {}", quote(&format_mac_addr(mac)));
+            }
+            if let Some(password) = &device.wol_password {
+                let _ = writeln!(res, "wol_password = {}", quote(&format_password(password)));
+            }
+            if let Some(serial) = &device.serial {
+                let _ = writeln!(res, "serial = {}", quote(serial));
+            }
+            let input = device.input.map(|x| x.to_string()).unwrap_or_default();
+            let _ = writeln!(res, "input = {}", quote(&input));
+            let _ = writeln!(res, "wake_keys = {}", string_array(&device.wake_keys));
+            let _ = writeln!(res, "sleep_keys = {}", string_array(&device.sleep_keys));
+            let _ = writeln!(res, "ready_timeout = {}", device.ready_timeout.as_secs());
+            let _ = writeln!(res, "wol_interval = {}", device.wol_interval.as_secs());
+        }
+
+        let _ = writeln!(res, "\n[server]");
+        let ip = Ipv4Addr::from(self.server_addr).to_string();
+        let _ = writeln!(res, "ip = {}", quote(&ip));
+        let _ = writeln!(res, "port = {}", self.server_port);
+        if !self.listen_addrs.is_empty() {
+            let _ = writeln!(res, "listen = {}", string_array(&self.listen_addrs));
+        }
+        if let Some(token) = &self.api_token {
+            let _ = writeln!(res, "token = {}", quote(token));
+        }
+        if !self.allowed_clients.is_empty() {
+            let _ = writeln!(
+                res,
+                "allowed_clients = {}",
+                string_array(&self.allowed_clients)
+            );
+        }
+        if let Some(secret) = &self.hmac_secret {
+            let _ = writeln!(res, "hmac_secret = {}", quote(secret));
+        }
+        let _ = writeln!(res, "hmac_max_skew = {}", self.hmac_max_skew);
+
+        let _ = writeln!(res, "\n[capture]");
+        let _ = writeln!(res, "dir = {}", quote(&self.screen_dir));
+
+        let _ = writeln!(res, "\n[wol]");
+        if let Some(interface) = &self.wol.interface {
+            let _ = writeln!(res, "interface = {}", quote(&interface.to_string()));
+        }
+        if let Some(broadcast) = &self.wol.broadcast {
+            let _ = writeln!(res, "broadcast = {}", quote(&broadcast.to_string()));
+        }
+        let ports = self.wol.ports.iter().map(ToString::to_string);
+        let _ = writeln!(res, "ports = [{}]", ports.collect::<Vec<_>>().join(", "));
+        let _ = writeln!(res, "repeat = {}", self.wol.repeat);
+        if !self.wol.listen_ports.is_empty() {
+            let ports = self.wol.listen_ports.iter().map(ToString::to_string);
+            let _ = writeln!(
+                res,
+                "listen_ports = [{}]",
+                ports.collect::<Vec<_>>().join(", ")
+            );
+        }
+        if self.wol.relay {
+            let _ = writeln!(res, "relay = true");
+        }
+
+        for shortcut in &self.shortcuts {
+            if defaults.contains(shortcut) {
+                continue;
+            }
+            let _ = writeln!(res, "\n[[shortcuts]]");
+            let _ = writeln!(res, "name = {}", quote(&shortcut.name));
+            if let Some(device) = &shortcut.device {
+                let _ = writeln!(res, "device = {}", quote(device));
+            }
+            if let Some(menu) = &shortcut.menu {
+                let _ = writeln!(res, "menu = {}", quote(menu));
+            }
+            if let Some(route) = &shortcut.route {
+                let _ = writeln!(res, "route = {}", quote(route));
+            }
+            if shortcut.left_click {
+                let _ = writeln!(res, "left_click = true");
+            }
+            if let Some(hotkey) = &shortcut.hotkey {
+                let _ = writeln!(res, "hotkey = {}", quote(&hotkey.to_string()));
+            }
+            if shortcut.concurrency != Concurrency::Queue {
+                let _ = writeln!(
+                    res,
+                    "concurrency = {}",
+                    quote(&shortcut.concurrency.to_string())
+                );
+            }
+            let _ = writeln!(res, "actions = {}", string_array(&shortcut.steps));
+        }
+        for entry in &self.schedule {
+            let _ = writeln!(res, "\n[[schedule]]");
+            let _ = writeln!(res, "shortcut = {}", quote(&entry.shortcut));
+            if let Some(device) = &entry.device {
+                let _ = writeln!(res, "device = {}", quote(device));
+            }
+            let _ = match &entry.when {
+                When::Cron(cron) => writeln!(res, "cron = {}", quote(&cron.to_string())),
+                When::At(at) => writeln!(res, "at = {}", quote(&at.to_string())),
+            };
+        }
+        for shortcut in &defaults {
+            if !self.shortcuts.iter().any(|x| x.name == shortcut.name) {
+                let _ = writeln!(res, "\n[[shortcuts]]");
+                let _ = writeln!(res, "name = {}", quote(&shortcut.name));
+                let _ = writeln!(res, "enabled = false");
+            }
+        }
+        res
+    }
+}
+
+/// Reads a `[[devices]]` entry, which needs a name and an ip.
+fn parse_device_table(file: &str, table: &Table) -> Result<DeviceConfig, Error> {
+    let name = match table.entries.iter().find(|x| x.key == "name") {
+        Some(entry) => {
+            let field = Field::new(file, table, entry);
+            field.check(device_name(field.string()?))?
+        }
+        None => return Err(located(file, table.line, "devices", "Missing name")),
+    };
+    let mut device = DeviceConfig::new(&name);
+    parse_device(file, table, &mut device)?;
+    if device.ip.is_empty() {
+        return Err(located(
+            file,
+            table.line,
+            "devices",
+            format!("Device '{name}' has no ip"),
+        ));
+    }
+    Ok(device)
+}
+
+fn parse_device(file: &str, table: &Table, device: &mut DeviceConfig) -> Result<(), Error> {
+    for entry in &table.entries {
+        let field = Field::new(file, table, entry);
+        match entry.key.as_str() {
+            "name" if table.array => {}
+            "ip" => device.ip = field.check(device_ip(field.string()?))?,
+            "mac" => device.mac = Some(field.check(parse_mac_addr(field.string()?))?),
+            "wol_password" => {
+                device.wol_password = Some(field.check(parse_password(field.string()?))?);
+            }
+            "serial" => device.serial = non_empty(field.string()?),
+            "input" => {
+                device.input = match non_empty(field.string()?) {
+                    Some(value) => Some(field.check(value.parse::<AndroidKey>())?),
+                    None => None,
+                };
+            }
+            "wake_keys" => device.wake_keys = keycodes(&field)?,
+            "sleep_keys" => device.sleep_keys = keycodes(&field)?,
+            "ready_timeout" => {
+                let secs = field.check(seconds(field.integer()?))?;
+                device.ready_timeout = Duration::from_secs(secs);
+            }
+            "wol_interval" => {
+                let secs = field.check(seconds(field.integer()?))?;
+                device.wol_interval = Duration::from_secs(secs);
+            }
+            _ => return Err(field.unknown()),
+        }
+    }
+    Ok(())
+}
+
+fn ports(field: &Field) -> Result<Vec<u16>, Error> {
+    field
+        .integers()?
+        .into_iter()
+        .map(|x| field.check(port(x)))
+        .collect()
+}
+
+fn keycodes(field: &Field) -> Result<Vec<AndroidKey>, Error> {
+    field
+        .strings()?
+        .into_iter()
+        .map(|x| field.check(x.parse::<AndroidKey>()))
+        .collect()
+}
+
+fn parse_schedule(file: &str, table: &Table) -> Result<ScheduleConfig, Error> {
+    let mut shortcut = None;
+    let mut device = None;
+    let mut when = None;
+    for entry in &table.entries {
+        let field = Field::new(file, table, entry);
+        let value = match entry.key.as_str() {
+            "shortcut" => {
+                shortcut = Some(field.string()?.to_owned());
+                continue;
+            }
+            "device" => {
+                device = Some(field.string()?.to_owned());
+                continue;
+            }
+            "cron" => When::Cron(field.check(field.string()?.parse())?),
+            "at" => When::At(field.check(field.string()?.parse())?),
+            _ => return Err(field.unknown()),
+        };
+        if when.replace(value).is_some() {
+            return Err(field.error("Only one of cron and at may be set"));
+        }
+    }
+    let missing = |what| located(file, table.line, "schedule", what);
+    Ok(ScheduleConfig {
+        shortcut: shortcut.ok_or_else(|| missing("Missing shortcut"))?,
+        device,
+        when: when.ok_or_else(|| missing("Missing cron or at"))?,
+    })
+}
+
+fn parse_shortcut(file: &str, table: &Table) -> Result<ShortcutConfig, Error> {
+    let name = match table.entries.iter().find(|x| x.key == "name") {
+        Some(entry) => {
+            let field = Field::new(file, table, entry);
+            non_empty(field.string()?).ok_or_else(|| field.error("Must not be empty"))?
+        }
+        None => return Err(located(file, table.line, "shortcuts", "Missing name")),
+    };
+    let mut shortcut = ShortcutConfig::new(&name);
+    for entry in &table.entries {
+        let field = Field::new(file, table, entry);
+        match entry.key.as_str() {
+            "name" => {}
+            "device" => shortcut.device = Some(field.string()?.to_owned()),
+            "menu" => shortcut.menu = Some(field.string()?.to_owned()),
+            "route" => shortcut.route = Some(field.check(route(field.string()?))?),
+            "left_click" => shortcut.left_click = field.bool()?,
+            "hotkey" => shortcut.hotkey = Some(field.check(field.string()?.parse())?),
+            "enabled" => shortcut.enabled = field.bool()?,
+            "concurrency" => shortcut.concurrency = field.check(field.string()?.parse())?,
+            "actions" => {
+                shortcut.steps = field
+                    .strings()?
+                    .into_iter()
+                    .map(|x| field.check(x.parse::<Step>()))
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(field.unknown()),
+        }
+    }
+    Ok(shortcut)
+}
+
+/// A TOML entry being read, to report errors as `file:line: table.key: message`.
+struct Field<'a> {
+    file: &'a str,
+    table: &'a Table,
+    entry: &'a Entry,
+}
+
+impl<'a> Field<'a> {
+    fn new(file: &'a str, table: &'a Table, entry: &'a Entry) -> Self {
+        Self { file, table, entry }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        let name = format!("{}.{}", self.table.name, self.entry.key);
+        located(self.file, self.entry.line, &name, message)
+    }
+
+    fn unknown(&self) -> Error {
+        self.error("Unknown key")
+    }
+
+    fn check<T>(&self, result: Result<T, String>) -> Result<T, Error> {
+        result.map_err(|err| self.error(err))
+    }
+
+    fn expected(&self, what: &str) -> Error {
+        self.error(format!(
+            "Expected {what}, found {}",
+            self.entry.value.type_name()
+        ))
+    }
+
+    fn string(&self) -> Result<&'a str, Error> {
+        match &self.entry.value {
+            Value::String(value) => Ok(value),
+            _ => Err(self.expected("a string")),
+        }
+    }
+
+    fn integer(&self) -> Result<i64, Error> {
+        match &self.entry.value {
+            Value::Integer(value) => Ok(*value),
+            _ => Err(self.expected("an integer")),
+        }
+    }
+
+    fn bool(&self) -> Result<bool, Error> {
+        match &self.entry.value {
+            Value::Bool(value) => Ok(*value),
+            _ => Err(self.expected("true or false")),
+        }
+    }
+
+    fn integers(&self) -> Result<Vec<i64>, Error> {
+        match &self.entry.value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Integer(value) => Ok(*value),
+                    _ => Err(self.expected("an array of integers")),
+                })
+                .collect(),
+            _ => Err(self.expected("an array of integers")),
+        }
+    }
+
+    fn strings(&self) -> Result<Vec<&'a str>, Error> {
+        match &self.entry.value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(value) => Ok(value.as_str()),
+                    _ => Err(self.expected("an array of strings")),
+                })
+                .collect(),
+            _ => Err(self.expected("an array of strings")),
+        }
+    }
+}
+
+fn located(file: &str, line: usize, field: &str, message: impl std::fmt::Display) -> Error {
+    Error::config(format!("{file}:{line}: {field}: {message}"))
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|err| Error::Config {
+        context: format!("Fail to read {}", path.display()),
+        source: Some(err.into()),
+    })
+}
+
+fn string_array<T: ToString>(items: &[T]) -> String {
+    let items = items
+        .iter()
+        .map(|x| quote(&x.to_string()))
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+fn device_ip(value: &str) -> Result<String, String> {
+    match non_empty(value) {
+        Some(value) => Ok(value),
+        None => Err("Must not be empty".to_string()),
+    }
+}
+
+/// Device names appear in routes, so they are kept to letters, digits, `-` and `_`.
+fn device_name(value: &str) -> Result<String, String> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match value {
+        DEFAULT_DEVICE => Err(format!("'{DEFAULT_DEVICE}' is configured by [tv]")),
+        _ if valid => Ok(value.to_owned()),
+        _ => Err(format!(
+            "Invalid device name '{value}', use letters, digits, '-' and '_'"
+        )),
+    }
+}
+
+fn port(value: i64) -> Result<u16, String> {
+    u16::try_from(value)
+        .ok()
+        .filter(|x| *x != 0)
+        .ok_or_else(|| format!("Invalid port {value}, expected a number from 1 to 65535"))
+}
+
+fn seconds(value: i64) -> Result<u64, String> {
+    u64::try_from(value).map_err(|_| format!("Invalid duration {value}, expected seconds"))
+}
+
+fn listen_addr(value: &str) -> Result<IpAddr, String> {
+    value
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| format!("Invalid address '{value}'"))
+}
+
+fn route(value: &str) -> Result<String, String> {
+    if value.starts_with('/') {
+        Ok(value.to_owned())
+    } else {
+        Err(format!("Invalid route '{value}', routes start with '/'"))
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim().to_owned()).filter(|x| !x.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const LEGACY: &str =
+        "TV_IP::192.168.1.40\nPORT::9200\n\n[shortcut hello]\nROUTE::/hello\nACTION::echo hi\n";
+
+    /// An empty directory for one test, removed again by the caller.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("windows-shortcuts-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_converts_legacy_config() {
+        let dir = test_dir("convert");
+        fs::write(dir.join(LEGACY_CONFIG_FILE), LEGACY).unwrap();
+        let config = Config::load(&dir).unwrap();
+        assert_eq!(config.tv().ip, "192.168.1.40");
+        assert_eq!(config.server_port, 9200);
+        assert!(config.shortcuts.iter().any(|x| x.name == "hello"));
+        // read back from the converted config.toml
+        let converted = fs::read_to_string(dir.join(CONFIG_FILE)).unwrap();
+        assert_eq!(converted, config.to_toml());
+        assert_eq!(Config::load(&dir).unwrap().to_toml(), converted);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_without_migrating_writes_nothing() {
+        let dir = test_dir("check");
+        fs::write(dir.join(LEGACY_CONFIG_FILE), LEGACY).unwrap();
+        let config = Config::load_without_migrating(&dir).unwrap();
+        assert_eq!(config.tv().ip, "192.168.1.40");
+        assert!(!dir.join(CONFIG_FILE).exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn toml_wins_over_legacy_config() {
+        let dir = test_dir("both");
+        fs::write(dir.join(LEGACY_CONFIG_FILE), LEGACY).unwrap();
+        fs::write(dir.join(CONFIG_FILE), "[tv]\nip = \"192.168.1.50\"\n").unwrap();
+        assert_eq!(
+            Config::load_without_migrating(&dir).unwrap().tv().ip,
+            "192.168.1.50"
+        );
+        assert_eq!(Config::load(&dir).unwrap().tv().ip, "192.168.1.50");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn binds_server_ip_by_default() {
+        let config = Config::from_toml("[server]\nip = \"127.0.0.1\"\nport = 9200\n").unwrap();
+        assert_eq!(config.bind_addrs(), ["127.0.0.1:9200".parse().unwrap()]);
+    }
+
+    #[test]
+    fn binds_every_listen_address() {
+        let config = Config::from_toml(
+            "[server]\nip = \"127.0.0.1\"\nport = 9200\nlisten = [\"0.0.0.0\", \"[::1]\", \" ::\"]\n",
+        )
+        .unwrap();
+        let expected: [SocketAddr; 3] = [
+            "0.0.0.0:9200".parse().unwrap(),
+            "[::1]:9200".parse().unwrap(),
+            "[::]:9200".parse().unwrap(),
+        ];
+        assert_eq!(config.bind_addrs(), expected);
+        let err =
+            Config::from_toml("[server]\nlisten = [\"0.0.0.0\", \"localhost\"]\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "config.toml:2: server.listen: Invalid address 'localhost'"
+        );
+    }
+
+    #[test]
+    fn rejects_ports_out_of_range() {
+        for value in ["0", "65536", "-1"] {
+            let err = Config::from_toml(&format!("[server]\nport = {value}\n")).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "config.toml:2: server.port: Invalid port {value}, expected a number from 1 to 65535"
+                )
+            );
+        }
+        let config = Config::from_toml("[server]\nport = 65535\n").unwrap();
+        assert_eq!(config.server_port, 65535);
+    }
+
+    #[test]
+    fn locates_errors() {
+        let error = |content: &str| Config::from_toml(content).unwrap_err().to_string();
+        assert_eq!(
+            error("[tv]\nip = \"192.168.1.20\"\nbrightness = 3\n"),
+            "config.toml:3: tv.brightness: Unknown key"
+        );
+        assert_eq!(
+            error("[server]\nip = \"127.0.0.1\"\nport = \"9200\"\n"),
+            "config.toml:3: server.port: Expected an integer, found a string"
+        );
+        assert_eq!(
+            error("[wol]\nports = [9, 70000]\n"),
+            "config.toml:2: wol.ports: Invalid port 70000, expected a number from 1 to 65535"
+        );
+        assert_eq!(
+            error(
+                "[[devices]]\nname = \"projector\"\nip = \"192.168.1.30\"\n\nmac = \"aa:bb:cc:dd:ee\"\n"
+            ),
+            concat!(
+                "config.toml:5: devices.mac: Invalid MAC address 'aa:bb:cc:dd:ee', expected a form ",
+                "like aa:bb:cc:dd:ee:ff, aa-bb-cc-dd-ee-ff, aabb.ccdd.eeff or aabbccddeeff"
+            )
+        );
     }
 }
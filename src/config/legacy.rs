@@ -0,0 +1,142 @@
+//! Reads the `KEY::value` config.txt format, with `[shortcut <name>]` sections.
+
+use crate::error::Error;
+use crate::server::auth::Cidr;
+use crate::shortcuts::step::Step;
+use crate::utils::others::{parse_ip_addr, parse_mac_addr};
+
+use super::{
+    Config, ShortcutConfig, device_ip, listen_addr, located, non_empty, port, route, seconds,
+};
+
+const KEY_TV_IP: &str = "TV_IP";
+const KEY_TV_MAC: &str = "TV_MAC";
+const KEY_SERVER_IP: &str = "SERVER_IP";
+const KEY_SERVER_PORT: &str = "PORT";
+const KEY_LISTEN: &str = "LISTEN";
+const KEY_SCREEN_DIR: &str = "SCREEN_DIR";
+const KEY_API_TOKEN: &str = "API_TOKEN";
+const KEY_ALLOWED_CLIENTS: &str = "ALLOWED_CLIENTS";
+const KEY_HMAC_SECRET: &str = "HMAC_SECRET";
+const KEY_HMAC_MAX_SKEW: &str = "HMAC_MAX_SKEW";
+
+const SECTION_SHORTCUT: &str = "shortcut";
+const KEY_MENU: &str = "MENU";
+const KEY_ROUTE: &str = "ROUTE";
+const KEY_LEFT_CLICK: &str = "LEFT_CLICK";
+const KEY_ENABLED: &str = "ENABLED";
+const KEY_ACTION: &str = "ACTION";
+
+pub fn parse(config: &mut Config, file: &str, content: &str) -> Result<(), Error> {
+    let mut section: Option<(ShortcutConfig, usize)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            if let Some((prev, start)) = section.take() {
+                merge(config, file, prev, start)?;
+            }
+            let name = match header.split_once(char::is_whitespace) {
+                Some((SECTION_SHORTCUT, name)) if !name.trim().is_empty() => name.trim(),
+                _ => {
+                    return Err(Error::config(format!(
+                        "{file}:{line_no}: Invalid section [{header}], expected [shortcut <name>]"
+                    )));
+                }
+            };
+            section = Some((ShortcutConfig::new(name), line_no));
+            continue;
+        }
+        let Some((key, value)) = line.split_once("::") else {
+            if !line.is_empty() && !line.starts_with('#') {
+                return Err(Error::config(format!(
+                    "{file}:{line_no}: Expected KEY::value, found '{line}'"
+                )));
+            }
+            continue;
+        };
+        let value = value.trim();
+        let result = match section.as_mut() {
+            Some((shortcut, _)) => parse_shortcut_entry(shortcut, key, value),
+            None => parse_entry(config, key, value),
+        };
+        result.map_err(|err| located(file, line_no, key, err))?;
+    }
+    if let Some((prev, start)) = section.take() {
+        merge(config, file, prev, start)?;
+    }
+    Ok(())
+}
+
+fn merge(
+    config: &mut Config,
+    file: &str,
+    shortcut: ShortcutConfig,
+    line: usize,
+) -> Result<(), Error> {
+    let section = format!("[{SECTION_SHORTCUT} {}]", shortcut.name);
+    config
+        .merge_shortcut(shortcut)
+        .map_err(|err| located(file, line, &section, err))
+}
+
+fn parse_entry(config: &mut Config, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        KEY_TV_IP => config.tv_mut().ip = device_ip(value)?,
+        KEY_TV_MAC => config.tv_mut().mac = Some(parse_mac_addr(value)?),
+        KEY_SERVER_IP => config.server_addr = parse_ip_addr(value)?,
+        KEY_SERVER_PORT => {
+            let value = value
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid port '{value}'"))?;
+            config.server_port = port(value)?;
+        }
+        KEY_LISTEN => config.listen_addrs = list(value, listen_addr)?,
+        KEY_SCREEN_DIR => config.screen_dir = value.to_owned(),
+        KEY_API_TOKEN => config.api_token = non_empty(value),
+        KEY_ALLOWED_CLIENTS => config.allowed_clients = list(value, str::parse::<Cidr>)?,
+        KEY_HMAC_SECRET => config.hmac_secret = non_empty(value),
+        KEY_HMAC_MAX_SKEW => {
+            let value = value
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid duration '{value}', expected seconds"))?;
+            config.hmac_max_skew = seconds(value)?;
+        }
+        _ => return Err("Unknown key".to_string()),
+    }
+    Ok(())
+}
+
+fn parse_shortcut_entry(
+    shortcut: &mut ShortcutConfig,
+    key: &str,
+    value: &str,
+) -> Result<(), String> {
+    match key {
+        KEY_MENU => shortcut.menu = Some(value.to_owned()),
+        KEY_ROUTE => shortcut.route = Some(route(value)?),
+        KEY_LEFT_CLICK => shortcut.left_click = parse_bool(value)?,
+        KEY_ENABLED => shortcut.enabled = parse_bool(value)?,
+        KEY_ACTION => shortcut.steps.push(value.parse::<Step>()?),
+        _ => return Err("Unknown key".to_string()),
+    }
+    Ok(())
+}
+
+/// A comma-separated list, empty items are skipped.
+fn list<T>(value: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(parse)
+        .collect()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("Invalid value '{value}', expected true or false")),
+    }
+}
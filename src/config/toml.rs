@@ -0,0 +1,505 @@
+//! The subset of TOML the config needs: `[table]` and `[[array]]` headers,
+//! `key = value` pairs with strings, integers, booleans and arrays of those,
+//! and comments. Every table and entry keeps its line for error messages.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    /// Empty for the keys before the first header.
+    pub name: String,
+    /// Declared with `[[name]]`.
+    pub array: bool,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses a document into its tables, in file order.
+pub fn parse(content: &str) -> Result<Vec<Table>, ParseError> {
+    let mut parser = Parser {
+        src: content.as_bytes(),
+        pos: 0,
+        line: 1,
+    };
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        line: 1,
+        entries: vec![],
+    }];
+    loop {
+        parser.skip_blank_lines();
+        let Some(c) = parser.peek() else {
+            break;
+        };
+        if c == b'[' {
+            let table = parser.table_header()?;
+            if !table.array && tables.iter().any(|x| x.name == table.name) {
+                return Err(parser.error(format!("Table [{}] is defined twice", table.name)));
+            }
+            tables.push(table);
+        } else {
+            let entry = parser.entry()?;
+            let table = tables.last_mut().unwrap();
+            if table.entries.iter().any(|x| x.key == entry.key) {
+                return Err(parser.error(format!("Key '{}' is defined twice", entry.key)));
+            }
+            table.entries.push(entry);
+        }
+        parser.end_of_line()?;
+    }
+    Ok(tables)
+}
+
+/// Writes `text` as a basic string.
+pub fn quote(text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 2);
+    res.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => res.push_str(&format!("\\u{:04X}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            _ => Err(self.error(format!("Expected '{}'", expected as char))),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while !matches!(self.peek(), None | Some(b'\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, comments and newlines, as allowed between array items.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some(b'\n') => {
+                    self.bump();
+                }
+                Some(b'\r') if self.src.get(self.pos + 1) == Some(&b'\n') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some(b'\n') => Ok(()),
+            Some(b'\r') if self.src.get(self.pos + 1) == Some(&b'\n') => Ok(()),
+            Some(_) => Err(self.error("Unexpected text after value")),
+        }
+    }
+
+    fn table_header(&mut self) -> Result<Table, ParseError> {
+        let line = self.line;
+        self.expect(b'[')?;
+        let array = self.peek() == Some(b'[');
+        if array {
+            self.bump();
+        }
+        self.skip_spaces();
+        let mut name = self.key()?;
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some(b'.') {
+                break;
+            }
+            self.bump();
+            self.skip_spaces();
+            name.push('.');
+            name.push_str(&self.key()?);
+        }
+        self.expect(b']')?;
+        if array {
+            self.expect(b']')?;
+        }
+        Ok(Table {
+            name,
+            array,
+            line,
+            entries: vec![],
+        })
+    }
+
+    fn entry(&mut self) -> Result<Entry, ParseError> {
+        let line = self.line;
+        let key = self.key()?;
+        self.skip_spaces();
+        if self.peek() == Some(b'.') {
+            return Err(self.error("Dotted keys are not supported, use a [table]"));
+        }
+        self.expect(b'=')?;
+        self.skip_spaces();
+        let value = self.value()?;
+        Ok(Entry { key, value, line })
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(b'"') => self.basic_string(),
+            Some(b'\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+                {
+                    self.bump();
+                }
+                if start == self.pos {
+                    return Err(self.error("Expected a key"));
+                }
+                Ok(String::from_utf8_lossy(&self.src[start..self.pos]).into_owned())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some(b'"') => self.basic_string().map(Value::String),
+            Some(b'\'') => self.literal_string().map(Value::String),
+            Some(b'[') => self.array(),
+            Some(b'{') => Err(self.error("Inline tables are not supported, use a [table]")),
+            Some(b't' | b'f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(self.error(format!("Invalid value '{word}'"))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == b'-' || c == b'+' => {
+                let word = self.word();
+                word.replace('_', "")
+                    .parse::<i64>()
+                    .map(Value::Integer)
+                    .map_err(|_| self.error(format!("Invalid integer '{word}'")))
+            }
+            Some(b'\n' | b'\r') | None => Err(self.error("Missing value")),
+            Some(c) => {
+                let word = self.word();
+                if word.is_empty() {
+                    return Err(self.error(format!("Unexpected '{}'", c as char)));
+                }
+                Err(self.error(format!("Invalid value '{word}'")))
+            }
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if !c.is_ascii_whitespace() && !matches!(c, b',' | b']' | b'#'))
+        {
+            self.bump();
+        }
+        String::from_utf8_lossy(&self.src[start..self.pos]).into_owned()
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.expect(b'[')?;
+        let mut items = vec![];
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(b']') {
+                self.bump();
+                break;
+            }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.bump() {
+                Some(b',') => {}
+                Some(b']') => break,
+                _ => return Err(self.error("Expected ',' or ']' in array")),
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn literal_string(&mut self) -> Result<String, ParseError> {
+        self.expect(b'\'')?;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'\'') => break,
+                None | Some(b'\n') => return Err(self.error("Unterminated string")),
+                Some(_) => {
+                    self.bump();
+                }
+            }
+        }
+        let res = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+        self.bump();
+        Ok(res)
+    }
+
+    fn basic_string(&mut self) -> Result<String, ParseError> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            let c = match self.peek() {
+                None | Some(b'\n') => return Err(self.error("Unterminated string")),
+                Some(c) => c,
+            };
+            self.bump();
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.bump() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'u') => self.unicode_escape(4)?,
+                        Some(b'U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("Invalid escape in string")),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("String is not valid UTF-8"))
+    }
+
+    fn unicode_escape(&mut self, len: usize) -> Result<char, ParseError> {
+        let digits = self.src.get(self.pos..self.pos + len).unwrap_or_default();
+        let c = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|x| u32::from_str_radix(x, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        self.pos += len;
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(content: &str) -> Vec<(String, Value)> {
+        let tables = parse(content).unwrap();
+        tables
+            .into_iter()
+            .flat_map(|x| x.entries)
+            .map(|x| (x.key, x.value))
+            .collect()
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn parses_strings() {
+        let parsed = values(concat!(
+            r#"basic = "say \"hi\"\t\\ \u00e9\U0001F4FA\n""#,
+            "\n",
+            r#"literal = 'C:\shots\ "no" escapes'"#,
+            "\n",
+            r#""quoted key" = ''"#,
+            "\n",
+        ));
+        assert_eq!(
+            parsed,
+            [
+                ("basic".to_string(), string("say \"hi\"\t\\ é📺\n")),
+                ("literal".to_string(), string(r#"C:\shots\ "no" escapes"#)),
+                ("quoted key".to_string(), string("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_strings() {
+        let err = |content| parse(content).unwrap_err();
+        assert_eq!(err("a = \"\\q\"").message, "Invalid escape in string");
+        assert_eq!(err("a = \"\\u12\"").message, "Invalid unicode escape");
+        assert_eq!(err("a = \"\\uD800\"").message, "Invalid unicode escape");
+        assert_eq!(err("a = \"open\nb = 1").message, "Unterminated string");
+        assert_eq!(err("a = 'open").message, "Unterminated string");
+    }
+
+    #[test]
+    fn parses_arrays() {
+        let parsed = values("a = [1, -2, +3_000,]\nb = [\n  'x', # first\n  \"y\",\n]\nc = []\n");
+        assert_eq!(
+            parsed,
+            [
+                (
+                    "a".to_string(),
+                    Value::Array(vec![
+                        Value::Integer(1),
+                        Value::Integer(-2),
+                        Value::Integer(3000)
+                    ])
+                ),
+                (
+                    "b".to_string(),
+                    Value::Array(vec![string("x"), string("y")])
+                ),
+                ("c".to_string(), Value::Array(vec![])),
+            ]
+        );
+        assert_eq!(
+            parse("a = [1 2]").unwrap_err().message,
+            "Expected ',' or ']' in array"
+        );
+        assert_eq!(parse("a = [1,,]").unwrap_err().message, "Unexpected ','");
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let err = parse("[tv]\nip = \"a\"\nip = \"b\"\n").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 3,
+                message: "Key 'ip' is defined twice".to_string()
+            }
+        );
+        let err = parse("[tv]\nip = \"a\"\n\n[tv]\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 4: Table [tv] is defined twice");
+        // the same key in different tables, and repeated array tables
+        let tables =
+            parse("ip = 1\n[tv]\nip = 2\n[[devices]]\nip = 3\n[[devices]]\nip = 4\n").unwrap();
+        assert_eq!(tables.len(), 4);
+    }
+
+    #[test]
+    fn reads_crlf_input() {
+        let content = "# comment\r\n[server]\r\nport = 9200 # inline\r\n\r\nlisten = [\r\n  \"::1\",\r\n]\r\n[[devices]]\r\nname = 'tv'\r\n";
+        let tables = parse(content).unwrap();
+        let names: Vec<_> = tables
+            .iter()
+            .map(|x| (x.name.as_str(), x.array, x.line))
+            .collect();
+        assert_eq!(
+            names,
+            [("", false, 1), ("server", false, 2), ("devices", true, 8)]
+        );
+        assert_eq!(tables[1].entries[0].value, Value::Integer(9200));
+        assert_eq!(
+            tables[1].entries[1].value,
+            Value::Array(vec![string("::1")])
+        );
+        assert_eq!(tables[2].entries[0].value, string("tv"));
+    }
+
+    #[test]
+    fn keeps_line_numbers() {
+        let content =
+            "\n# devices\n[[devices]]\nname = \"tv\"\nkeys = [\n  1,\n  2,\n]\nmac = \"x\"\n";
+        let tables = parse(content).unwrap();
+        assert_eq!(tables[1].line, 3);
+        let lines: Vec<_> = tables[1].entries.iter().map(|x| x.line).collect();
+        assert_eq!(lines, [4, 5, 9]);
+        assert_eq!(parse("a = 1\n\nb = nope\n").unwrap_err().line, 3);
+        assert_eq!(
+            parse("a = 1\nb = [\n 1,\n x]\n").unwrap_err().to_string(),
+            "line 4: Invalid value 'x'"
+        );
+    }
+
+    #[test]
+    fn quotes_round_trip() {
+        let text = "tab\there \"quoted\" \\ \r\n\u{1}é";
+        let parsed = values(&format!("a = {}\n", quote(text)));
+        assert_eq!(parsed, [("a".to_string(), string(text))]);
+    }
+}
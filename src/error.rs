@@ -0,0 +1,166 @@
+use std::any::Any;
+use std::fmt;
+use std::time::Duration;
+
+use crate::utils::adb::AdbError;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors surfaced by shortcuts and the helpers behind them.
+#[derive(Debug)]
+pub enum Error {
+    Config {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Network {
+        context: String,
+        source: std::io::Error,
+    },
+    Adb(AdbError),
+    Registry {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Capture {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Display {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Input {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Process {
+        context: String,
+        source: Option<BoxError>,
+    },
+    Timeout {
+        context: String,
+        after: Duration,
+    },
+    /// The job running the shortcut was cancelled.
+    Cancelled,
+    /// An action panicked, with the panic message.
+    Panicked(String),
+}
+
+impl Error {
+    pub fn config(context: impl Into<String>) -> Self {
+        Error::Config {
+            context: context.into(),
+            source: None,
+        }
+    }
+
+    pub fn network(context: impl Into<String>, source: std::io::Error) -> Self {
+        Error::Network {
+            context: context.into(),
+            source,
+        }
+    }
+
+    pub fn registry(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Error::Registry {
+            context: context.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn capture(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Error::Capture {
+            context: context.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn display(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Error::Display {
+            context: context.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn input(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Error::Input {
+            context: context.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn process(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Error::Process {
+            context: context.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn timeout(context: impl Into<String>, after: Duration) -> Self {
+        Error::Timeout {
+            context: context.into(),
+            after,
+        }
+    }
+
+    /// From what `catch_unwind` caught.
+    pub fn panicked(payload: Box<dyn Any + Send>) -> Self {
+        let msg = match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(msg) => msg.to_string(),
+                Err(_) => "unknown reason".to_string(),
+            },
+        };
+        Error::Panicked(msg)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Adb(err) => write!(f, "{err}"),
+            Error::Network { context, source } => write!(f, "{context}, {source}"),
+            Error::Cancelled => write!(f, "Cancelled"),
+            Error::Panicked(msg) => write!(f, "Panicked, {msg}"),
+            Error::Timeout { context, after } => {
+                write!(f, "{context}, timed out after {}s", after.as_secs_f32())
+            }
+            Error::Config { context, source }
+            | Error::Registry { context, source }
+            | Error::Capture { context, source }
+            | Error::Display { context, source }
+            | Error::Input { context, source }
+            | Error::Process { context, source } => match source {
+                Some(source) => write!(f, "{context}, {source}"),
+                None => write!(f, "{context}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Adb(err) => Some(err),
+            Error::Network { source, .. } => Some(source),
+            Error::Timeout { .. } | Error::Cancelled | Error::Panicked(_) => None,
+            Error::Config { source, .. }
+            | Error::Registry { source, .. }
+            | Error::Capture { source, .. }
+            | Error::Display { source, .. }
+            | Error::Input { source, .. }
+            | Error::Process { source, .. } => source
+                .as_deref()
+                .map(|x| x as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+impl From<AdbError> for Error {
+    fn from(err: AdbError) -> Self {
+        Error::Adb(err)
+    }
+}
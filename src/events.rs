@@ -0,0 +1,126 @@
+pub mod devices;
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, SystemTime};
+
+use crate::shortcuts::ActionOutput;
+
+/// Events a subscriber may fall behind by before it is dropped.
+const BACKLOG: usize = 256;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_SUBSCRIBER: AtomicU64 = AtomicU64::new(1);
+/// By subscription id.
+static SUBSCRIBERS: Mutex<Vec<(u64, mpsc::SyncSender<Arc<Event>>)>> = Mutex::new(vec![]);
+
+/// Something that happened in the app, streamed by `GET /api/events`.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: u64,
+    pub at: SystemTime,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum EventKind {
+    ShortcutStarted {
+        job: u64,
+        shortcut: String,
+        device: String,
+    },
+    ShortcutFinished {
+        job: u64,
+        shortcut: String,
+        device: String,
+        output: ActionOutput,
+    },
+    /// The shortcut failed or was cancelled.
+    ShortcutFailed {
+        job: u64,
+        shortcut: String,
+        device: String,
+        error: String,
+    },
+    ConfigReloaded {
+        shortcuts: usize,
+    },
+    ScreenshotSaved {
+        path: PathBuf,
+    },
+    DeviceReachable {
+        device: String,
+    },
+    DeviceUnreachable {
+        device: String,
+    },
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::ShortcutStarted { .. } => "shortcut_started",
+            EventKind::ShortcutFinished { .. } => "shortcut_finished",
+            EventKind::ShortcutFailed { .. } => "shortcut_failed",
+            EventKind::ConfigReloaded { .. } => "config_reloaded",
+            EventKind::ScreenshotSaved { .. } => "screenshot_saved",
+            EventKind::DeviceReachable { .. } => "device_reachable",
+            EventKind::DeviceUnreachable { .. } => "device_unreachable",
+        }
+    }
+}
+
+/// Receives the events published from its creation on, until dropped.
+pub struct Subscription {
+    id: u64,
+    events: mpsc::Receiver<Arc<Event>>,
+}
+
+impl Subscription {
+    /// Waits up to `timeout` for the next event. Fails once the subscriber fell
+    /// too far behind and was dropped.
+    pub fn next(&self, timeout: Duration) -> Result<Option<Arc<Event>>, mpsc::RecvError> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(mpsc::RecvError),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        SUBSCRIBERS.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Hands the event to every subscriber.
+pub fn publish(kind: EventKind) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    let event = Arc::new(Event {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        at: SystemTime::now(),
+        kind,
+    });
+    subscribers.retain(|(_, tx)| tx.try_send(event.clone()).is_ok());
+}
+
+/// Subscribes unless there are `limit` subscribers already.
+pub fn subscribe(limit: usize) -> Option<Subscription> {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.len() >= limit {
+        return None;
+    }
+    let id = NEXT_SUBSCRIBER.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = mpsc::sync_channel(BACKLOG);
+    subscribers.push((id, tx));
+    Some(Subscription { id, events: rx })
+}
+
+pub fn subscribers() -> usize {
+    SUBSCRIBERS.lock().unwrap().len()
+}
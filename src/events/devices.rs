@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::app_config;
+use crate::utils::adb::wait_for_device;
+
+use super::{EventKind, publish, subscribers};
+
+/// How often devices are probed while someone listens to events.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How often to check whether someone started listening.
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Probes the adb port of every device while there are subscribers, and
+/// publishes when one becomes reachable or unreachable. The first probe after
+/// someone subscribed reports every device.
+pub fn watch() {
+    thread::spawn(|| {
+        let mut reachable = HashMap::<String, bool>::new();
+        loop {
+            if subscribers() == 0 {
+                reachable.clear();
+                thread::sleep(IDLE_INTERVAL);
+                continue;
+            }
+            let config = app_config();
+            reachable.retain(|name, _| config.device(name).is_some());
+            for device in &config.devices {
+                let up = wait_for_device(device.adb_serial(), PROBE_TIMEOUT, || {}).is_ok();
+                if reachable.insert(device.name.clone(), up) == Some(up) {
+                    continue;
+                }
+                let device = device.name.clone();
+                if up {
+                    publish(EventKind::DeviceReachable { device });
+                } else {
+                    publish(EventKind::DeviceUnreachable { device });
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,443 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::thread;
+
+use crate::cli::{Command, EXIT_USAGE, Output, execute};
+use crate::log;
+
+#[cfg(unix)]
+use std::{
+    fs,
+    io::ErrorKind,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+#[cfg(windows)]
+use std::{
+    fs::{File, OpenOptions},
+    io::ErrorKind,
+    os::windows::io::{AsRawHandle, FromRawHandle},
+    time::Duration,
+};
+#[cfg(windows)]
+use windows::Win32::Foundation::{ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, HANDLE};
+#[cfg(windows)]
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+#[cfg(windows)]
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+    PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+#[cfg(windows)]
+use windows::core::HSTRING;
+
+// A second instance sends its arguments one per line and an empty line. The
+// running one answers with `out <text>` and `err <text>` lines, then `exit <code>`.
+
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\WindowsShortcuts";
+#[cfg(windows)]
+const PIPE_BUFFER_SIZE: u32 = 4096;
+/// How often a client tries again while every pipe instance is taken.
+#[cfg(windows)]
+const PIPE_BUSY_RETRIES: usize = 20;
+#[cfg(unix)]
+const SOCKET_NAME: &str = "windows-shortcuts.sock";
+
+/// Passes a command line to the running instance and prints its output to
+/// `output`. Returns its exit code, or `None` when no instance is running.
+pub fn forward(args: &[String], output: &mut dyn Output) -> Result<Option<i32>, String> {
+    if args
+        .iter()
+        .any(|x| x.is_empty() || x.contains(['\r', '\n']))
+    {
+        return Err(
+            "Arguments passed to the running instance can't be empty or span lines".to_string(),
+        );
+    }
+    let Some(stream) = connect()? else {
+        return Ok(None);
+    };
+    exchange(stream, args, output).map(Some)
+}
+
+/// Sends the arguments over `stream` and relays the reply to `output`, up to the exit code.
+fn exchange(
+    mut stream: impl Read + Write,
+    args: &[String],
+    output: &mut dyn Output,
+) -> Result<i32, String> {
+    let request = format!("{}\n\n", args.join("\n"));
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|err| format!("Fail to pass the command to the running instance, {err}"))?;
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|err| format!("Fail to read from the running instance, {err}"))?;
+        match line.split_once(' ') {
+            Some(("out", text)) => output.out(text),
+            Some(("err", text)) => output.err(text),
+            Some(("exit", code)) => {
+                return code
+                    .parse()
+                    .map_err(|_| format!("Invalid exit code '{code}' from the running instance"));
+            }
+            _ => {
+                return Err(format!(
+                    "Unexpected reply '{line}' from the running instance"
+                ));
+            }
+        }
+    }
+    Err("The running instance closed the connection".to_string())
+}
+
+/// Runs the command line a second instance sent and streams the output back.
+fn serve_client(stream: impl Read + Write) {
+    let mut reader = BufReader::new(stream);
+    let mut args = vec![];
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        args.push(line.to_string());
+    }
+    log!("Running '{}' for another instance", args.join(" "));
+    let mut reply = Reply(reader.into_inner());
+    let code = match Command::parse(args) {
+        Ok(command) => execute(&command, &mut reply),
+        Err(err) => {
+            reply.err(&err);
+            EXIT_USAGE
+        }
+    };
+    let _ = writeln!(reply.0, "exit {code}");
+}
+
+struct Reply<W: Write>(W);
+
+impl<W: Write> Output for Reply<W> {
+    fn out(&mut self, text: &str) {
+        for line in text.lines() {
+            let _ = writeln!(self.0, "out {line}");
+        }
+    }
+
+    fn err(&mut self, text: &str) {
+        for line in text.lines() {
+            let _ = writeln!(self.0, "err {line}");
+        }
+    }
+}
+
+/// Takes command lines from other instances. Fails when another instance already does.
+#[cfg(unix)]
+pub fn listen() -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if connect()?.is_some() {
+        return Err("Another instance is running. This instance will abort.".to_string());
+    }
+    let path = socket_path()?;
+    // Left behind by an instance that was killed
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .map_err(|err| format!("Fail to listen on {}, {err}", path.display()))?;
+    // Only the user running the app may send it commands
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .map_err(|err| format!("Fail to restrict {}, {err}", path.display()))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve_client(stream));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn connect() -> Result<Option<UnixStream>, String> {
+    match UnixStream::connect(socket_path()?) {
+        Ok(stream) => Ok(Some(stream)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::NotFound | ErrorKind::ConnectionRefused
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(format!("Fail to reach the running instance, {err}")),
+    }
+}
+
+/// In `$XDG_RUNTIME_DIR`, else in a directory of the user's own under the
+/// temp dir, so other users can neither take the name nor reach the socket.
+#[cfg(unix)]
+fn socket_path() -> Result<PathBuf, String> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|x| !x.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let uid = unsafe { getuid() };
+            private_dir(
+                &std::env::temp_dir().join(format!("windows-shortcuts-{uid}")),
+                uid,
+            )?
+        }
+    };
+    Ok(dir.join(SOCKET_NAME))
+}
+
+#[cfg(unix)]
+unsafe extern "C" {
+    fn getuid() -> u32;
+}
+
+/// Creates `dir` for `uid` alone, or checks that the existing one is.
+#[cfg(unix)]
+fn private_dir(dir: &Path, uid: u32) -> Result<PathBuf, String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(format!("Fail to create {}, {err}", dir.display())),
+    }
+    // not following links, a link could point anywhere
+    let meta = fs::symlink_metadata(dir)
+        .map_err(|err| format!("Fail to read {}, {err}", dir.display()))?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(format!(
+            "{} is not a directory only this user can access, remove it",
+            dir.display()
+        ));
+    }
+    Ok(dir.to_path_buf())
+}
+
+/// Takes command lines from other instances. Fails when another instance already does.
+#[cfg(windows)]
+pub fn listen() -> Result<(), String> {
+    let mut pipe = create_pipe(true)?;
+    thread::spawn(move || {
+        loop {
+            match unsafe { ConnectNamedPipe(HANDLE(pipe.as_raw_handle()), None) } {
+                Ok(()) => {}
+                // The client connected before the wait started
+                Err(err) if err.code() == ERROR_PIPE_CONNECTED.to_hresult() => {}
+                Err(err) => {
+                    log!("Fail to accept a pipe client, {err}");
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            }
+            let next = match create_pipe(false) {
+                Ok(next) => next,
+                Err(err) => {
+                    log!("{err}");
+                    return;
+                }
+            };
+            let client = std::mem::replace(&mut pipe, next);
+            thread::spawn(move || {
+                serve_client(&client);
+                // Waits for the client to read the reply before the pipe closes
+                let _ = client.sync_all();
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+fn create_pipe(first: bool) -> Result<File, String> {
+    let mut mode = PIPE_ACCESS_DUPLEX;
+    if first {
+        mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+    }
+    let handle = unsafe {
+        CreateNamedPipeW(
+            &HSTRING::from(PIPE_NAME),
+            mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            None,
+        )
+    };
+    if handle.is_invalid() {
+        let err = windows::core::Error::from_win32();
+        return Err(format!("Fail to create pipe {PIPE_NAME}, {err}"));
+    }
+    Ok(unsafe { File::from_raw_handle(handle.0) })
+}
+
+#[cfg(windows)]
+fn connect() -> Result<Option<File>, String> {
+    for _ in 0..PIPE_BUSY_RETRIES {
+        match OpenOptions::new().read(true).write(true).open(PIPE_NAME) {
+            Ok(file) => return Ok(Some(file)),
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32) => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(err) => return Err(format!("Fail to reach the running instance, {err}")),
+        }
+    }
+    Err("The running instance is busy".to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::net::Shutdown;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    use super::*;
+    use crate::cli::{EXIT_FAILED, EXIT_OK, tests::Recorded};
+    use crate::config::Config;
+    use crate::platform::mock;
+
+    const CONFIG: &str = r#"
+[[devices]]
+name = "projector"
+ip = "192.168.1.30"
+
+[[shortcuts]]
+name = "where"
+actions = ["echo {device}\nat {device_ip}"]
+
+[[shortcuts]]
+name = "desk"
+actions = ["send_keys ctrl+alt+t"]
+"#;
+
+    /// Sends a raw request to `serve_client` over a socket pair and returns the raw reply.
+    fn send(request: &str) -> String {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        let served = thread::spawn(move || serve_client(server));
+        client.write_all(request.as_bytes()).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        served.join().unwrap();
+        reply
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn serves_command_lines() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        assert_eq!(
+            send("run\nwhere\r\n--device\nprojector\n\n"),
+            "out projector\nout at 192.168.1.30\nexit 0\n"
+        );
+        mock.update(|x| x.fail_next = Some("no desktop".to_string()));
+        assert_eq!(
+            send("run\ndesk\n\n"),
+            "err desk failed, Fail to send input, no desktop\nexit 1\n"
+        );
+        assert_eq!(
+            send("run\nnope\n\n"),
+            "err Unknown shortcut 'nope', `list` shows them\nexit 2\n"
+        );
+        // a client that leaves before finishing the request
+        assert_eq!(send("run\nwhere\n"), "");
+    }
+
+    #[test]
+    fn answers_bad_arguments_with_usage_code() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        assert_eq!(
+            send("run\n\n"),
+            format!("err Invalid arguments for 'run'\nexit {EXIT_USAGE}\n")
+        );
+        assert_eq!(
+            send("events\n\n"),
+            format!(
+                "err Only run and list are passed to the running instance\nexit {EXIT_USAGE}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn forwards_and_relays_the_reply() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let exchange_with_server = |args: &[String]| {
+            let (client, server) = UnixStream::pair().unwrap();
+            let served = thread::spawn(move || serve_client(server));
+            let mut output = Recorded::default();
+            let code = exchange(client, args, &mut output);
+            served.join().unwrap();
+            (code, output)
+        };
+        let (code, output) = exchange_with_server(&args(&["run", "where"]));
+        assert_eq!(code, Ok(EXIT_OK));
+        assert_eq!(output.out, ["tv", "at 192.168.1.20"]);
+        mock.update(|x| x.fail_next = Some("no desktop".to_string()));
+        let (code, output) = exchange_with_server(&args(&["run", "desk"]));
+        assert_eq!(code, Ok(EXIT_FAILED));
+        assert_eq!(output.err, ["desk failed, Fail to send input, no desktop"]);
+    }
+
+    #[test]
+    fn exchange_rejects_bad_replies() {
+        for reply in ["out hi\n", "exit zero\n", "hello\n"] {
+            let (client, mut server) = UnixStream::pair().unwrap();
+            server.write_all(reply.as_bytes()).unwrap();
+            drop(server);
+            let err = exchange(client, &args(&["list"]), &mut Recorded::default());
+            assert!(err.is_err(), "{reply}");
+        }
+    }
+
+    #[test]
+    fn forward_rejects_arguments_spanning_lines() {
+        for bad in ["two\nlines", "cr\r", ""] {
+            let err = forward(&args(&["run", bad]), &mut Recorded::default()).unwrap_err();
+            assert_eq!(
+                err,
+                "Arguments passed to the running instance can't be empty or span lines"
+            );
+        }
+    }
+
+    #[test]
+    fn private_dir_is_only_for_its_user() {
+        let uid = unsafe { getuid() };
+        let root =
+            std::env::temp_dir().join(format!("windows-shortcuts-{}-ipc", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let dir = root.join("new");
+        assert_eq!(private_dir(&dir, uid), Ok(dir.clone()));
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        // created before
+        assert_eq!(private_dir(&dir, uid), Ok(dir.clone()));
+        // someone else's
+        assert!(private_dir(&dir, uid + 1).is_err());
+
+        let open = root.join("open");
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(private_dir(&open, uid).is_err());
+
+        let link = root.join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(private_dir(&link, uid).is_err());
+
+        let file = root.join("file");
+        fs::write(&file, "").unwrap();
+        assert!(private_dir(&file, uid).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
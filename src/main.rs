@@ -1,29 +1,25 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use app::start_app;
-use utils::instance::SingleInstance;
-
 pub mod app;
+pub mod cli;
 pub mod config;
 pub mod constants;
+pub mod error;
+pub mod events;
+pub mod ipc;
+pub mod platform;
+pub mod reload;
+pub mod schedule;
 pub mod screen;
 pub mod server;
 pub mod shortcuts;
+#[cfg(windows)]
 pub mod startup;
+#[cfg(windows)]
 pub mod trayicon;
 pub mod utils;
+pub mod wol;
 
 fn main() {
-    if let Err(err) = run() {
-        alert!("{err}");
-        std::process::exit(1);
-    }
-}
-
-fn run() -> Result<(), String> {
-    let instance = SingleInstance::create("WindowSwitcherMutex")?;
-    if !instance.is_single() {
-        return Err("Another instance is running. This instance will abort.".to_string());
-    }
-    start_app()
+    std::process::exit(cli::main(std::env::args().skip(1)));
 }
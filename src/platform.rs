@@ -0,0 +1,83 @@
+#[cfg(any(test, not(windows)))]
+pub mod mock;
+#[cfg(windows)]
+mod win32;
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::error::Error;
+use crate::screen::modes::CaptureMode;
+use crate::utils::monitors::DisplayTopology;
+
+/// The backend the OS-facing actions go through, set on first use.
+static PLATFORM: RwLock<Option<Arc<dyn Platform>>> = RwLock::new(None);
+
+pub trait Displays {
+    fn set_display_topology(&self, topology: DisplayTopology) -> Result<(), Error>;
+}
+
+pub trait Input {
+    /// Presses the virtual keys in order, then releases them in reverse order.
+    fn send_keys(&self, keys: &[u16]) -> Result<(), Error>;
+}
+
+pub trait Clipboard {
+    fn clear_clipboard(&self) -> Result<(), Error>;
+}
+
+pub trait Processes {
+    /// Ends explorer.exe, which Windows restarts.
+    fn kill_explorer(&self) -> Result<(), Error>;
+}
+
+/// Binary values under the current user's settings, the registry on Windows.
+pub trait Settings {
+    fn read_setting(&self, key: &str, name: &str) -> Result<Option<Vec<u8>>, Error>;
+    fn write_setting(&self, key: &str, name: &str, value: &[u8]) -> Result<(), Error>;
+}
+
+pub trait ScreenCapture {
+    /// Saves a PNG of the screen or window to `dir` and returns its path.
+    fn take_screenshot(&self, dir: &str, mode: &CaptureMode) -> Result<PathBuf, Error>;
+}
+
+/// Messages for the user, message boxes next to the tray icon on Windows.
+pub trait Tray {
+    fn alert(&self, text: &str);
+    fn inform(&self, text: &str);
+}
+
+pub trait Platform:
+    Displays + Input + Clipboard + Processes + Settings + ScreenCapture + Tray + Send + Sync
+{
+}
+
+impl<T> Platform for T where
+    T: Displays + Input + Clipboard + Processes + Settings + ScreenCapture + Tray + Send + Sync
+{
+}
+
+pub fn platform() -> Arc<dyn Platform> {
+    if let Some(platform) = PLATFORM.read().unwrap().as_ref() {
+        return platform.clone();
+    }
+    PLATFORM.write().unwrap().get_or_insert_with(native).clone()
+}
+
+/// Replaces the backend, e.g. with a `MockPlatform` in tests.
+#[cfg_attr(not(test), allow(unused))]
+pub fn set_platform(platform: Arc<dyn Platform>) {
+    *PLATFORM.write().unwrap() = Some(platform);
+}
+
+#[cfg(windows)]
+fn native() -> Arc<dyn Platform> {
+    Arc::new(win32::Win32)
+}
+
+#[cfg(not(windows))]
+fn native() -> Arc<dyn Platform> {
+    crate::log!("No desktop backend for this OS, display, input and capture actions are simulated");
+    Arc::new(mock::MockPlatform::default())
+}
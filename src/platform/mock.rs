@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+#[cfg(test)]
+use std::sync::{Arc, MutexGuard};
+
+use crate::error::Error;
+use crate::log;
+use crate::screen::modes::CaptureMode;
+use crate::utils::monitors::DisplayTopology;
+
+use super::{Clipboard, Displays, Input, Processes, ScreenCapture, Settings, Tray};
+
+/// What the actions did to a `MockPlatform`.
+#[derive(Debug, Clone, Default)]
+pub struct MockState {
+    pub topology: Option<DisplayTopology>,
+    pub keys: Vec<Vec<u16>>,
+    pub clipboard_clears: usize,
+    pub explorer_kills: usize,
+    /// By key and value name.
+    pub settings: HashMap<(String, String), Vec<u8>>,
+    pub screenshots: Vec<PathBuf>,
+    pub alerts: Vec<String>,
+    pub infos: Vec<String>,
+    /// The next call fails with this message.
+    pub fail_next: Option<String>,
+    /// The next call panics with this message.
+    pub panic_next: Option<String>,
+}
+
+/// Keeps everything in memory, for running the shortcut engine off Windows.
+#[derive(Default)]
+pub struct MockPlatform {
+    state: Mutex<MockState>,
+}
+
+impl MockPlatform {
+    pub fn state(&self) -> MockState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Changes the state, e.g. to seed settings or make the next call fail.
+    pub fn update(&self, f: impl FnOnce(&mut MockState)) {
+        f(&mut self.state.lock().unwrap());
+    }
+
+    fn call<T>(
+        &self,
+        error: fn(String) -> Error,
+        f: impl FnOnce(&mut MockState) -> T,
+    ) -> Result<T, Error> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(msg) = state.panic_next.take() {
+            drop(state);
+            panic!("{msg}");
+        }
+        match state.fail_next.take() {
+            Some(msg) => Err(error(msg)),
+            None => Ok(f(&mut state)),
+        }
+    }
+}
+
+impl Displays for MockPlatform {
+    fn set_display_topology(&self, topology: DisplayTopology) -> Result<(), Error> {
+        self.call(
+            |msg| Error::display("Fail to switch display", msg),
+            |state| state.topology = Some(topology),
+        )
+    }
+}
+
+impl Input for MockPlatform {
+    fn send_keys(&self, keys: &[u16]) -> Result<(), Error> {
+        self.call(
+            |msg| Error::input("Fail to send input", msg),
+            |state| state.keys.push(keys.to_vec()),
+        )
+    }
+}
+
+impl Clipboard for MockPlatform {
+    fn clear_clipboard(&self) -> Result<(), Error> {
+        self.call(
+            |msg| Error::input("Fail to empty clipboard", msg),
+            |state| state.clipboard_clears += 1,
+        )
+    }
+}
+
+impl Processes for MockPlatform {
+    fn kill_explorer(&self) -> Result<(), Error> {
+        self.call(
+            |msg| Error::process("Fail to kill explorer", msg),
+            |state| state.explorer_kills += 1,
+        )
+    }
+}
+
+impl Settings for MockPlatform {
+    fn read_setting(&self, key: &str, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.call(
+            |msg| Error::registry("Fail to get reg value", msg),
+            |state| {
+                let id = (key.to_string(), name.to_string());
+                state.settings.get(&id).cloned()
+            },
+        )
+    }
+
+    fn write_setting(&self, key: &str, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.call(
+            |msg| Error::registry("Fail to set reg value", msg),
+            |state| {
+                let id = (key.to_string(), name.to_string());
+                state.settings.insert(id, value.to_vec());
+            },
+        )
+    }
+}
+
+impl ScreenCapture for MockPlatform {
+    /// Returns where the screenshot would be saved, without writing it.
+    fn take_screenshot(&self, dir: &str, _: &CaptureMode) -> Result<PathBuf, Error> {
+        self.call(
+            |msg| Error::capture("Fail to take screenshot", msg),
+            |state| {
+                let file = PathBuf::from(dir).join(format!("mock-{}.png", state.screenshots.len()));
+                state.screenshots.push(file.clone());
+                file
+            },
+        )
+    }
+}
+
+impl Tray for MockPlatform {
+    fn alert(&self, text: &str) {
+        log!("{text}");
+        self.state.lock().unwrap().alerts.push(text.to_string());
+    }
+
+    fn inform(&self, text: &str) {
+        log!("{text}");
+        self.state.lock().unwrap().infos.push(text.to_string());
+    }
+}
+
+/// Installs a fresh `MockPlatform` and `config` for a test. The returned
+/// guard keeps other tests from swapping them until it is dropped.
+#[cfg(test)]
+pub fn install(config: crate::config::Config) -> (MutexGuard<'static, ()>, Arc<MockPlatform>) {
+    static INSTALLED: Mutex<()> = Mutex::new(());
+    let guard = INSTALLED.lock().unwrap_or_else(|err| err.into_inner());
+    let mock = Arc::new(MockPlatform::default());
+    super::set_platform(mock.clone());
+    crate::config::set_app_config(config);
+    crate::shortcuts::build_shortcuts();
+    (guard, mock)
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use windows::Win32::UI::WindowsAndMessaging::{
+    MB_ICONERROR, MB_ICONINFORMATION, MB_OK, MessageBoxW,
+};
+use windows::core::{PCWSTR, w};
+
+use crate::constants::APP_NAME;
+use crate::error::Error;
+use crate::screen::{modes::CaptureMode, take_screenshot_for_windows};
+use crate::utils::monitors::{self, DisplayTopology};
+use crate::utils::others::to_wstring;
+use crate::utils::registry::{get_key, get_raw_value, set_raw_value};
+use crate::utils::{clipboard, explorer, inputs};
+
+use super::{Clipboard, Displays, Input, Processes, ScreenCapture, Settings, Tray};
+
+pub struct Win32;
+
+impl Displays for Win32 {
+    fn set_display_topology(&self, topology: DisplayTopology) -> Result<(), Error> {
+        monitors::set_display_topology(topology)
+    }
+}
+
+impl Input for Win32 {
+    fn send_keys(&self, keys: &[u16]) -> Result<(), Error> {
+        inputs::send_keys(keys)
+    }
+}
+
+impl Clipboard for Win32 {
+    fn clear_clipboard(&self) -> Result<(), Error> {
+        clipboard::clear_clipboard()
+    }
+}
+
+impl Processes for Win32 {
+    fn kill_explorer(&self) -> Result<(), Error> {
+        explorer::kill_explorer()
+    }
+}
+
+/// Keys are under HKEY_CURRENT_USER.
+impl Settings for Win32 {
+    fn read_setting(&self, key: &str, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let (key, name) = (to_wstring(key), to_wstring(name));
+        let key = get_key(PCWSTR(key.as_ptr()))?;
+        get_raw_value(&key.hkey, PCWSTR(name.as_ptr()))
+    }
+
+    fn write_setting(&self, key: &str, name: &str, value: &[u8]) -> Result<(), Error> {
+        let (key, name) = (to_wstring(key), to_wstring(name));
+        let key = get_key(PCWSTR(key.as_ptr()))?;
+        set_raw_value(&key.hkey, PCWSTR(name.as_ptr()), value)
+    }
+}
+
+impl ScreenCapture for Win32 {
+    fn take_screenshot(&self, dir: &str, mode: &CaptureMode) -> Result<PathBuf, Error> {
+        take_screenshot_for_windows(dir, mode.clone())
+    }
+}
+
+impl Tray for Win32 {
+    fn alert(&self, text: &str) {
+        let text = to_wstring(text);
+        unsafe {
+            MessageBoxW(
+                None,
+                PCWSTR(text.as_ptr() as _),
+                w!("Window Switcher Error"),
+                MB_OK | MB_ICONERROR,
+            )
+        };
+    }
+
+    fn inform(&self, text: &str) {
+        let text = to_wstring(text);
+        unsafe {
+            MessageBoxW(
+                None,
+                PCWSTR(text.as_ptr() as _),
+                APP_NAME,
+                MB_OK | MB_ICONINFORMATION,
+            )
+        };
+    }
+}
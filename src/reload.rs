@@ -0,0 +1,152 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    alert,
+    config::{CONFIG_FILE, Config, app_config, set_app_config},
+    error::Error,
+    events::{EventKind, publish},
+    log,
+    shortcuts::{build_shortcuts, shortcuts},
+};
+
+/// How often the watcher looks at config.toml.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Editors often write a file in several steps, wait for them to finish.
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+type Listener = Box<dyn Fn() + Send + Sync>;
+
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Serializes reloads, so listeners see them in order.
+static RELOADING: Mutex<()> = Mutex::new(());
+static LISTENERS: Mutex<Vec<Listener>> = Mutex::new(vec![]);
+
+/// Loads the config from `dir` for the first time and remembers where it came from.
+pub fn init(dir: PathBuf) -> Result<(), Error> {
+    let config = Config::load(&dir)?;
+    set_app_config(config);
+    build_shortcuts();
+    let _ = CONFIG_DIR.set(dir);
+    Ok(())
+}
+
+/// Reads the config again and swaps it in with the shortcuts built from it.
+/// On error the config in effect stays untouched.
+pub fn reload() -> Result<(), Error> {
+    let _guard = RELOADING.lock().unwrap();
+    let dir = CONFIG_DIR
+        .get()
+        .ok_or_else(|| Error::config("Config was never loaded"))?;
+    let config = Config::load(dir)?;
+    if config.bind_addrs() != app_config().bind_addrs() {
+        log!("Server addresses changed, restart the app to listen on them");
+    }
+    set_app_config(config);
+    build_shortcuts();
+    for listener in LISTENERS.lock().unwrap().iter() {
+        listener();
+    }
+    let count = shortcuts().len();
+    log!("Reloaded config, {count} shortcuts");
+    publish(EventKind::ConfigReloaded { shortcuts: count });
+    Ok(())
+}
+
+/// Runs `listener` after every successful reload.
+pub fn on_reload(listener: impl Fn() + Send + Sync + 'static) {
+    LISTENERS.lock().unwrap().push(Box::new(listener));
+}
+
+/// Reloads whenever config.toml changes on disk.
+pub fn watch() {
+    let Some(dir) = CONFIG_DIR.get() else {
+        return;
+    };
+    let path = dir.join(CONFIG_FILE);
+    let modified = move || fs::metadata(&path).and_then(|x| x.modified()).ok();
+    let mut last: Option<SystemTime> = modified();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified();
+            if current.is_none() || current == last {
+                continue;
+            }
+            thread::sleep(SETTLE_DELAY);
+            last = modified();
+            if let Err(err) = reload() {
+                log!("Fail to reload config, {err}");
+                alert!("Fail to reload config, {err}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::platform::mock;
+
+    /// The config directory of this test process, the same for every test
+    /// and removed again by the caller.
+    fn config_dir() -> &'static PathBuf {
+        let dir = CONFIG_DIR.get_or_init(|| {
+            std::env::temp_dir().join(format!("windows-shortcuts-{}-reload", std::process::id()))
+        });
+        fs::create_dir_all(dir).unwrap();
+        dir
+    }
+
+    const CONFIG: &str = r#"
+[tv]
+ip = "192.168.1.40"
+
+[[shortcuts]]
+name = "hello"
+actions = ["echo hi"]
+"#;
+
+    #[test]
+    fn reload_swaps_config_and_runs_listeners() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let (_guard, _mock) = mock::install(Config::default());
+        on_reload(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        });
+        let calls = CALLS.load(Ordering::SeqCst);
+        fs::write(config_dir().join(CONFIG_FILE), CONFIG).unwrap();
+        reload().unwrap();
+        assert_eq!(app_config().tv().ip, "192.168.1.40");
+        assert!(shortcuts().iter().any(|x| x.name == "hello"));
+        assert_eq!(CALLS.load(Ordering::SeqCst), calls + 1);
+        fs::remove_dir_all(config_dir()).unwrap();
+    }
+
+    #[test]
+    fn failed_reload_keeps_config() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        on_reload(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        });
+        let before = (app_config().to_toml(), shortcuts().len());
+        fs::write(
+            config_dir().join(CONFIG_FILE),
+            "[tv]\nip = \"192.168.1.50\"\nport = \"x\"\n",
+        )
+        .unwrap();
+        let err = reload().unwrap_err();
+        assert!(err.to_string().contains("tv.port"), "{err}");
+        assert_eq!((app_config().to_toml(), shortcuts().len()), before);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+        fs::remove_dir_all(config_dir()).unwrap();
+    }
+}
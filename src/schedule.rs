@@ -0,0 +1,360 @@
+pub mod cron;
+
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(windows)]
+use windows::Win32::System::SystemInformation::GetLocalTime;
+
+use crate::config::{ScheduleConfig, app_config};
+use crate::log;
+use crate::shortcuts::{find_shortcut, job};
+
+use cron::{Cron, LocalTime};
+
+/// How far back a late tick still runs what it missed, e.g. after the PC slept.
+const MAX_CATCH_UP: usize = 5;
+/// Margin past the minute boundary, so the clock reads the new minute.
+const TICK_MARGIN: Duration = Duration::from_millis(50);
+
+/// When a scheduled shortcut runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum When {
+    /// `cron = "0 1 * * *"`
+    Cron(Cron),
+    /// `at = "2026-12-24 18:00"`, once
+    At(LocalTime),
+}
+
+impl When {
+    pub fn matches(&self, time: &LocalTime) -> bool {
+        match self {
+            When::Cron(cron) => cron.matches(time),
+            When::At(at) => at == time,
+        }
+    }
+
+    pub fn next_after(&self, time: LocalTime) -> Option<LocalTime> {
+        match self {
+            When::Cron(cron) => cron.next_after(time),
+            When::At(at) => Some(*at).filter(|at| *at > time),
+        }
+    }
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            When::Cron(cron) => write!(f, "{cron}"),
+            When::At(at) => write!(f, "{at}"),
+        }
+    }
+}
+
+/// Where the scheduler reads the time, so it can be driven by another clock.
+pub trait Clock {
+    fn now(&self) -> LocalTime;
+    /// Blocks until the next minute starts.
+    fn wait_next_minute(&self);
+}
+
+/// The local wall clock.
+pub struct SystemClock;
+
+#[cfg(windows)]
+impl Clock for SystemClock {
+    fn now(&self) -> LocalTime {
+        let time = unsafe { GetLocalTime() };
+        LocalTime {
+            year: time.wYear as i32,
+            month: time.wMonth as u32,
+            day: time.wDay as u32,
+            hour: time.wHour as u32,
+            minute: time.wMinute as u32,
+        }
+    }
+
+    fn wait_next_minute(&self) {
+        let time = unsafe { GetLocalTime() };
+        let elapsed = Duration::from_secs(time.wSecond as u64)
+            + Duration::from_millis(time.wMilliseconds as u64);
+        thread::sleep(Duration::from_secs(60).saturating_sub(elapsed) + TICK_MARGIN);
+    }
+}
+
+/// Seconds the local time is ahead of UTC at `secs` past the epoch.
+#[cfg(any(
+    all(target_os = "linux", target_env = "gnu", target_pointer_width = "64"),
+    target_os = "macos"
+))]
+fn utc_offset(secs: i64) -> i64 {
+    /// `struct tm` of glibc on 64-bit Linux and of macOS, where `time_t` is
+    /// 64-bit and the struct ends with `tm_gmtoff` and `tm_zone`.
+    #[repr(C)]
+    struct Tm {
+        tm_sec: i32,
+        tm_min: i32,
+        tm_hour: i32,
+        tm_mday: i32,
+        tm_mon: i32,
+        tm_year: i32,
+        tm_wday: i32,
+        tm_yday: i32,
+        tm_isdst: i32,
+        tm_gmtoff: i64,
+        tm_zone: *const std::ffi::c_char,
+    }
+
+    unsafe extern "C" {
+        fn localtime_r(time: *const i64, tm: *mut Tm) -> *mut Tm;
+    }
+
+    let mut tm = std::mem::MaybeUninit::<Tm>::zeroed();
+    if unsafe { localtime_r(&secs, tm.as_mut_ptr()) }.is_null() {
+        return 0;
+    }
+    unsafe { tm.assume_init() }.tm_gmtoff
+}
+
+/// Other C libraries lay `struct tm` out differently, the schedule runs on UTC there.
+#[cfg(all(
+    unix,
+    not(any(
+        all(target_os = "linux", target_env = "gnu", target_pointer_width = "64"),
+        target_os = "macos"
+    ))
+))]
+fn utc_offset(_: i64) -> i64 {
+    0
+}
+
+#[cfg(unix)]
+impl Clock for SystemClock {
+    fn now(&self) -> LocalTime {
+        let secs = unix_now().as_secs() as i64;
+        LocalTime::from_unix(secs + utc_offset(secs))
+    }
+
+    fn wait_next_minute(&self) {
+        let now = unix_now();
+        let elapsed = Duration::new(now.as_secs() % 60, now.subsec_nanos());
+        thread::sleep(Duration::from_secs(60).saturating_sub(elapsed) + TICK_MARGIN);
+    }
+}
+
+#[cfg(unix)]
+fn unix_now() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Finds the entries due at each tick. Every minute since the previous tick is
+/// checked, so a late tick doesn't miss any, and none runs twice when the
+/// clock goes back.
+#[derive(Default)]
+pub struct Scheduler {
+    last: Option<LocalTime>,
+}
+
+impl Scheduler {
+    pub fn due<'a>(
+        &mut self,
+        now: LocalTime,
+        entries: &'a [ScheduleConfig],
+    ) -> Vec<&'a ScheduleConfig> {
+        let mut minutes = vec![];
+        let mut time = match self.last {
+            Some(last) if last >= now => return vec![],
+            Some(last) => last.next_minute(),
+            None => now,
+        };
+        while time <= now {
+            if minutes.len() == MAX_CATCH_UP {
+                minutes.remove(0);
+            }
+            minutes.push(time);
+            time = time.next_minute();
+        }
+        self.last = Some(now);
+        entries
+            .iter()
+            .filter(|entry| minutes.iter().any(|x| entry.when.matches(x)))
+            .collect()
+    }
+}
+
+/// Runs the schedule in `[[schedule]]` on a background thread.
+pub fn start() {
+    thread::spawn(|| run(&SystemClock, start_job));
+}
+
+/// Checks the schedule in effect every minute of `clock` and calls `fire` with the due entries.
+pub fn run(clock: &impl Clock, mut fire: impl FnMut(&ScheduleConfig)) {
+    let mut scheduler = Scheduler::default();
+    loop {
+        let config = app_config();
+        for entry in scheduler.due(clock.now(), &config.schedule) {
+            fire(entry);
+        }
+        clock.wait_next_minute();
+    }
+}
+
+fn start_job(entry: &ScheduleConfig) {
+    let Some(shortcut) = find_shortcut(&entry.shortcut) else {
+        log!("Scheduled shortcut {} no longer exists", entry.shortcut);
+        return;
+    };
+    match job::submit(&shortcut, entry.device.as_deref()) {
+        Ok(job) => log!(
+            "{} started by schedule '{}' as job {}",
+            shortcut.name,
+            entry.when,
+            job.id
+        ),
+        Err(running) => log!(
+            "{} skipped by schedule '{}', job {} is still running",
+            shortcut.name,
+            entry.when,
+            running.id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Moves by `step` minutes on every wait.
+    struct TestClock {
+        now: Cell<LocalTime>,
+        step: Cell<u32>,
+    }
+
+    impl TestClock {
+        fn new(now: &str) -> Self {
+            TestClock {
+                now: Cell::new(now.parse().unwrap()),
+                step: Cell::new(1),
+            }
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> LocalTime {
+            self.now.get()
+        }
+
+        fn wait_next_minute(&self) {
+            for _ in 0..self.step.get() {
+                self.now.set(self.now.get().next_minute());
+            }
+        }
+    }
+
+    fn entry(shortcut: &str, cron: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            shortcut: shortcut.to_string(),
+            device: None,
+            when: When::Cron(cron.parse().unwrap()),
+        }
+    }
+
+    fn due(
+        scheduler: &mut Scheduler,
+        clock: &TestClock,
+        entries: &[ScheduleConfig],
+    ) -> Vec<String> {
+        let res = scheduler
+            .due(clock.now(), entries)
+            .into_iter()
+            .map(|x| x.shortcut.clone())
+            .collect();
+        clock.wait_next_minute();
+        res
+    }
+
+    #[test]
+    fn due_every_matching_minute() {
+        let clock = TestClock::new("2026-03-01 09:58");
+        let entries = [
+            entry("nine", "0 9 * * *"),
+            entry("ten", "0 10 * * *"),
+            entry("quarter", "*/15 * * * *"),
+        ];
+        let mut scheduler = Scheduler::default();
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["ten", "quarter"]);
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+    }
+
+    #[test]
+    fn late_tick_catches_up() {
+        let clock = TestClock::new("2026-03-01 09:58");
+        let entries = [entry("ten", "0 10 * * *"), entry("one past", "1 10 * * *")];
+        let mut scheduler = Scheduler::default();
+        clock.step.set(4);
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        // 09:59 to 10:02
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["ten", "one past"]);
+    }
+
+    #[test]
+    fn catch_up_is_limited() {
+        let entries = [entry("ten", "0 10 * * *"), entry("five past", "5 10 * * *")];
+        let clock = TestClock::new("2026-03-01 09:50");
+        let mut scheduler = Scheduler::default();
+        clock.step.set(60);
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        // woken an hour later, only 10:46 to 10:50 are checked
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+
+        let clock = TestClock::new("2026-03-01 10:00");
+        let mut scheduler = Scheduler::default();
+        clock.step.set(5);
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["ten"]);
+        // 10:01 to 10:05
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["five past"]);
+    }
+
+    #[test]
+    fn clock_going_back_runs_nothing_twice() {
+        let clock = TestClock::new("2026-10-25 02:59");
+        let entries = [entry("three", "0 3 * * *")];
+        let mut scheduler = Scheduler::default();
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["three"]);
+        // back an hour, as when daylight saving time ends
+        clock.now.set("2026-10-25 02:01".parse().unwrap());
+        for _ in 0..59 {
+            assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        }
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+    }
+
+    #[test]
+    fn at_runs_once() {
+        let when = When::At("2026-12-24 18:00".parse().unwrap());
+        let entries = [ScheduleConfig {
+            shortcut: "eve".to_string(),
+            device: None,
+            when: when.clone(),
+        }];
+        let clock = TestClock::new("2026-12-24 17:59");
+        let mut scheduler = Scheduler::default();
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert_eq!(due(&mut scheduler, &clock, &entries), ["eve"]);
+        assert!(due(&mut scheduler, &clock, &entries).is_empty());
+        assert_eq!(
+            when.next_after("2026-12-24 17:00".parse().unwrap()),
+            Some("2026-12-24 18:00".parse().unwrap())
+        );
+        assert_eq!(when.next_after("2026-12-24 18:00".parse().unwrap()), None);
+    }
+}
@@ -0,0 +1,475 @@
+use std::fmt;
+use std::str::FromStr;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+/// How far ahead `Cron::next_after` looks before deciding an expression never fires, e.g. `0 0 30 2 *`.
+const SEARCH_YEARS: i32 = 8;
+
+/// A minute on the local wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl LocalTime {
+    /// 0 is Sunday.
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as u32
+    }
+
+    /// The minute `secs` seconds after 1970-01-01 00:00.
+    #[cfg(any(test, unix))]
+    pub fn from_unix(secs: i64) -> LocalTime {
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let secs = secs.rem_euclid(86400);
+        LocalTime {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u32,
+            minute: (secs % 3600 / 60) as u32,
+        }
+    }
+
+    pub fn next_minute(self) -> LocalTime {
+        match self.minute {
+            59 => self.next_hour(),
+            minute => LocalTime {
+                minute: minute + 1,
+                ..self
+            },
+        }
+    }
+
+    fn next_hour(self) -> LocalTime {
+        match self.hour {
+            23 => self.next_day(),
+            hour => LocalTime {
+                hour: hour + 1,
+                minute: 0,
+                ..self
+            },
+        }
+    }
+
+    fn next_day(self) -> LocalTime {
+        if self.day >= days_in_month(self.year, self.month) {
+            return self.next_month();
+        }
+        LocalTime {
+            day: self.day + 1,
+            hour: 0,
+            minute: 0,
+            ..self
+        }
+    }
+
+    fn next_month(self) -> LocalTime {
+        let (year, month) = match self.month {
+            12 => (self.year + 1, 1),
+            month => (self.year, month + 1),
+        };
+        LocalTime {
+            year,
+            month,
+            day: 1,
+            hour: 0,
+            minute: 0,
+        }
+    }
+}
+
+/// Written `2026-12-24 18:00`.
+impl FromStr for LocalTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid time '{s}', expected a local time like 2026-12-24 18:00");
+        let (date, time) = s.trim().split_once([' ', 'T']).ok_or_else(invalid)?;
+        let number = |x: &str| x.trim().parse::<u32>().map_err(|_| invalid());
+        let (year, rest) = date.split_once('-').ok_or_else(invalid)?;
+        let (month, day) = rest.split_once('-').ok_or_else(invalid)?;
+        let (hour, minute) = time.split_once(':').ok_or_else(invalid)?;
+        let res = LocalTime {
+            year: year.trim().parse().map_err(|_| invalid())?,
+            month: number(month)?,
+            day: number(day)?,
+            hour: number(hour)?,
+            minute: number(minute)?,
+        };
+        let valid = (1..=12).contains(&res.month)
+            && (1..=days_in_month(res.year, res.month)).contains(&res.day)
+            && res.hour < 24
+            && res.minute < 60;
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+/// A cron expression, `minute hour day month weekday`, or an alias such as `@daily`.
+/// Fields take `*`, numbers, names like `mon` or `jan`, `a-b` ranges, `,` lists and `/n` steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    text: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day and weekday fields were `*`. When both are restricted,
+    /// either one matching is enough, as in classic cron.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn matches(&self, time: &LocalTime) -> bool {
+        bit(self.minutes, time.minute)
+            && bit(self.hours, time.hour)
+            && bit(self.months, time.month)
+            && self.day_matches(time)
+    }
+
+    /// The first matching minute after `time`.
+    pub fn next_after(&self, time: LocalTime) -> Option<LocalTime> {
+        let mut time = time.next_minute();
+        let last_year = time.year + SEARCH_YEARS;
+        while time.year <= last_year {
+            if !bit(self.months, time.month) {
+                time = time.next_month();
+            } else if !self.day_matches(&time) {
+                time = time.next_day();
+            } else if !bit(self.hours, time.hour) {
+                time = time.next_hour();
+            } else if !bit(self.minutes, time.minute) {
+                time = time.next_minute();
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, time: &LocalTime) -> bool {
+        let day = bit(self.days, time.day);
+        let weekday = bit(self.weekdays, time.weekday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let expanded = match text {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            alias if alias.starts_with('@') => {
+                return Err(format!(
+                    "Unknown alias '{alias}', expected @yearly, @monthly, @weekly, @daily or @hourly"
+                ));
+            }
+            _ => text,
+        };
+        let [minute, hour, day, month, weekday] = expanded
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| {
+                format!("Invalid cron '{text}', expected 5 fields: minute hour day month weekday")
+            })?;
+        let mut weekdays = field(weekday, 0, 7, &WEEKDAYS)?;
+        // 7 is Sunday too
+        if bit(weekdays, 7) {
+            weekdays |= 1;
+        }
+        Ok(Cron {
+            text: text.to_string(),
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// One cron field as a bit set of the values it allows. `names` are the values from `min` on.
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |x: &str| {
+        let lower = x.to_ascii_lowercase();
+        names
+            .iter()
+            .position(|name| *name == lower)
+            .map(|index| index as u32 + min)
+            .or_else(|| x.parse().ok())
+            .filter(|x| (min..=max).contains(x))
+            .ok_or_else(|| format!("Invalid cron value '{x}', expected {min} to {max}"))
+    };
+    let mut res = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|x| *x > 0)
+                    .ok_or_else(|| format!("Invalid cron step '{step}'"))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // `5/15` runs from 5 to the end
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let value = value(range)?;
+                (value, value)
+            }
+        };
+        if first > last {
+            return Err(format!("Invalid cron range '{range}'"));
+        }
+        for x in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            res |= 1 << x;
+        }
+    }
+    Ok(res)
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01, from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date `days` after 1970-01-01, the inverse of `days_from_civil`.
+#[cfg(any(test, unix))]
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year as i32, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> LocalTime {
+        s.parse().unwrap()
+    }
+
+    fn next(cron: &str, after: &str) -> Option<String> {
+        let cron = cron.parse::<Cron>().unwrap();
+        cron.next_after(time(after)).map(|x| x.to_string())
+    }
+
+    #[test]
+    fn parse_local_time() {
+        let res = time("2026-12-24 18:05");
+        assert_eq!(
+            res,
+            LocalTime {
+                year: 2026,
+                month: 12,
+                day: 24,
+                hour: 18,
+                minute: 5,
+            }
+        );
+        assert_eq!(time(" 2026-12-24T18:05 "), res);
+        assert_eq!(res.to_string(), "2026-12-24 18:05");
+        assert_eq!(time("2028-02-29 00:00").day, 29);
+        for invalid in [
+            "",
+            "2026-12-24",
+            "2026-12-24 18",
+            "2026-13-01 00:00",
+            "2026-02-29 00:00",
+            "2026-04-31 00:00",
+            "2026-12-24 24:00",
+            "2026-12-24 18:60",
+            "tomorrow 18:00",
+        ] {
+            assert!(invalid.parse::<LocalTime>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn weekday_and_next_minute() {
+        // a Thursday
+        assert_eq!(time("1970-01-01 00:00").weekday(), 4);
+        assert_eq!(time("2026-10-18 00:00").weekday(), 0);
+        assert_eq!(
+            time("2024-12-31 23:59").next_minute(),
+            time("2025-01-01 00:00")
+        );
+        assert_eq!(
+            time("2028-02-28 23:59").next_minute(),
+            time("2028-02-29 00:00")
+        );
+        assert_eq!(
+            time("2026-02-28 23:59").next_minute(),
+            time("2026-03-01 00:00")
+        );
+    }
+
+    #[test]
+    fn from_unix() {
+        assert_eq!(LocalTime::from_unix(0), time("1970-01-01 00:00"));
+        assert_eq!(
+            LocalTime::from_unix(1_798_759_259),
+            time("2026-12-31 23:20")
+        );
+        assert_eq!(LocalTime::from_unix(951_782_400), time("2000-02-29 00:00"));
+        assert_eq!(LocalTime::from_unix(-60), time("1969-12-31 23:59"));
+    }
+
+    #[test]
+    fn parse_cron() {
+        assert!("0 0 * *".parse::<Cron>().is_err());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("* * 0 * *".parse::<Cron>().is_err());
+        assert!("* * * * 8".parse::<Cron>().is_err());
+        assert!("5-1 * * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+        assert!("@sometimes".parse::<Cron>().is_err());
+        assert_eq!("@daily".parse::<Cron>().unwrap().to_string(), "@daily");
+    }
+
+    #[test]
+    fn next_after() {
+        assert_eq!(
+            next("30 9 * * *", "2026-03-01 09:30").as_deref(),
+            Some("2026-03-02 09:30")
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-01 09:31").as_deref(),
+            Some("2026-03-01 09:45")
+        );
+        assert_eq!(
+            next("5/20 * * * *", "2026-03-01 09:46").as_deref(),
+            Some("2026-03-01 10:05")
+        );
+        assert_eq!(
+            next("0 0 1 jan *", "2026-03-01 00:00").as_deref(),
+            Some("2027-01-01 00:00")
+        );
+        assert_eq!(
+            next("@hourly", "2026-12-31 23:00").as_deref(),
+            Some("2027-01-01 00:00")
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2026-03-01 00:00").as_deref(),
+            Some("2028-02-29 00:00")
+        );
+        assert_eq!(next("0 0 30 2 *", "2026-03-01 00:00"), None);
+    }
+
+    #[test]
+    fn day_or_weekday() {
+        // the 13th or any Friday, 2026-03-06 being a Friday
+        let cron = "0 12 13 * fri";
+        assert_eq!(
+            next(cron, "2026-03-01 00:00").as_deref(),
+            Some("2026-03-06 12:00")
+        );
+        assert_eq!(
+            next(cron, "2026-03-06 12:00").as_deref(),
+            Some("2026-03-13 12:00")
+        );
+        assert_eq!(
+            next(cron, "2026-03-13 12:00").as_deref(),
+            Some("2026-03-20 12:00")
+        );
+        // with the day a wildcard, only the weekday counts
+        assert_eq!(
+            next("0 12 * * fri", "2026-03-06 12:00").as_deref(),
+            Some("2026-03-13 12:00")
+        );
+        assert_eq!(
+            next("0 12 */10 * *", "2026-03-01 12:00").as_deref(),
+            Some("2026-03-11 12:00")
+        );
+    }
+
+    #[test]
+    fn sunday_is_0_or_7() {
+        // 2026-10-18 is a Sunday
+        let expected = Some("2026-10-18 08:00");
+        assert_eq!(next("0 8 * * 0", "2026-10-17 09:00").as_deref(), expected);
+        assert_eq!(next("0 8 * * 7", "2026-10-17 09:00").as_deref(), expected);
+        assert_eq!(next("0 8 * * sun", "2026-10-17 09:00").as_deref(), expected);
+        assert_eq!(next("0 8 * * 5-7", "2026-10-17 09:00").as_deref(), expected);
+        assert!(
+            "0 8 * * 7"
+                .parse::<Cron>()
+                .unwrap()
+                .matches(&time("2026-10-18 08:00"))
+        );
+    }
+}
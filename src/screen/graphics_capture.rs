@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::time::SystemTime;
+
+use windows::Foundation::TypedEventHandler;
+use windows::Graphics::Capture::{Direct3D11CaptureFramePool, GraphicsCaptureItem};
+use windows::Graphics::DirectX::DirectXPixelFormat;
+use windows::Graphics::Imaging::{BitmapAlphaMode, BitmapEncoder, BitmapPixelFormat};
+use windows::Storage::{CreationCollisionOption, FileAccessMode, StorageFolder};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Direct3D11::{
+    D3D11_MAP_READ, D3D11_MAPPED_SUBRESOURCE, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+    ID3D11Resource, ID3D11Texture2D,
+};
+use windows::Win32::Graphics::Gdi::{HMONITOR, MONITOR_DEFAULTTOPRIMARY, MonitorFromWindow};
+use windows::Win32::System::WinRT::{
+    Graphics::Capture::IGraphicsCaptureItemInterop, RO_INIT_MULTITHREADED, RoInitialize,
+};
+use windows::Win32::UI::WindowsAndMessaging::GetDesktopWindow;
+use windows::core::{HSTRING, IInspectable, Result};
+
+use super::capture::enumerate_capturable_windows;
+use super::d3d;
+use super::display_info::enumerate_displays;
+use super::modes::CaptureMode;
+use super::window_info::WindowInfo;
+use windows::core::Interface;
+
+use crate::error::Error;
+
+fn create_capture_item_for_window(window_handle: HWND) -> Result<GraphicsCaptureItem> {
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    unsafe { interop.CreateForWindow(window_handle) }
+}
+
+fn create_capture_item_for_monitor(monitor_handle: HMONITOR) -> Result<GraphicsCaptureItem> {
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    unsafe { interop.CreateForMonitor(monitor_handle) }
+}
+
+fn take_screenshot(item: &GraphicsCaptureItem, save_dir: &str) -> Result<PathBuf> {
+    let item_size = item.Size()?;
+
+    let d3d_device = d3d::create_d3d_device()?;
+    let d3d_context = unsafe { d3d_device.GetImmediateContext()? };
+    let device = d3d::create_direct3d_device(&d3d_device)?;
+    let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+        &device,
+        DirectXPixelFormat::B8G8R8A8UIntNormalized,
+        1,
+        item_size,
+    )?;
+    let session = frame_pool.CreateCaptureSession(item)?;
+
+    let (sender, receiver) = channel();
+    frame_pool.FrameArrived(
+        &TypedEventHandler::<Direct3D11CaptureFramePool, IInspectable>::new({
+            move |frame_pool, _| {
+                let frame_pool = frame_pool.as_ref().unwrap();
+                let frame = frame_pool.TryGetNextFrame()?;
+                sender.send(frame).unwrap();
+                Ok(())
+            }
+        }),
+    )?;
+    session.StartCapture()?;
+
+    let texture = unsafe {
+        let frame = receiver.recv().unwrap();
+
+        let source_texture: ID3D11Texture2D =
+            d3d::get_d3d_interface_from_object(&frame.Surface()?)?;
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        source_texture.GetDesc(&mut desc);
+        // desc.BindFlags = D3D11_BIND_FLAG(0);
+        // desc.MiscFlags = D3D11_RESOURCE_MISC_FLAG(0);
+        desc.BindFlags = 0;
+        desc.MiscFlags = 0;
+        desc.Usage = D3D11_USAGE_STAGING;
+        // desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ;
+        desc.CPUAccessFlags = 131072;
+        let copy_texture = {
+            let mut texture = None;
+            d3d_device.CreateTexture2D(&desc, None, Some(&mut texture))?;
+            texture.unwrap()
+        };
+
+        d3d_context.CopyResource(
+            Some(&copy_texture.cast::<ID3D11Resource>()?),
+            Some(&source_texture.cast::<ID3D11Resource>()?),
+        );
+
+        session.Close()?;
+        frame_pool.Close()?;
+
+        copy_texture
+    };
+
+    let bits = unsafe {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        texture.GetDesc(&mut desc as *mut _);
+
+        let resource: ID3D11Resource = texture.cast()?;
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        d3d_context.Map(
+            Some(&resource.clone()),
+            0,
+            D3D11_MAP_READ,
+            0,
+            Some(&mut mapped),
+        )?;
+
+        // Get a slice of bytes
+        let slice: &[u8] = {
+            std::slice::from_raw_parts(
+                mapped.pData as *const _,
+                (desc.Height * mapped.RowPitch) as usize,
+            )
+        };
+
+        let bytes_per_pixel = 4;
+        let mut bits = vec![0u8; (desc.Width * desc.Height * bytes_per_pixel) as usize];
+        for row in 0..desc.Height {
+            let data_begin = (row * (desc.Width * bytes_per_pixel)) as usize;
+            let data_end = ((row + 1) * (desc.Width * bytes_per_pixel)) as usize;
+            let slice_begin = (row * mapped.RowPitch) as usize;
+            let slice_end = slice_begin + (desc.Width * bytes_per_pixel) as usize;
+            bits[data_begin..data_end].copy_from_slice(&slice[slice_begin..slice_end]);
+        }
+
+        d3d_context.Unmap(Some(&resource), 0);
+
+        bits
+    };
+
+    let folder = StorageFolder::GetFolderFromPathAsync(&HSTRING::from(save_dir))?.get()?;
+    let time = std::time::SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let file_name = time.to_string() + ".png";
+    let file = folder
+        .CreateFileAsync(
+            &HSTRING::from(&file_name),
+            CreationCollisionOption::ReplaceExisting,
+        )?
+        .get()?;
+
+    {
+        let stream = file.OpenAsync(FileAccessMode::ReadWrite)?.get()?;
+        let encoder = BitmapEncoder::CreateAsync(BitmapEncoder::PngEncoderId()?, &stream)?.get()?;
+        encoder.SetPixelData(
+            BitmapPixelFormat::Bgra8,
+            BitmapAlphaMode::Premultiplied,
+            item_size.Width as u32,
+            item_size.Height as u32,
+            1.0,
+            1.0,
+            &bits,
+        )?;
+
+        encoder.FlushAsync()?.get()?;
+    }
+
+    Ok(PathBuf::from(save_dir).join(file_name))
+}
+
+fn get_window_from_query(query: &str) -> std::result::Result<WindowInfo, Error> {
+    let windows = find_window(query);
+    match windows.first() {
+        Some(window) => Ok(window.clone()),
+        None => Err(Error::Capture {
+            context: format!("No window matching '{query}' found"),
+            source: None,
+        }),
+    }
+}
+
+fn find_window(window_name: &str) -> Vec<WindowInfo> {
+    let window_list = enumerate_capturable_windows();
+    let mut windows: Vec<WindowInfo> = Vec::new();
+    for window_info in window_list.into_iter() {
+        let title = window_info.title.to_lowercase();
+        if title.contains(&window_name.to_string().to_lowercase()) {
+            windows.push(window_info.clone());
+        }
+    }
+    windows
+}
+
+/// Saves a png of the requested window or monitor into `save_dir` and returns its path.
+pub fn take_screenshot_for_windows(
+    save_dir: &str,
+    mode: CaptureMode,
+) -> std::result::Result<PathBuf, Error> {
+    unsafe { RoInitialize(RO_INIT_MULTITHREADED) }
+        .map_err(|err| Error::capture("Fail to initialize WinRT", err))?;
+
+    let item = match mode {
+        CaptureMode::Window(query) => {
+            let window = get_window_from_query(&query)?;
+            create_capture_item_for_window(window.handle)
+        }
+        CaptureMode::Monitor(id) => {
+            let displays = enumerate_displays()
+                .map_err(|err| Error::capture("Fail to enumerate displays", err))?;
+            if id == 0 || id > displays.len() {
+                return Err(Error::Capture {
+                    context: format!(
+                        "Invalid monitor {id}, ids start with 1 and there are {} displays",
+                        displays.len()
+                    ),
+                    source: None,
+                });
+            }
+            let display = &displays[id - 1];
+            create_capture_item_for_monitor(display.handle)
+        }
+        CaptureMode::Primary => {
+            let monitor_handle =
+                unsafe { MonitorFromWindow(GetDesktopWindow(), MONITOR_DEFAULTTOPRIMARY) };
+            create_capture_item_for_monitor(monitor_handle)
+        }
+    }
+    .map_err(|err| Error::capture("Fail to create capture item", err))?;
+
+    take_screenshot(&item, save_dir).map_err(|err| Error::capture("Fail to take screenshot", err))
+}
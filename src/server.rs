@@ -1,84 +1,499 @@
+pub mod api;
+pub mod auth;
+pub mod client;
+pub mod http;
+pub mod json;
+pub mod panel;
+pub mod pool;
+pub mod router;
+
 use std::{
-    collections::HashMap,
-    io::{BufRead, BufReader, Write},
-    net::{TcpListener, TcpStream},
-    sync::Arc,
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    constants::APP_CONFIG,
-    shortcuts::{SHORTCUTS, Shortcut},
+    config::{Config, app_config},
+    error::Error,
+    log, reload,
+    shortcuts::{ActionOutput, Shortcut, find_shortcut, job, shortcuts},
 };
 
+use auth::{Auth, SeenSignatures};
+use http::{Request, Response};
+use pool::WorkerPool;
+use router::Router;
+
+const WORKERS: usize = 8;
+const QUEUED_CONNECTIONS: usize = 32;
+/// How long a client has to send a whole request, however slowly it trickles in.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an idle keep-alive connection holds a worker.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUESTS_PER_CONNECTION: usize = 100;
+/// The listener is polled so that shutdown is noticed without a connection.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
 pub struct ShortServer {
-    listener: Arc<TcpListener>,
-    url_shortcuts: HashMap<String, fn() -> ()>,
+    listeners: Vec<TcpListener>,
+    service: Arc<Service>,
+}
+
+/// Stops the server from another thread.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<AtomicBool>);
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// What the workers share: routing, access checks and the shutdown flag.
+struct Service {
+    routes: RwLock<Arc<Routes>>,
+    /// Kept across reloads, otherwise a reload would let signed requests be replayed.
+    seen: Arc<SeenSignatures>,
+    shutdown: ShutdownHandle,
+}
+
+/// The parts of the service built from the config, swapped on reload.
+struct Routes {
+    router: Router,
+    auth: Option<Auth>,
 }
 
 impl ShortServer {
-    pub fn from_config() -> Self {
-        let port = APP_CONFIG.get().unwrap().server_port.to_owned();
-        let url = format!("0.0.0.0:{port}");
-        let mut url_shortcuts = HashMap::new();
-        let scs = SHORTCUTS
-            .get()
-            .unwrap()
-            .to_vec()
+    pub fn from_config() -> Result<Self, Error> {
+        let config = app_config();
+        let listeners = config
+            .bind_addrs()
             .into_iter()
-            .filter(|x| x.web_req_url.is_some())
-            .collect::<Vec<Shortcut>>();
-        for ele in scs {
-            let url = ele.web_req_url.unwrap();
-            url_shortcuts.insert(url, ele.func);
+            .map(|addr| {
+                let listener = TcpListener::bind(addr)
+                    .map_err(|err| Error::network(format!("Fail to listen on {addr}"), err))?;
+                listener
+                    .set_nonblocking(true)
+                    .map_err(|err| Error::network(format!("Fail to listen on {addr}"), err))?;
+                Ok(listener)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let seen = Arc::new(SeenSignatures::default());
+        let service = Arc::new(Service::new(build_routes(&config, &seen), seen));
+        let reloaded = service.clone();
+        reload::on_reload(move || {
+            let routes = build_routes(&app_config(), &reloaded.seen);
+            *reloaded.routes.write().unwrap() = Arc::new(routes);
+        });
+        Ok(ShortServer { listeners, service })
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.service.shutdown.clone()
+    }
+
+    /// Accepts connections until shut down, then waits for the requests in flight.
+    pub fn start_server(&self) {
+        let service = self.service.clone();
+        let pool = WorkerPool::new(WORKERS, QUEUED_CONNECTIONS, move |stream| {
+            service.handle_connection(stream)
+        });
+        while !self.service.shutdown.is_shutdown() {
+            let mut idle = true;
+            for listener in &self.listeners {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        idle = false;
+                        if let Err(err) = configure(&stream) {
+                            log!("Fail to configure connection, {err}");
+                            continue;
+                        }
+                        if let Err(stream) = pool.execute(stream) {
+                            reject_busy(stream);
+                        }
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                    Err(err) => log!("Fail to accept connection, {err}"),
+                }
+            }
+            if idle {
+                thread::sleep(ACCEPT_POLL);
+            }
         }
-        ShortServer {
-            listener: Arc::new(TcpListener::bind(url).unwrap()),
-            url_shortcuts,
+        drop(pool);
+    }
+
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|x| x.local_addr().ok())
+            .collect()
+    }
+}
+
+impl Service {
+    fn new(routes: Routes, seen: Arc<SeenSignatures>) -> Self {
+        Self {
+            routes: RwLock::new(Arc::new(routes)),
+            seen,
+            shutdown: ShutdownHandle(Arc::new(AtomicBool::new(false))),
         }
     }
 
-    pub fn start_server(&self) {
-        for stream in self.listener.incoming() {
-            match stream {
-                Ok(val) => self.handle_connection(val),
-                Err(_) => {}
+    fn handle_connection(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(DeadlineReader::new(&stream, READ_TIMEOUT));
+        for served in 0..MAX_REQUESTS_PER_CONNECTION {
+            if served > 0 {
+                // Wait for the next request, which then gets the full read timeout
+                reader.get_mut().deadline = Instant::now() + KEEP_ALIVE_TIMEOUT;
+                match reader.fill_buf() {
+                    Ok(buf) if !buf.is_empty() => {}
+                    _ => return,
+                }
+                reader.get_mut().deadline = Instant::now() + READ_TIMEOUT;
+            }
+            let (response, head, keep_alive) = match Request::read_from(&mut reader) {
+                Ok(Some(mut req)) => {
+                    let response = self.respond(&mut req, &stream);
+                    (response, req.method == "HEAD", req.keep_alive())
+                }
+                Ok(None) => return,
+                Err(err) => match err.to_response() {
+                    Some(response) => (response, false, false),
+                    None => return,
+                },
+            };
+            if response.stream.is_some() {
+                // Streams run on their own thread, so they don't hold a worker
+                drop(reader);
+                let response = response.with_header("Connection", "close");
+                thread::spawn(move || {
+                    // Fails once the client goes away
+                    let _ = response.write_to(&mut &stream, head);
+                });
+                return;
+            }
+            let keep_alive = keep_alive
+                && served + 1 < MAX_REQUESTS_PER_CONNECTION
+                && !self.shutdown.is_shutdown();
+            let connection = if keep_alive { "keep-alive" } else { "close" };
+            let response = response.with_header("Connection", connection);
+            if let Err(err) = response.write_to(&mut &stream, head) {
+                log!("Fail to write response, {err}");
+                return;
+            }
+            if !keep_alive {
+                return;
             }
         }
     }
 
-    fn handle_connection(&self, mut stream: TcpStream) {
-        let buf_reader = BufReader::new(&mut stream);
-        let http_request: Vec<_> = buf_reader
-            .lines()
-            .map(|result| result.unwrap())
-            .take_while(|line| !line.is_empty())
-            .collect();
-        let urls: Vec<_> = http_request
-            .clone()
-            .into_iter()
-            .filter(|x| x.starts_with("HEAD") || x.starts_with("GET"))
-            .collect();
-        if urls.len() == 1 {
-            let url = &urls[0];
-            let url: Vec<_> = url.split(" ").collect();
-            if url.len() >= 2 {
-                let url = url[1];
-                let func = self.url_shortcuts[&String::from(url)];
-                func();
-                let response = "HTTP/1.1 200 OK\r\n\r\n";
-                stream.write_all(response.as_bytes()).unwrap();
+    fn respond(&self, req: &mut Request, stream: &TcpStream) -> Response {
+        // Not holding the lock while handling, a handler may reload the config.
+        let routes = self.routes.read().unwrap().clone();
+        if let Some(auth) = &routes.auth {
+            let peer = match stream.peer_addr() {
+                Ok(addr) => addr.ip(),
+                Err(err) => return Response::text(400, format!("Unknown peer, {err}")),
+            };
+            if let Err(response) = auth.check(req, peer) {
+                log!("Rejected {} {} from {peer}", req.method, req.path);
+                return response;
             }
         }
+        routes.router.handle(req)
+    }
+}
+
+fn build_routes(config: &Config, seen: &Arc<SeenSignatures>) -> Routes {
+    let mut router = Router::new();
+    api::register(&mut router);
+    panel::register(&mut router);
+    for shortcut in shortcuts().iter() {
+        if let Some(route) = &shortcut.web_req_url {
+            let shortcut = shortcut.clone();
+            router.route("GET", route, move |_| run_shortcut(&shortcut, None));
+        }
     }
+    router.route("GET", "/devices/{device}/{shortcut}", run_on_device);
+    Routes {
+        router,
+        auth: Some(Auth::from_config(config, seen.clone())),
+    }
+}
+
+/// Reads until a deadline for the whole request rather than for each read.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
 }
 
-impl Default for ShortServer {
-    fn default() -> ShortServer {
-        let listener = TcpListener::bind("0.0.0.0:9111").unwrap();
-        let val: ShortServer = ShortServer {
-            listener: Arc::new(listener),
-            url_shortcuts: HashMap::new(),
-        };
-        val
+impl<'a> DeadlineReader<'a> {
+    fn new(stream: &'a TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Request took too long"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        // Unix reports a read timeout as WouldBlock, Windows as TimedOut
+        self.stream.read(buf).map_err(|err| match err.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+                io::Error::new(ErrorKind::TimedOut, "Request took too long")
+            }
+            _ => err,
+        })
+    }
+}
+
+fn configure(stream: &TcpStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))
+}
+
+fn reject_busy(stream: TcpStream) {
+    let response = Response::text(503, "Server is busy, try again later")
+        .with_header("Retry-After", "1")
+        .with_header("Connection", "close");
+    let _ = response.write_to(&mut &stream, false);
+}
+
+/// Runs any shortcut against a device, e.g. `/devices/projector/wake`.
+fn run_on_device(req: &Request) -> Response {
+    let device = req.param("device").unwrap_or_default();
+    let name = req.param("shortcut").unwrap_or_default();
+    if app_config().device(device).is_none() {
+        return Response::text(404, format!("No device '{device}'"));
+    }
+    match find_shortcut(name) {
+        Some(shortcut) => run_shortcut(&shortcut, Some(device)),
+        None => Response::text(404, format!("No shortcut '{name}'")),
+    }
+}
+
+/// Runs the shortcut as a job and answers once it finished.
+fn run_shortcut(shortcut: &Shortcut, device: Option<&str>) -> Response {
+    let job = match job::submit(shortcut, device) {
+        Ok(job) => job,
+        Err(running) => {
+            let msg = format!("{} is already running as job {}", shortcut.name, running.id);
+            return Response::text(409, msg);
+        }
+    };
+    match job.wait() {
+        Ok(ActionOutput::None) => Response::text(200, ""),
+        Ok(ActionOutput::Text(text)) => Response::text(200, text),
+        Ok(ActionOutput::File(file)) => Response::text(200, file.display().to_string()),
+        Err(err) => Response::text(500, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::platform::mock;
+    use crate::utils::monitors::DisplayTopology;
+
+    /// Serves one connection with `router` and returns the client end.
+    fn connect(router: Router) -> (TcpStream, thread::JoinHandle<()>) {
+        serve(Service::new(Routes { router, auth: None }, Arc::default()))
+    }
+
+    fn serve(service: Service) -> (TcpStream, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let server = thread::spawn(move || service.handle_connection(stream));
+        (client, server)
+    }
+
+    fn get(path: &str) -> String {
+        request("GET", path)
+    }
+
+    /// Sends one request to the routes of the config in effect and returns the response.
+    pub(super) fn request(method: &str, path: &str) -> String {
+        let seen = Arc::new(SeenSignatures::default());
+        let service = Service::new(build_routes(&app_config(), &seen), seen);
+        let (mut client, server) = serve(service);
+        write!(
+            client,
+            "{method} {path} HTTP/1.1\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        server.join().unwrap();
+        received
+    }
+
+    fn hello() -> Router {
+        let mut router = Router::new();
+        router.route("GET", "/hello", |_| Response::text(200, "hello"));
+        router
+    }
+
+    #[test]
+    fn keeps_connection_alive_until_asked_to_close() {
+        let (mut client, server) = connect(hello());
+        client.write_all(b"GET /hello HTTP/1.1\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all(b"HEAD /hello HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        server.join().unwrap();
+        let (first, second) = received.split_at(received.rfind("HTTP/1.1").unwrap());
+        assert!(first.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(first.contains("Connection: keep-alive\r\n"));
+        assert!(first.ends_with("\r\n\r\nhello"));
+        assert!(second.contains("Connection: close\r\n"));
+        assert!(second.ends_with("Content-Length: 5\r\n\r\n"));
+    }
+
+    #[test]
+    fn answers_bad_requests_and_closes() {
+        let (mut client, server) = connect(hello());
+        client
+            .write_all(b"POST /hello HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n")
+            .unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        server.join().unwrap();
+        assert!(received.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+        assert!(received.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn deadline_covers_the_whole_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let trickle = thread::spawn(move || {
+            // one byte at a time, each well within a per-read timeout
+            for b in b"GET /hello HTTP/1.1\r\n" {
+                if client.write_all(&[*b]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let started = Instant::now();
+        let mut reader = BufReader::new(DeadlineReader::new(&stream, Duration::from_millis(300)));
+        let err = Request::read_from(&mut reader).unwrap_err();
+        assert!(matches!(err, http::HttpError::Io(ref err) if err.kind() == ErrorKind::TimedOut));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        drop(stream);
+        trickle.join().unwrap();
+    }
+
+    #[test]
+    fn silent_client_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = DeadlineReader::new(&stream, Duration::from_millis(200));
+        let err = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "Request took too long");
+    }
+
+    const CONFIG: &str = r#"
+[capture]
+dir = "/shots"
+
+[[devices]]
+name = "projector"
+ip = "192.168.1.30"
+
+[[shortcuts]]
+name = "desk"
+route = "/desk"
+actions = ["send_keys ctrl+alt+t", "display internal", "screenshot primary"]
+
+[[shortcuts]]
+name = "where"
+actions = ["echo {device} at {device_ip}"]
+"#;
+
+    #[test]
+    fn route_runs_shortcut_on_the_platform() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let received = get("/desk");
+        assert!(received.starts_with("HTTP/1.1 200 OK\r\n"), "{received}");
+        assert!(
+            received.ends_with("\r\n\r\n/shots/mock-0.png"),
+            "{received}"
+        );
+        let state = mock.state();
+        assert_eq!(state.keys, [vec![0x11, 0x12, 0x54]]);
+        assert_eq!(state.topology, Some(DisplayTopology::Internal));
+        assert_eq!(state.screenshots.len(), 1);
+    }
+
+    #[test]
+    fn route_reports_failed_shortcut() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        mock.update(|x| x.fail_next = Some("no desktop".to_string()));
+        let received = get("/desk");
+        assert!(received.starts_with("HTTP/1.1 500 "), "{received}");
+        assert!(
+            received.ends_with("Fail to send input, no desktop"),
+            "{received}"
+        );
+        assert_eq!(mock.state().topology, None);
+    }
+
+    #[test]
+    fn runs_shortcut_on_device() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let received = get("/devices/projector/where");
+        assert!(received.starts_with("HTTP/1.1 200 OK\r\n"), "{received}");
+        assert!(
+            received.ends_with("projector at 192.168.1.30"),
+            "{received}"
+        );
+        assert!(get("/devices/nope/where").starts_with("HTTP/1.1 404 "));
+        assert!(get("/devices/projector/nope").starts_with("HTTP/1.1 404 "));
+    }
+
+    #[test]
+    fn fails_to_listen_on_a_bound_port() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        let config = format!(
+            "[server]\nip = \"127.0.0.1\"\nport = {}\nlisten = [\"127.0.0.1\"]\n",
+            addr.port()
+        );
+        let (_guard, _mock) = mock::install(Config::from_toml(&config).unwrap());
+        let err = ShortServer::from_config().err().expect("port is taken");
+        assert!(
+            err.to_string()
+                .starts_with(&format!("Fail to listen on {addr}")),
+            "{err}"
+        );
     }
 }
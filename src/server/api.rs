@@ -0,0 +1,848 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    config::{DeviceConfig, app_config},
+    constants::STARTED_AT,
+    events::{self, Event, EventKind},
+    log, reload,
+    schedule::{Clock, SystemClock, When},
+    shortcuts::{
+        ActionOutput, RunRecord, Shortcut, find_shortcut,
+        job::{self, Job},
+        last_runs, shortcuts,
+        step::StepResult,
+    },
+    utils::{
+        adb::{AndroidKey, device_state, input_text, long_press, send_keyevent},
+        magic_packet::MagicPacket,
+        others::{format_mac_addr, parse_mac_addr},
+    },
+};
+
+use super::http::{Request, Response};
+use super::json::Json;
+use super::router::Router;
+
+/// Event streams open at once, each holds a thread.
+const MAX_EVENT_STREAMS: usize = 16;
+/// A comment is sent this often when there are no events, so a closed stream is noticed.
+const EVENT_PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Adds the `/api` routes.
+pub fn register(router: &mut Router) {
+    router
+        .route("GET", "/api/shortcuts", list_shortcuts)
+        .route("POST", "/api/shortcuts/{id}/run", run_shortcut)
+        .route("GET", "/api/devices", list_devices)
+        .route("GET", "/api/devices/{id}/state", get_device_state)
+        .route("POST", "/api/devices/{id}/key/{name}", press_key)
+        .route("POST", "/api/devices/{id}/text", type_text)
+        .route("GET", "/api/jobs", list_jobs)
+        .route("GET", "/api/jobs/{id}", get_job)
+        .route("DELETE", "/api/jobs/{id}", cancel_job)
+        .route("POST", "/api/wol/{mac}", wake_on_lan)
+        .route("GET", "/api/schedule", list_schedule)
+        .route("GET", "/api/screenshots/latest", latest_screenshot)
+        .route("GET", "/api/events", stream_events)
+        .route("GET", "/api/status", status)
+        .route("POST", "/api/reload", reload_config);
+}
+
+fn list_shortcuts(_: &Request) -> Response {
+    Json::from(shortcuts().iter().map(shortcut_json).collect::<Vec<_>>()).into_response(200)
+}
+
+fn list_devices(_: &Request) -> Response {
+    let config = app_config();
+    Json::from(config.devices.iter().map(device_json).collect::<Vec<_>>()).into_response(200)
+}
+
+fn get_device_state(req: &Request) -> Response {
+    let config = app_config();
+    let id = req.param("id").unwrap_or_default();
+    let Some(device) = config.device(id) else {
+        return error_json(404, format!("No device '{id}'"));
+    };
+    let state = match device_state(device.adb_serial()) {
+        Ok(state) => state,
+        Err(err) => {
+            log!("Fail to query {id}, {err}");
+            return error_json(500, err.to_string());
+        }
+    };
+    Json::object([
+        ("device", Json::from(id)),
+        ("on", Json::from(state.power.is_on())),
+        (
+            "wakefulness",
+            Json::from(state.power.wakefulness.to_string()),
+        ),
+        ("display_on", Json::from(state.power.display_on)),
+        ("foreground", Json::from(state.foreground)),
+        ("hdmi_input", Json::from(state.hdmi_input.map(u64::from))),
+    ])
+    .into_response(200)
+}
+
+/// Presses a key on a device, held down with `?long=true`.
+fn press_key(req: &Request) -> Response {
+    let config = app_config();
+    let id = req.param("id").unwrap_or_default();
+    let Some(device) = config.device(id) else {
+        return error_json(404, format!("No device '{id}'"));
+    };
+    let key = match req.param("name").unwrap_or_default().parse::<AndroidKey>() {
+        Ok(key) => key,
+        Err(err) => return error_json(400, err),
+    };
+    let long = matches!(req.query_param("long"), Some("true" | "1"));
+    let result = if long {
+        long_press(device.adb_serial(), key)
+    } else {
+        send_keyevent(device.adb_serial(), key)
+    };
+    if let Err(err) = result {
+        log!("Fail to press {key} on {id}, {err}");
+        return error_json(500, err.to_string());
+    }
+    Json::object([
+        ("device", Json::from(id)),
+        ("key", Json::from(key.to_string())),
+        ("code", Json::from(key.code() as u64)),
+        ("long", Json::from(long)),
+    ])
+    .into_response(200)
+}
+
+/// Types the request body, as UTF-8 text, into the device's focused field.
+fn type_text(req: &Request) -> Response {
+    let config = app_config();
+    let id = req.param("id").unwrap_or_default();
+    let Some(device) = config.device(id) else {
+        return error_json(404, format!("No device '{id}'"));
+    };
+    let Ok(text) = std::str::from_utf8(&req.body) else {
+        return error_json(400, "Body is not UTF-8 text".to_string());
+    };
+    if text.is_empty() {
+        return error_json(400, "Body is empty, send the text to type".to_string());
+    }
+    if let Err(err) = input_text(device.adb_serial(), text) {
+        log!("Fail to type on {id}, {err}");
+        return error_json(500, err.to_string());
+    }
+    Json::object([
+        ("device", Json::from(id)),
+        ("chars", Json::from(text.chars().count())),
+    ])
+    .into_response(200)
+}
+
+/// Sends a magic packet as configured in `[wol]`, with the SecureOn password
+/// of the device that has this MAC.
+fn wake_on_lan(req: &Request) -> Response {
+    let mac = match parse_mac_addr(req.param("mac").unwrap_or_default()) {
+        Ok(mac) => mac,
+        Err(err) => return error_json(400, err),
+    };
+    let config = app_config();
+    let device = config.devices.iter().find(|x| x.mac == Some(mac));
+    let mut packet = MagicPacket::new(&mac);
+    if let Some(password) = device.and_then(|x| x.wol_password.as_deref()) {
+        packet = packet.with_password(password);
+    }
+    if let Err(err) = packet.send() {
+        log!("Fail to send magic packet, {err}");
+        return error_json(500, err.to_string());
+    }
+    Json::object([
+        ("mac", Json::from(format_mac_addr(&mac))),
+        ("device", Json::from(device.map(|x| x.name.as_str()))),
+        ("secure_on", Json::from(packet.password().is_some())),
+    ])
+    .into_response(200)
+}
+
+/// Starts a job on the shortcut's own device, or the one given as `?device=`, and
+/// answers once it finished, or at once with `?wait=false`.
+fn run_shortcut(req: &Request) -> Response {
+    let id = req.param("id").unwrap_or_default();
+    let Some(shortcut) = find_shortcut(id) else {
+        return error_json(404, format!("No shortcut '{id}'"));
+    };
+    let device = req.query_param("device");
+    if let Some(device) = device
+        && app_config().device(device).is_none()
+    {
+        return error_json(404, format!("No device '{device}'"));
+    }
+    let job = match job::submit(&shortcut, device) {
+        Ok(job) => job,
+        Err(running) => {
+            let error = format!(
+                "{} is already running on {}",
+                running.shortcut, running.device
+            );
+            return Json::object([
+                ("error", Json::from(error)),
+                ("job", Json::from(running.id)),
+            ])
+            .into_response(409);
+        }
+    };
+    if matches!(req.query_param("wait"), Some("false" | "0")) {
+        return job_json(&job).into_response(202);
+    }
+    let status = if job.wait().is_ok() { 200 } else { 500 };
+    job_json(&job).into_response(status)
+}
+
+fn list_jobs(_: &Request) -> Response {
+    Json::from(job::jobs().iter().map(|x| job_json(x)).collect::<Vec<_>>()).into_response(200)
+}
+
+fn get_job(req: &Request) -> Response {
+    match find_job_param(req) {
+        Ok(job) => job_json(&job).into_response(200),
+        Err(res) => res,
+    }
+}
+
+/// Cancels a job before its next step, the one running is let finish.
+fn cancel_job(req: &Request) -> Response {
+    let job = match find_job_param(req) {
+        Ok(job) => job,
+        Err(res) => return res,
+    };
+    if !job.cancel() {
+        return error_json(409, format!("Job {} already finished", job.id));
+    }
+    job_json(&job).into_response(200)
+}
+
+fn find_job_param(req: &Request) -> Result<Arc<Job>, Response> {
+    let id = req.param("id").unwrap_or_default();
+    id.parse::<u64>()
+        .ok()
+        .and_then(job::find_job)
+        .ok_or_else(|| error_json(404, format!("No job '{id}'")))
+}
+
+/// The scheduled shortcuts with when they next run, in local time.
+fn list_schedule(_: &Request) -> Response {
+    let now = SystemClock.now();
+    let config = app_config();
+    let entries = config.schedule.iter().map(|entry| {
+        let (kind, next) = match &entry.when {
+            When::Cron(_) => ("cron", entry.when.next_after(now)),
+            When::At(_) => ("at", entry.when.next_after(now)),
+        };
+        Json::object([
+            ("shortcut", Json::from(entry.shortcut.as_str())),
+            ("device", Json::from(entry.device.clone())),
+            (kind, Json::from(entry.when.to_string())),
+            ("next", Json::from(next.map(|x| x.to_string()))),
+        ])
+    });
+    Json::object([
+        ("now", Json::from(now.to_string())),
+        ("entries", Json::from(entries.collect::<Vec<_>>())),
+    ])
+    .into_response(200)
+}
+
+/// The newest PNG in `capture.dir`.
+fn latest_screenshot(_: &Request) -> Response {
+    let Some(file) = newest_png(&app_config().screen_dir) else {
+        return error_json(404, "No screenshot yet".to_string());
+    };
+    match fs::read(&file) {
+        Ok(png) => Response::new(200)
+            .with_body("image/png", png)
+            .with_header("Cache-Control", "no-store"),
+        Err(err) => error_json(500, format!("Fail to read {}, {err}", file.display())),
+    }
+}
+
+fn newest_png(dir: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|x| {
+            x.path()
+                .extension()
+                .is_some_and(|x| x.eq_ignore_ascii_case("png"))
+        })
+        .filter_map(|x| Some((x.metadata().ok()?.modified().ok()?, x.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Server-sent events, one `event:` named after the kind with its JSON as `data:`.
+fn stream_events(_: &Request) -> Response {
+    // Subscribed here, so that the events published from now on are streamed
+    let Some(events) = events::subscribe(MAX_EVENT_STREAMS) else {
+        return error_json(503, "Too many event streams".to_string());
+    };
+    // The stream is written once, but `Response` takes a `Fn`
+    let events = Mutex::new(Some(events));
+    Response::stream(200, "text/event-stream", move |writer| {
+        let Some(events) = events.lock().unwrap().take() else {
+            return Ok(());
+        };
+        writer.write_all(b": connected\n\n")?;
+        writer.flush()?;
+        // Ends when the subscriber fell behind, the client reconnects
+        while let Ok(event) = events.next(EVENT_PING_INTERVAL) {
+            match event {
+                Some(event) => write!(
+                    writer,
+                    "id: {}\nevent: {}\ndata: {}\n\n",
+                    event.id,
+                    event.kind.name(),
+                    event_json(&event)
+                )?,
+                None => writer.write_all(b": ping\n\n")?,
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    })
+    .with_header("Cache-Control", "no-cache")
+}
+
+fn status(_: &Request) -> Response {
+    let uptime = STARTED_AT.get().map(Instant::elapsed).unwrap_or_default();
+    let config = app_config();
+    let config = Json::object([
+        ("tv_ip", Json::from(config.tv().ip.as_str())),
+        ("devices", Json::from(config.devices.len())),
+        ("server_port", Json::from(config.server_port as u64)),
+        (
+            "listen",
+            Json::from(
+                config
+                    .bind_addrs()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+        ("screen_dir", Json::from(config.screen_dir.as_str())),
+        ("shortcuts", Json::from(config.shortcuts.len())),
+    ]);
+    Json::object([
+        ("uptime_secs", Json::from(uptime.as_secs())),
+        ("config", config),
+        (
+            "last_runs",
+            Json::from(last_runs().iter().map(run_json).collect::<Vec<_>>()),
+        ),
+    ])
+    .into_response(200)
+}
+
+fn reload_config(_: &Request) -> Response {
+    match reload::reload() {
+        Ok(()) => Json::object([
+            ("ok", Json::from(true)),
+            ("shortcuts", Json::from(shortcuts().len())),
+        ])
+        .into_response(200),
+        Err(err) => {
+            log!("Fail to reload config, {err}");
+            error_json(422, err.to_string())
+        }
+    }
+}
+
+pub fn error_json(status: u16, error: String) -> Response {
+    Json::object([("error", Json::from(error))]).into_response(status)
+}
+
+fn shortcut_json(shortcut: &Shortcut) -> Json {
+    Json::object([
+        ("id", Json::from(shortcut.id)),
+        ("name", Json::from(shortcut.name.as_str())),
+        ("device", Json::from(shortcut.device.clone())),
+        ("menu_name", Json::from(shortcut.menu_name.clone())),
+        ("route", Json::from(shortcut.web_req_url.clone())),
+        ("left_click", Json::from(shortcut.is_left_click)),
+        ("hotkey", Json::from(shortcut.hotkey.map(|x| x.to_string()))),
+        (
+            "actions",
+            Json::from(
+                shortcut
+                    .steps
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+    ])
+}
+
+fn device_json(device: &DeviceConfig) -> Json {
+    Json::object([
+        ("name", Json::from(device.name.as_str())),
+        ("ip", Json::from(device.ip.as_str())),
+        ("mac", Json::from(device.mac.as_ref().map(format_mac_addr))),
+        ("serial", Json::from(device.adb_serial())),
+        ("input", Json::from(device.input.map(|x| x.to_string()))),
+        ("wake_keys", keys_json(&device.wake_keys)),
+        ("sleep_keys", keys_json(&device.sleep_keys)),
+    ])
+}
+
+fn keys_json(keys: &[AndroidKey]) -> Json {
+    Json::from(keys.iter().map(ToString::to_string).collect::<Vec<_>>())
+}
+
+fn run_json(record: &RunRecord) -> Json {
+    let finished_at = record
+        .finished_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Json::object([
+        ("name", Json::from(record.name.as_str())),
+        ("device", Json::from(record.device.as_str())),
+        ("finished_at", Json::from(finished_at.as_secs())),
+        ("ok", Json::from(record.result.is_ok())),
+        (
+            "duration_ms",
+            Json::from(record.duration.as_millis() as u64),
+        ),
+        ("output", output_json(record.result.as_ref().ok())),
+        ("error", Json::from(record.result.clone().err())),
+        ("steps", steps_json(&record.steps)),
+    ])
+}
+
+fn job_json(job: &Job) -> Json {
+    let status = job.status();
+    let duration = status
+        .started_at
+        .map(|x| {
+            status
+                .finished_at
+                .unwrap_or_else(SystemTime::now)
+                .duration_since(x)
+        })
+        .map(|x| x.unwrap_or_default().as_millis() as u64);
+    Json::object([
+        ("id", Json::from(job.id)),
+        ("shortcut", Json::from(job.shortcut.as_str())),
+        ("device", Json::from(job.device.as_str())),
+        ("state", Json::from(status.state.to_string())),
+        ("created_at", unix_json(Some(job.created_at))),
+        ("started_at", unix_json(status.started_at)),
+        ("finished_at", unix_json(status.finished_at)),
+        ("duration_ms", Json::from(duration)),
+        ("total_steps", Json::from(job.total_steps)),
+        ("done_steps", Json::from(status.steps.len())),
+        ("steps", steps_json(&status.steps)),
+        ("ok", Json::from(status.result.as_ref().map(Result::is_ok))),
+        (
+            "output",
+            output_json(status.result.as_ref().and_then(|x| x.as_ref().ok())),
+        ),
+        ("error", Json::from(status.result.and_then(Result::err))),
+    ])
+}
+
+fn event_json(event: &Event) -> Json {
+    let mut fields = vec![
+        ("id", Json::from(event.id)),
+        ("type", Json::from(event.kind.name())),
+        ("at", unix_json(Some(event.at))),
+    ];
+    match &event.kind {
+        EventKind::ShortcutStarted {
+            job,
+            shortcut,
+            device,
+        } => fields.extend([
+            ("job", Json::from(*job)),
+            ("shortcut", Json::from(shortcut.as_str())),
+            ("device", Json::from(device.as_str())),
+        ]),
+        EventKind::ShortcutFinished {
+            job,
+            shortcut,
+            device,
+            output,
+        } => fields.extend([
+            ("job", Json::from(*job)),
+            ("shortcut", Json::from(shortcut.as_str())),
+            ("device", Json::from(device.as_str())),
+            ("output", output_json(Some(output))),
+        ]),
+        EventKind::ShortcutFailed {
+            job,
+            shortcut,
+            device,
+            error,
+        } => fields.extend([
+            ("job", Json::from(*job)),
+            ("shortcut", Json::from(shortcut.as_str())),
+            ("device", Json::from(device.as_str())),
+            ("error", Json::from(error.as_str())),
+        ]),
+        EventKind::ConfigReloaded { shortcuts } => {
+            fields.push(("shortcuts", Json::from(*shortcuts)));
+        }
+        EventKind::ScreenshotSaved { path } => {
+            fields.push(("path", Json::from(path.display().to_string())));
+        }
+        EventKind::DeviceReachable { device } | EventKind::DeviceUnreachable { device } => {
+            fields.push(("device", Json::from(device.as_str())));
+        }
+    }
+    Json::object(fields)
+}
+
+fn unix_json(time: Option<SystemTime>) -> Json {
+    Json::from(time.map(|x| x.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()))
+}
+
+fn steps_json(steps: &[StepResult]) -> Json {
+    let result_json = |result: &Result<ActionOutput, String>| {
+        Json::object([
+            ("ok", Json::from(result.is_ok())),
+            ("output", output_json(result.as_ref().ok())),
+            ("error", Json::from(result.clone().err())),
+        ])
+    };
+    let steps = steps.iter().map(|step| {
+        Json::object([
+            ("step", Json::from(step.step.as_str())),
+            ("attempts", Json::from(step.attempts as u64)),
+            ("duration_ms", Json::from(step.duration.as_millis() as u64)),
+            ("result", result_json(&step.result)),
+            (
+                "fallback",
+                step.fallback.as_ref().map_or(Json::Null, result_json),
+            ),
+        ])
+    });
+    Json::from(steps.collect::<Vec<_>>())
+}
+
+fn output_json(output: Option<&ActionOutput>) -> Json {
+    match output {
+        None | Some(ActionOutput::None) => Json::Null,
+        Some(ActionOutput::Text(text)) => Json::object([("text", Json::from(text.as_str()))]),
+        Some(ActionOutput::File(file)) => {
+            Json::object([("file", Json::from(file.display().to_string()))])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::Duration;
+
+    use std::io::Write;
+
+    use super::super::tests::request;
+    use super::*;
+    use crate::config::Config;
+    use crate::config::toml::quote;
+    use crate::platform::mock;
+    use crate::utils::adb::tests::{accept_signed, listen, serve};
+
+    const CONFIG: &str = r#"
+[[devices]]
+name = "projector"
+ip = "192.168.1.30"
+
+[[shortcuts]]
+name = "where"
+route = "/where"
+actions = ["echo {device} at {device_ip}"]
+
+[[shortcuts]]
+name = "desk"
+actions = ["send_keys ctrl+alt+t", "echo done"]
+
+[[shortcuts]]
+name = "slow"
+concurrency = "reject"
+actions = ["delay 300", "echo done"]
+"#;
+
+    fn split(response: &str) -> (&str, &str) {
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap(), body)
+    }
+
+    #[test]
+    fn lists_shortcuts() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("GET", "/api/shortcuts");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Type: application/json"));
+        assert!(
+            body.contains(
+                r#""name":"where","device":null,"menu_name":null,"route":"/where","left_click":false,"hotkey":null,"actions":["echo {device} at {device_ip}"]}"#
+            ),
+            "{body}"
+        );
+        assert!(body.contains(r#""actions":["send_keys ctrl+alt+t","echo done"]"#));
+    }
+
+    #[test]
+    fn runs_shortcut() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("POST", "/api/shortcuts/desk/run");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains(r#""shortcut":"desk","device":"tv","state":"succeeded""#));
+        assert!(body.contains(r#""total_steps":2,"done_steps":2"#), "{body}");
+        assert!(body.ends_with(r#""ok":true,"output":{"text":"done"},"error":null}"#));
+        assert_eq!(mock.state().keys.len(), 1);
+
+        let response = request("POST", "/api/shortcuts/where/run?device=projector");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains(r#""output":{"text":"projector at 192.168.1.30"}"#));
+    }
+
+    #[test]
+    fn runs_shortcut_without_waiting() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("POST", "/api/shortcuts/slow/run?wait=false");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 202 Accepted");
+        assert!(body.contains(r#""shortcut":"slow""#));
+        assert!(body.contains(r#""finished_at":null"#), "{body}");
+        assert!(body.ends_with(r#""ok":null,"output":null,"error":null}"#));
+
+        // refused while the first run goes on
+        let response = request("POST", "/api/shortcuts/slow/run?wait=0");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 409 Conflict");
+        assert!(
+            body.starts_with(r#"{"error":"slow is already running on tv","job":"#),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_shortcut_and_device() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("POST", "/api/shortcuts/nope/run");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert_eq!(body, r#"{"error":"No shortcut 'nope'"}"#);
+
+        let response = request("POST", "/api/shortcuts/desk/run?device=nope");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert_eq!(body, r#"{"error":"No device 'nope'"}"#);
+        assert!(mock.state().keys.is_empty());
+    }
+
+    #[test]
+    fn reports_failed_run() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        mock.update(|x| x.fail_next = Some("no \"desktop\"".to_string()));
+        let response = request("POST", "/api/shortcuts/desk/run");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 500 Internal Server Error");
+        assert!(body.contains(r#""state":"failed""#), "{body}");
+        assert!(
+            body.ends_with(
+                r#""ok":false,"output":null,"error":"Fail to send input, no \"desktop\""}"#
+            ),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn reports_status() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        request("POST", "/api/shortcuts/where/run");
+        let response = request("GET", "/api/status");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(
+            body.contains(
+                r#""config":{"tv_ip":"192.168.1.20","devices":2,"server_port":9111,"listen":["0.0.0.0:9111"],"screen_dir":""#
+            ),
+            "{body}"
+        );
+        assert!(
+            body.contains(r#"{"name":"where","device":"tv","finished_at":"#),
+            "{body}"
+        );
+        assert!(body.contains(r#""output":{"text":"tv at 192.168.1.20"}"#));
+    }
+
+    #[test]
+    fn presses_key_on_device() {
+        let (listener, addr) = listen();
+        let daemon = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_signed(&mut stream);
+            serve(&mut stream, "shell:input keyevent 26", &[]);
+        });
+        let config =
+            format!("[[devices]]\nname = \"box\"\nip = \"127.0.0.1\"\nserial = \"{addr}\"\n");
+        let (_guard, _mock) = mock::install(Config::from_toml(&config).unwrap());
+        let response = request("POST", "/api/devices/box/key/power");
+        daemon.join().unwrap();
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            r#"{"device":"box","key":"KEYCODE_POWER","code":26,"long":false}"#
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_key() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("POST", "/api/devices/projector/key/nope");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body, r#"{"error":"Unknown Android key 'nope'"}"#);
+        let response = request("POST", "/api/devices/nope/key/home");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert_eq!(body, r#"{"error":"No device 'nope'"}"#);
+    }
+
+    #[test]
+    fn sends_magic_packet() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = format!(
+            "[[devices]]\nname = \"pc\"\nip = \"192.168.1.40\"\nmac = \"00:11:22:aa:bb:cc\"\nwol_password = \"1.2.3.4\"\n\n[wol]\nbroadcast = \"127.0.0.1\"\nports = [{port}]\n"
+        );
+        let (_guard, _mock) = mock::install(Config::from_toml(&config).unwrap());
+        let response = request("POST", "/api/wol/00-11-22-AA-BB-CC");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            r#"{"mac":"00:11:22:aa:bb:cc","device":"pc","secure_on":true}"#
+        );
+        let mut buf = [0; 512];
+        let len = listener.recv(&mut buf).unwrap();
+        assert_eq!(len, 106);
+        assert_eq!(
+            buf[..12],
+            [
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc
+            ]
+        );
+        assert_eq!(buf[102..106], [1, 2, 3, 4]);
+
+        let response = request("POST", "/api/wol/nope");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert!(
+            body.starts_with(r#"{"error":"Invalid MAC address 'nope'"#),
+            "{body}"
+        );
+    }
+
+    /// The `"id"` of a job in a response body.
+    fn job_id(body: &str) -> u64 {
+        let start = body.find(r#""id":"#).unwrap() + 5;
+        let end = start + body[start..].find(',').unwrap();
+        body[start..end].parse().unwrap()
+    }
+
+    #[test]
+    fn gets_and_cancels_jobs() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let response = request("POST", "/api/shortcuts/slow/run?wait=false");
+        let id = job_id(split(&response).1);
+
+        let response = request("GET", &format!("/api/jobs/{id}"));
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(
+            body.starts_with(&format!(r#"{{"id":{id},"shortcut":"slow""#)),
+            "{body}"
+        );
+
+        let response = request("DELETE", &format!("/api/jobs/{id}"));
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.starts_with(&format!(r#"{{"id":{id},"#)), "{body}");
+        job::find_job(id).unwrap().wait().unwrap_err();
+
+        let response = request("GET", &format!("/api/jobs/{id}"));
+        assert!(
+            split(&response).1.contains(r#""state":"cancelled""#),
+            "{response}"
+        );
+        let response = request("DELETE", &format!("/api/jobs/{id}"));
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 409 Conflict");
+        assert_eq!(body, format!(r#"{{"error":"Job {id} already finished"}}"#));
+    }
+
+    #[test]
+    fn rejects_unknown_jobs() {
+        let (_guard, _mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        for method in ["GET", "DELETE"] {
+            for id in ["999999999", "nope"] {
+                let response = request(method, &format!("/api/jobs/{id}"));
+                let (status, body) = split(&response);
+                assert_eq!(status, "HTTP/1.1 404 Not Found", "{method} {id}");
+                assert_eq!(body, format!(r#"{{"error":"No job '{id}'"}}"#));
+            }
+        }
+    }
+
+    #[test]
+    fn serves_newest_screenshot() {
+        let dir = std::env::temp_dir().join(format!(
+            "windows-shortcuts-{}-screenshots",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let config = format!("[capture]\ndir = {}\n", quote(&dir.display().to_string()));
+        let (_guard, _mock) = mock::install(Config::from_toml(&config).unwrap());
+        // no directory yet, then an empty one
+        for _ in 0..2 {
+            let response = request("GET", "/api/screenshots/latest");
+            let (status, body) = split(&response);
+            assert_eq!(status, "HTTP/1.1 404 Not Found");
+            assert_eq!(body, r#"{"error":"No screenshot yet"}"#);
+            fs::create_dir_all(&dir).unwrap();
+        }
+
+        let now = SystemTime::now();
+        for (name, age) in [
+            ("old.png", 30),
+            ("new.PNG", 10),
+            ("older.png", 60),
+            ("notes.txt", 0),
+        ] {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            (&file).write_all(name.as_bytes()).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        let response = request("GET", "/api/screenshots/latest");
+        let (status, body) = split(&response);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Type: image/png\r\n"));
+        assert!(response.contains("Cache-Control: no-store\r\n"));
+        assert_eq!(body, "new.PNG");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
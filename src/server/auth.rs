@@ -0,0 +1,378 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::Config;
+use crate::utils::sha256::{constant_time_eq, hmac, to_hex};
+
+use super::http::{Request, Response};
+
+pub const HEADER_TIMESTAMP: &str = "X-Timestamp";
+pub const HEADER_SIGNATURE: &str = "X-Signature";
+const QUERY_TOKEN: &str = "token";
+
+/// An address block such as `192.168.1.0/24` or `fd00::/8`; a bare address is a single host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_eq(
+                u32::from(net) as u128,
+                u32::from(ip) as u128,
+                32,
+                self.prefix,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(u128::from(net), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_eq(net: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    let shift = bits - prefix;
+    shift == bits || net >> shift == ip >> shift
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid address '{addr}'"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|x| *x <= max)
+                .ok_or_else(|| format!("Invalid prefix length '{prefix}'"))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Checks run on every request before it reaches the router. Each one is off
+/// until configured.
+pub struct Auth {
+    token: Option<String>,
+    allowed_clients: Vec<Cidr>,
+    hmac_secret: Option<String>,
+    max_skew: u64,
+    seen: Arc<SeenSignatures>,
+}
+
+/// Signatures accepted within the skew window, so a captured request cannot be replayed.
+/// Outlives the `Auth` built from each config reload.
+#[derive(Default)]
+pub struct SeenSignatures(Mutex<HashMap<String, u64>>);
+
+impl Auth {
+    pub fn from_config(config: &Config, seen: Arc<SeenSignatures>) -> Self {
+        Self {
+            token: config.api_token.clone(),
+            allowed_clients: config.allowed_clients.clone(),
+            hmac_secret: config.hmac_secret.clone(),
+            max_skew: config.hmac_max_skew,
+            seen,
+        }
+    }
+
+    pub fn check(&self, req: &Request, peer: IpAddr) -> Result<(), Response> {
+        if !self.allowed_clients.is_empty()
+            && !self.allowed_clients.iter().any(|x| x.contains(peer))
+        {
+            return Err(Response::text(403, format!("{peer} is not allowed")));
+        }
+        if let Some(token) = &self.token {
+            let given = req
+                .header("Authorization")
+                .and_then(|x| x.strip_prefix("Bearer "))
+                .or_else(|| req.query_param(QUERY_TOKEN));
+            match given {
+                Some(given) if constant_time_eq(given.trim().as_bytes(), token.as_bytes()) => {}
+                _ => {
+                    return Err(Response::text(401, "Missing or invalid token")
+                        .with_header("WWW-Authenticate", "Bearer"));
+                }
+            }
+        }
+        if let Some(secret) = &self.hmac_secret {
+            self.check_signature(req, secret)
+                .map_err(|err| Response::text(401, err))?;
+        }
+        Ok(())
+    }
+
+    fn check_signature(&self, req: &Request, secret: &str) -> Result<(), String> {
+        let (Some(timestamp), Some(signature)) =
+            (req.header(HEADER_TIMESTAMP), req.header(HEADER_SIGNATURE))
+        else {
+            return Err(format!(
+                "Missing {HEADER_TIMESTAMP} or {HEADER_SIGNATURE} header"
+            ));
+        };
+        let timestamp = timestamp
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid {HEADER_TIMESTAMP}"))?;
+        let now = unix_now();
+        if now.abs_diff(timestamp) > self.max_skew {
+            return Err(format!("{HEADER_TIMESTAMP} is too far from server time"));
+        }
+
+        let expected = sign(secret, timestamp, &req.method, &req.target, &req.body);
+        let signature = signature.to_ascii_lowercase();
+        if !constant_time_eq(signature.as_bytes(), expected.as_bytes()) {
+            return Err(format!("Invalid {HEADER_SIGNATURE}"));
+        }
+
+        let mut seen = self.seen.0.lock().unwrap();
+        seen.retain(|_, at| now.abs_diff(*at) <= self.max_skew);
+        if seen.insert(signature, timestamp).is_some() {
+            return Err("Request was already used".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The hex HMAC-SHA256 of `{timestamp}\n{method}\n{target}\n{body}`.
+pub fn sign(secret: &str, timestamp: u64, method: &str, target: &str, body: &[u8]) -> String {
+    let mut message = format!("{timestamp}\n{method}\n{target}\n").into_bytes();
+    message.extend_from_slice(body);
+    to_hex(&hmac(secret.as_bytes(), &message))
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    fn auth() -> Auth {
+        Auth {
+            token: None,
+            allowed_clients: vec![],
+            hmac_secret: None,
+            max_skew: 30,
+            seen: Arc::default(),
+        }
+    }
+
+    fn request(raw: &str) -> Request {
+        Request::read_from(&mut raw.as_bytes()).unwrap().unwrap()
+    }
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    fn signed(timestamp: u64, signature: &str) -> Request {
+        request(&format!(
+            "POST /api/reload HTTP/1.1\r\n{HEADER_TIMESTAMP}: {timestamp}\r\n\
+             {HEADER_SIGNATURE}: {signature}\r\nContent-Length: 2\r\n\r\n{{}}"
+        ))
+    }
+
+    fn sign_now(offset: i64) -> Request {
+        let timestamp = unix_now().saturating_add_signed(offset);
+        signed(
+            timestamp,
+            &sign(SECRET, timestamp, "POST", "/api/reload", b"{}"),
+        )
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let net = "192.168.1.0/24".parse::<Cidr>().unwrap();
+        assert!(net.contains(ip("192.168.1.0")));
+        assert!(net.contains(ip("192.168.1.255")));
+        assert!(!net.contains(ip("192.168.2.1")));
+        assert!(!net.contains(ip("fd00::1")));
+
+        let host = "10.0.0.7".parse::<Cidr>().unwrap();
+        assert_eq!(host.to_string(), "10.0.0.7/32");
+        assert!(host.contains(ip("10.0.0.7")));
+        assert!(!host.contains(ip("10.0.0.6")));
+
+        let v6 = "fd00::/8".parse::<Cidr>().unwrap();
+        assert!(v6.contains(ip("fdab::1")));
+        assert!(!v6.contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn cidr_prefix_zero_matches_everything_of_its_family() {
+        let any = "0.0.0.0/0".parse::<Cidr>().unwrap();
+        assert!(any.contains(ip("8.8.8.8")));
+        assert!(any.contains(ip("255.255.255.255")));
+        assert!(!any.contains(ip("::1")));
+        let any = "::/0".parse::<Cidr>().unwrap();
+        assert!(any.contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn cidr_matches_ipv4_mapped_ipv6_peers() {
+        // what a dual-stack listener reports for an IPv4 client
+        let net = "192.168.1.0/24".parse::<Cidr>().unwrap();
+        assert!(net.contains(ip("::ffff:192.168.1.20")));
+        assert!(!net.contains(ip("::ffff:192.168.2.20")));
+    }
+
+    #[test]
+    fn cidr_rejects_invalid() {
+        assert!("192.168.1.0/33".parse::<Cidr>().is_err());
+        assert!("fd00::/129".parse::<Cidr>().is_err());
+        assert!("192.168.1/24".parse::<Cidr>().is_err());
+        assert!("192.168.1.0/x".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn allowlist_checks_peer() {
+        let auth = Auth {
+            allowed_clients: vec!["127.0.0.1".parse().unwrap(), "10.0.0.0/8".parse().unwrap()],
+            ..auth()
+        };
+        let req = request("GET / HTTP/1.1\r\n\r\n");
+        assert!(auth.check(&req, ip("127.0.0.1")).is_ok());
+        assert!(auth.check(&req, ip("10.1.2.3")).is_ok());
+        assert_eq!(auth.check(&req, ip("192.168.1.2")).unwrap_err().status, 403);
+    }
+
+    #[test]
+    fn token_from_header_or_query() {
+        let auth = Auth {
+            token: Some("abc".to_string()),
+            ..auth()
+        };
+        let peer = ip("127.0.0.1");
+        let check = |raw: &str| auth.check(&request(raw), peer).map_err(|x| x.status);
+        assert_eq!(
+            check("GET / HTTP/1.1\r\nAuthorization: Bearer abc\r\n\r\n"),
+            Ok(())
+        );
+        assert_eq!(check("GET /?token=abc HTTP/1.1\r\n\r\n"), Ok(()));
+        assert_eq!(check("GET / HTTP/1.1\r\n\r\n"), Err(401));
+        assert_eq!(
+            check("GET / HTTP/1.1\r\nAuthorization: Bearer abd\r\n\r\n"),
+            Err(401)
+        );
+        assert_eq!(
+            check("GET / HTTP/1.1\r\nAuthorization: Basic abc\r\n\r\n"),
+            Err(401)
+        );
+        assert_eq!(check("GET /?token=ab HTTP/1.1\r\n\r\n"), Err(401));
+        let res = auth
+            .check(&request("GET / HTTP/1.1\r\n\r\n"), peer)
+            .unwrap_err();
+        assert!(
+            res.headers
+                .contains(&("WWW-Authenticate".to_string(), "Bearer".to_string()))
+        );
+    }
+
+    #[test]
+    fn signature_is_checked() {
+        let auth = Auth {
+            hmac_secret: Some(SECRET.to_string()),
+            ..auth()
+        };
+        let peer = ip("127.0.0.1");
+        assert!(auth.check(&sign_now(0), peer).is_ok());
+        let timestamp = unix_now();
+        let wrong = sign("other", timestamp, "POST", "/api/reload", b"{}");
+        assert_eq!(
+            auth.check(&signed(timestamp, &wrong), peer)
+                .unwrap_err()
+                .status,
+            401
+        );
+        let missing = request("POST /api/reload HTTP/1.1\r\n\r\n");
+        assert_eq!(auth.check(&missing, peer).unwrap_err().status, 401);
+    }
+
+    #[test]
+    fn signature_outside_skew_is_rejected() {
+        let auth = Auth {
+            hmac_secret: Some(SECRET.to_string()),
+            ..auth()
+        };
+        let peer = ip("127.0.0.1");
+        assert!(auth.check(&sign_now(-25), peer).is_ok());
+        assert!(auth.check(&sign_now(25), peer).is_ok());
+        assert!(auth.check(&sign_now(-40), peer).is_err());
+        assert!(auth.check(&sign_now(40), peer).is_err());
+    }
+
+    #[test]
+    fn signature_cannot_be_replayed() {
+        let auth = Auth {
+            hmac_secret: Some(SECRET.to_string()),
+            ..auth()
+        };
+        let peer = ip("127.0.0.1");
+        let req = sign_now(0);
+        assert!(auth.check(&req, peer).is_ok());
+        let res = auth.check(&req, peer).unwrap_err();
+        assert_eq!(res.status, 401);
+        assert_eq!(res.body, b"Request was already used");
+    }
+
+    #[test]
+    fn replay_is_rejected_after_reload() {
+        let seen = Arc::new(SeenSignatures::default());
+        let before = Auth {
+            hmac_secret: Some(SECRET.to_string()),
+            seen: seen.clone(),
+            ..auth()
+        };
+        let peer = ip("127.0.0.1");
+        let req = sign_now(0);
+        assert!(before.check(&req, peer).is_ok());
+        // a reload builds a new Auth but keeps what was seen
+        let after = Auth {
+            hmac_secret: Some(SECRET.to_string()),
+            seen,
+            ..auth()
+        };
+        assert_eq!(after.check(&req, peer).unwrap_err().status, 401);
+    }
+
+    #[test]
+    fn sign_covers_every_part() {
+        let base = sign(SECRET, 1, "POST", "/a?b=c", b"body");
+        assert_eq!(base.len(), 64);
+        assert_ne!(base, sign(SECRET, 2, "POST", "/a?b=c", b"body"));
+        assert_ne!(base, sign(SECRET, 1, "GET", "/a?b=c", b"body"));
+        assert_ne!(base, sign(SECRET, 1, "POST", "/a?b=d", b"body"));
+        assert_ne!(base, sign(SECRET, 1, "POST", "/a?b=c", b"bodz"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+
+use crate::config::Config;
+
+use super::auth::{HEADER_SIGNATURE, HEADER_TIMESTAMP, sign, unix_now};
+
+const EVENTS_PATH: &str = "/api/events";
+
+/// Follows `GET /api/events` on the server `config` describes, with its token
+/// and signature, and calls `on_event` with the name and data of each event.
+/// Returns an error once the stream ends.
+pub fn stream_events(config: &Config, mut on_event: impl FnMut(&str, &str)) -> Result<(), String> {
+    let addr = local_addr(config)?;
+    let stream =
+        TcpStream::connect(addr).map_err(|err| format!("Fail to connect to {addr}, {err}"))?;
+    let mut request =
+        format!("GET {EVENTS_PATH} HTTP/1.1\r\nHost: {addr}\r\nAccept: text/event-stream\r\n");
+    if let Some(token) = &config.api_token {
+        request.push_str(&format!("Authorization: Bearer {token}\r\n"));
+    }
+    if let Some(secret) = &config.hmac_secret {
+        let timestamp = unix_now();
+        let signature = sign(secret, timestamp, "GET", EVENTS_PATH, b"");
+        request.push_str(&format!(
+            "{HEADER_TIMESTAMP}: {timestamp}\r\n{HEADER_SIGNATURE}: {signature}\r\n"
+        ));
+    }
+    request.push_str("\r\n");
+    let read_err = |err: std::io::Error| format!("Fail to read events from {addr}, {err}");
+    (&stream).write_all(request.as_bytes()).map_err(read_err)?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).map_err(read_err)?;
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(read_err)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse().unwrap_or_default();
+        }
+    }
+    if status.split(' ').nth(1) != Some("200") {
+        let mut body = vec![0; length];
+        let _ = reader.read_exact(&mut body);
+        return Err(format!(
+            "{}: {}",
+            status.trim_end(),
+            String::from_utf8_lossy(&body)
+        ));
+    }
+
+    let (mut event, mut data) = (String::new(), vec![]);
+    for line in reader.lines() {
+        let line = line.map_err(read_err)?;
+        if line.is_empty() {
+            if !data.is_empty() {
+                let name = if event.is_empty() { "message" } else { &event };
+                on_event(name, &data.join("\n"));
+            }
+            event.clear();
+            data.clear();
+            continue;
+        }
+        // Lines starting with a colon are comments, such as the pings
+        let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = value.to_string(),
+            "data" => data.push(value.to_string()),
+            _ => {}
+        }
+    }
+    Err(format!("{addr} closed the event stream"))
+}
+
+/// The first address the server listens on, loopback when it listens on all.
+fn local_addr(config: &Config) -> Result<SocketAddr, String> {
+    let mut addr = config
+        .bind_addrs()
+        .into_iter()
+        .next()
+        .ok_or_else(|| "The server has no address to listen on".to_string())?;
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => addr.set_ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        IpAddr::V6(ip) if ip.is_unspecified() => addr.set_ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+        _ => {}
+    }
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::events::{self, EventKind, publish};
+    use crate::platform::mock;
+    use crate::server::ShortServer;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn follows_the_event_stream() {
+        let mut config = Config {
+            listen_addrs: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            // any free port
+            server_port: 0,
+            api_token: Some("token".to_string()),
+            hmac_secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let (_guard, _mock) = mock::install(config.clone());
+        let server = ShortServer::from_config().unwrap();
+        config.server_port = server.local_addrs()[0].port();
+        let shutdown = server.shutdown_handle();
+        let server = thread::spawn(move || server.start_server());
+
+        // refused without the token
+        let mut wrong = config.clone();
+        wrong.api_token = Some("nope".to_string());
+        let err = stream_events(&wrong, |_, _| {}).unwrap_err();
+        assert!(err.starts_with("HTTP/1.1 401 "), "{err}");
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            stream_events(&config, |name, data| {
+                let _ = tx.send((name.to_string(), data.to_string()));
+            })
+        });
+        // subscribed before the response starts
+        let started = Instant::now();
+        while events::subscribers() == 0 {
+            assert!(started.elapsed() < TIMEOUT, "the stream never subscribed");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(events::subscribe(1).is_none());
+
+        publish(EventKind::ScreenshotSaved {
+            path: PathBuf::from("/shots/events.png"),
+        });
+        publish(EventKind::DeviceReachable {
+            device: "events-tv".to_string(),
+        });
+        let mut received = vec![];
+        while received.len() < 2 {
+            let (name, data) = rx.recv_timeout(TIMEOUT).unwrap();
+            // other tests publish too
+            if data.contains("events") {
+                received.push((name, data));
+            }
+        }
+        assert_eq!(received[0].0, "screenshot_saved");
+        assert!(received[0].1.starts_with("{\"id\":"), "{}", received[0].1);
+        assert!(
+            received[0].1.contains("\"path\":\"/shots/events.png\""),
+            "{}",
+            received[0].1
+        );
+        assert_eq!(received[1].0, "device_reachable");
+        assert!(
+            received[1].1.contains("\"device\":\"events-tv\""),
+            "{}",
+            received[1].1
+        );
+
+        shutdown.shutdown();
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,570 @@
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::Arc;
+
+const MAX_LINE_LEN: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub version: String,
+    /// The request target as sent, path and query still encoded.
+    pub target: String,
+    /// The path without the query, still encoded so that `%2F` stays within
+    /// its segment. The router decodes each segment.
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Values of `{name}` segments, filled in by the router.
+    pub params: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    Io(io::Error),
+    BadRequest(String),
+    PayloadTooLarge,
+    /// Transfer codings such as chunked are not supported.
+    NotImplemented(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Io(err) => write!(f, "{err}"),
+            HttpError::BadRequest(msg) => write!(f, "{msg}"),
+            HttpError::PayloadTooLarge => write!(f, "Request body is too large"),
+            HttpError::NotImplemented(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(err: io::Error) -> Self {
+        HttpError::Io(err)
+    }
+}
+
+impl HttpError {
+    /// The response to send back, `None` when the connection is gone.
+    pub fn to_response(&self) -> Option<Response> {
+        let status = match self {
+            HttpError::Io(_) => return None,
+            HttpError::BadRequest(_) => 400,
+            HttpError::PayloadTooLarge => 413,
+            HttpError::NotImplemented(_) => 501,
+        };
+        Some(Response::text(status, self.to_string()))
+    }
+}
+
+impl Request {
+    /// Reads one request, returns `None` if the peer closed the connection before sending anything.
+    pub fn read_from<R: BufRead>(reader: &mut R) -> Result<Option<Self>, HttpError> {
+        let request_line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        let mut parts = request_line.split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) if parts.next().is_none() => {
+                (method, target, version)
+            }
+            _ => return Err(bad_request("Malformed request line")),
+        };
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(bad_request("Malformed method"));
+        }
+        if !version.starts_with("HTTP/1.") {
+            return Err(bad_request("Unsupported HTTP version"));
+        }
+        if !target.starts_with('/') {
+            return Err(bad_request("Request target must be an absolute path"));
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, parse_query(query)?),
+            None => (target, vec![]),
+        };
+        percent_decode(path, false)?;
+        let path = path.to_string();
+
+        let mut headers = vec![];
+        loop {
+            let line =
+                read_line(reader)?.ok_or_else(|| bad_request("Unexpected end of headers"))?;
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(bad_request("Too many headers"));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| bad_request("Malformed header"))?;
+            if name.is_empty() || name.ends_with(char::is_whitespace) {
+                return Err(bad_request("Malformed header name"));
+            }
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+
+        let mut req = Request {
+            method: method.to_string(),
+            version: version.to_string(),
+            target: target.to_string(),
+            path,
+            query,
+            headers,
+            body: vec![],
+            params: vec![],
+        };
+        req.read_body(reader)?;
+        Ok(Some(req))
+    }
+
+    fn read_body<R: BufRead>(&mut self, reader: &mut R) -> Result<(), HttpError> {
+        if self.header("Transfer-Encoding").is_some() {
+            return Err(HttpError::NotImplemented(
+                "Transfer-Encoding is not supported, send Content-Length".to_string(),
+            ));
+        }
+        let lengths = self
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .map(|(_, value)| value.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| bad_request("Invalid Content-Length"))?;
+        let len = match lengths.as_slice() {
+            [] => return Ok(()),
+            [len] => *len,
+            [first, rest @ ..] if rest.iter().all(|x| x == first) => *first,
+            _ => return Err(bad_request("Conflicting Content-Length")),
+        };
+        if len > MAX_BODY_LEN {
+            return Err(HttpError::PayloadTooLarge);
+        }
+        self.body = vec![0; len];
+        reader.read_exact(&mut self.body)?;
+        Ok(())
+    }
+
+    /// Case-insensitive header lookup, the first one wins.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        find(&self.query, name)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find(&self.params, name)
+    }
+
+    /// HTTP/1.1 keeps the connection open unless told otherwise, HTTP/1.0 the reverse.
+    pub fn keep_alive(&self) -> bool {
+        let has = |option: &str| {
+            self.header("Connection")
+                .is_some_and(|x| x.split(',').any(|x| x.trim().eq_ignore_ascii_case(option)))
+        };
+        if has("close") {
+            false
+        } else {
+            has("keep-alive") || self.version != "HTTP/1.0"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Written after the headers instead of `body`, e.g. server-sent events.
+    /// The connection closes once it returns.
+    pub stream: Option<Stream>,
+}
+
+type StreamFn = dyn Fn(&mut dyn Write) -> io::Result<()> + Send + Sync;
+
+/// A body of unknown length, written as it is produced.
+#[derive(Clone)]
+pub struct Stream(Arc<StreamFn>);
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Stream")
+    }
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+            stream: None,
+        }
+    }
+
+    pub fn stream(
+        status: u16,
+        content_type: &str,
+        write: impl Fn(&mut dyn Write) -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        let mut res = Self::new(status).with_body(content_type, vec![]);
+        res.stream = Some(Stream(Arc::new(write)));
+        res
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self::new(status).with_body("text/plain; charset=utf-8", body.into().into_bytes())
+    }
+
+    pub fn with_body(mut self, content_type: &str, body: Vec<u8>) -> Self {
+        self.headers
+            .push(("Content-Type".to_string(), content_type.to_string()));
+        self.body = body;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Writes status line, headers and body; `head` omits the body but keeps its length.
+    /// A stream is written until it ends, without a length.
+    pub fn write_to<W: Write>(&self, writer: &mut W, head: bool) -> io::Result<()> {
+        let mut buf = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            buf.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if self.stream.is_none() {
+            buf.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        buf.push_str("\r\n");
+        let mut bytes = buf.into_bytes();
+        if !head {
+            bytes.extend_from_slice(&self.body);
+        }
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+        match &self.stream {
+            Some(Stream(write)) if !head => write(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+fn find<'a>(pairs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn bad_request(msg: &str) -> HttpError {
+    HttpError::BadRequest(msg.to_string())
+}
+
+/// Reads a CRLF (or bare LF) terminated line, `None` on EOF before any byte.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, HttpError> {
+    let mut line = vec![];
+    let read = reader
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(if line.len() > MAX_LINE_LEN {
+            bad_request("Line too long")
+        } else {
+            bad_request("Unexpected end of request")
+        });
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| bad_request("Request is not valid UTF-8"))
+}
+
+fn parse_query(query: &str) -> Result<Vec<(String, String)>, HttpError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(key, true)?, percent_decode(value, true)?))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes, and `+` as space in query strings.
+pub fn percent_decode(text: &str, plus_as_space: bool) -> Result<String, HttpError> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'%' => {
+                let hex = [iter.next(), iter.next()];
+                let value = match hex {
+                    [Some(h), Some(l)] => std::str::from_utf8(&[h, l])
+                        .ok()
+                        .and_then(|x| u8::from_str_radix(x, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(value.ok_or_else(|| bad_request("Invalid percent encoding"))?);
+            }
+            b'+' if plus_as_space => bytes.push(b' '),
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| bad_request("Invalid percent encoding"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::*;
+
+    fn parse(raw: &str) -> Result<Option<Request>, HttpError> {
+        Request::read_from(&mut raw.as_bytes())
+    }
+
+    fn status(raw: &str) -> u16 {
+        let err = parse(raw).unwrap_err();
+        err.to_response().unwrap().status
+    }
+
+    #[test]
+    fn parses_request_line_and_query() {
+        let req = parse(
+            "GET /api/shortcuts/night%20light/run?device=tv&wait=false&x=a+b%2B HTTP/1.1\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.version, "HTTP/1.1");
+        assert_eq!(req.path, "/api/shortcuts/night%20light/run");
+        assert_eq!(
+            req.target,
+            "/api/shortcuts/night%20light/run?device=tv&wait=false&x=a+b%2B"
+        );
+        assert_eq!(req.query_param("device"), Some("tv"));
+        assert_eq!(req.query_param("wait"), Some("false"));
+        assert_eq!(req.query_param("x"), Some("a b+"));
+        assert_eq!(req.query_param("missing"), None);
+        assert!(req.body.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_request_lines() {
+        for raw in [
+            "GET /\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\n\r\n",
+            "get / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/2\r\n\r\n",
+            "GET http://host/ HTTP/1.1\r\n\r\n",
+            "GET /%zz HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\n",
+        ] {
+            assert_eq!(status(raw), 400, "{raw:?}");
+        }
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+        assert_eq!(status(&long), 400);
+    }
+
+    #[test]
+    fn empty_connection_is_not_a_request() {
+        assert!(parse("").unwrap().is_none());
+    }
+
+    #[test]
+    fn parses_headers() {
+        let req =
+            parse("GET / HTTP/1.1\nHost: example\r\nX-Token:  secret \r\nhost: second\r\n\r\n")
+                .unwrap()
+                .unwrap();
+        assert_eq!(req.header("HOST"), Some("example"));
+        assert_eq!(req.header("x-token"), Some("secret"));
+        assert_eq!(req.headers.len(), 3);
+        assert_eq!(status("GET / HTTP/1.1\r\nNo colon\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\nName : value\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\n: value\r\n\r\n"), 400);
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert_eq!(status(&many), 400);
+    }
+
+    #[test]
+    fn reads_body_by_content_length() {
+        let mut raw = "POST /api/reload HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET".as_bytes();
+        let req = Request::read_from(&mut raw).unwrap().unwrap();
+        assert_eq!(req.body, b"hello");
+        // the next request on the connection is left unread
+        assert_eq!(raw, b"GET");
+
+        let same = parse("POST / HTTP/1.1\r\nContent-Length: 2\r\ncontent-length: 2\r\n\r\nhi");
+        assert_eq!(same.unwrap().unwrap().body, b"hi");
+        // a body cut short means the client went away, there is no one to answer
+        let short = parse("POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhi");
+        assert!(matches!(short, Err(HttpError::Io(_))));
+    }
+
+    #[test]
+    fn rejects_conflicting_or_invalid_content_length() {
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\nhi!"),
+            400
+        );
+        assert_eq!(status("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"), 400);
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nContent-Length: 1, 1\r\n\r\nh"),
+            400
+        );
+        let large = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_LEN + 1
+        );
+        assert_eq!(status(&large), 413);
+    }
+
+    #[test]
+    fn chunked_is_not_implemented() {
+        let raw = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        assert_eq!(status(raw), 501);
+        assert!(matches!(parse(raw), Err(HttpError::NotImplemented(_))));
+    }
+
+    #[test]
+    fn keep_alive_depends_on_version_and_connection() {
+        let keep_alive = |raw: &str| parse(raw).unwrap().unwrap().keep_alive();
+        assert!(keep_alive("GET / HTTP/1.1\r\n\r\n"));
+        assert!(!keep_alive("GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+        assert!(!keep_alive(
+            "GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n\r\n"
+        ));
+        assert!(!keep_alive("GET / HTTP/1.0\r\n\r\n"));
+        assert!(keep_alive(
+            "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn writes_response_with_length() {
+        let res = Response::text(404, "missing").with_header("Allow", "GET");
+        let mut buf = vec![];
+        res.write_to(&mut buf, false).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Allow: GET\r\nContent-Length: 7\r\n\r\nmissing"
+        );
+    }
+
+    #[test]
+    fn head_keeps_length_without_body() {
+        let mut buf = vec![];
+        Response::text(200, "hello")
+            .write_to(&mut buf, true)
+            .unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("Content-Length: 5\r\n"));
+        assert!(text.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn stream_is_written_without_length() {
+        let res = Response::stream(200, "text/event-stream", |out| {
+            out.write_all(b"data: 1\n\n")
+        });
+        let mut buf = vec![];
+        res.write_to(&mut buf, false).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("Content-Length"));
+        assert!(text.ends_with("\r\n\r\ndata: 1\n\n"));
+    }
+
+    #[test]
+    fn serves_pipelined_requests_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut paths = vec![];
+            while let Some(req) = Request::read_from(&mut reader).unwrap() {
+                let res = Response::text(200, format!("{} {}", req.method, req.path));
+                res.write_to(&mut &stream, req.method == "HEAD").unwrap();
+                let keep_alive = req.keep_alive();
+                paths.push(req.path);
+                if !keep_alive {
+                    break;
+                }
+            }
+            paths
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .write_all(
+                b"GET /one HTTP/1.1\r\n\r\n\
+                  POST /two HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc\
+                  HEAD /three HTTP/1.1\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).unwrap();
+        assert_eq!(server.join().unwrap(), ["/one", "/two", "/three"]);
+        let responses = received.split("HTTP/1.1 200 OK\r\n").collect::<Vec<_>>();
+        assert_eq!(responses.len(), 4, "{received}");
+        assert!(responses[1].ends_with("\r\n\r\nGET /one"));
+        assert!(responses[2].ends_with("\r\n\r\nPOST /two"));
+        assert!(responses[3].ends_with("Content-Length: 11\r\n\r\n"));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%2Fb+c", false).unwrap(), "a/b+c");
+        assert_eq!(percent_decode("a%2fb+c", true).unwrap(), "a/b c");
+        assert_eq!(percent_decode("%E2%9C%93", false).unwrap(), "\u{2713}");
+        assert!(percent_decode("%2", false).is_err());
+        assert!(percent_decode("%ff", false).is_err());
+    }
+}
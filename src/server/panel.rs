@@ -0,0 +1,47 @@
+use super::http::{Request, Response};
+use super::router::Router;
+
+/// The control panel, a single page with its styles and script inline.
+const PAGE: &str = include_str!("panel.html");
+
+/// Serves the control panel at `/`.
+pub fn register(router: &mut Router) {
+    router.route("GET", "/", show_panel);
+}
+
+fn show_panel(_: &Request) -> Response {
+    Response::new(200)
+        .with_body("text/html; charset=utf-8", PAGE.as_bytes().to_vec())
+        .with_header("Cache-Control", "no-cache")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::request;
+    use crate::config::Config;
+    use crate::platform::mock;
+
+    #[test]
+    fn serves_the_page() {
+        let (_guard, _mock) = mock::install(Config::default());
+        let response = request("GET", "/");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(response.contains("Cache-Control: no-cache\r\n"));
+        assert!(response.ends_with(super::PAGE));
+    }
+
+    #[test]
+    fn page_needs_the_token() {
+        let config = Config::from_toml("[server]\ntoken = \"secret\"\n").unwrap();
+        let (_guard, _mock) = mock::install(config);
+        let response = request("GET", "/");
+        assert!(response.starts_with("HTTP/1.1 401 "), "{response}");
+        assert!(response.contains("WWW-Authenticate: Bearer\r\n"));
+        assert!(!response.contains("text/html"));
+        assert!(request("GET", "/?token=nope").starts_with("HTTP/1.1 401 "));
+        let response = request("GET", "/?token=secret");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: text/html"));
+    }
+}
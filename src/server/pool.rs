@@ -0,0 +1,118 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::log;
+
+/// A fixed number of workers running one handler over items from a bounded
+/// queue. Items beyond the queue are handed back instead of piling up.
+pub struct WorkerPool<T: Send + 'static> {
+    sender: Option<SyncSender<T>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> WorkerPool<T> {
+    pub fn new<F>(workers: usize, queue: usize, handler: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = sync_channel::<T>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler = Arc::new(handler);
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let receiver = receiver.clone();
+                let handler = handler.clone();
+                thread::Builder::new()
+                    .name(format!("server-worker-{i}"))
+                    .spawn(move || worker(&receiver, &*handler))
+                    .expect("spawn server worker")
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queues the item, or returns it when every worker is busy and the queue is full.
+    pub fn execute(&self, item: T) -> Result<(), T> {
+        match self.sender.as_ref().unwrap().try_send(item) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(item) | TrySendError::Disconnected(item)) => Err(item),
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for WorkerPool<T> {
+    /// Lets queued items finish, then joins the workers.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                log!("A server worker panicked");
+            }
+        }
+    }
+}
+
+/// A panicking handler only loses its own item, the worker goes on with the next one.
+fn worker<T>(receiver: &Mutex<Receiver<T>>, handler: &dyn Fn(T)) {
+    loop {
+        let item = receiver.lock().unwrap().recv();
+        match item {
+            Ok(item) => {
+                if catch_unwind(AssertUnwindSafe(|| handler(item))).is_err() {
+                    log!("A server worker panicked while handling a request");
+                }
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn survives_panicking_handler() {
+        let (done, finished) = channel();
+        let done = Mutex::new(done);
+        let pool = WorkerPool::new(1, 8, move |item: u32| {
+            if item.is_multiple_of(2) {
+                panic!("item {item}");
+            }
+            done.lock().unwrap().send(item).unwrap();
+        });
+        for item in 0..6 {
+            pool.execute(item).unwrap();
+        }
+        drop(pool);
+        assert_eq!(finished.iter().collect::<Vec<_>>(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn hands_back_items_when_full() {
+        let (release, wait) = channel::<()>();
+        let wait = Mutex::new(wait);
+        let pool = WorkerPool::new(1, 1, move |_: u32| {
+            let _ = wait.lock().unwrap().recv_timeout(Duration::from_secs(5));
+        });
+        pool.execute(1).unwrap();
+        // let the worker pick up the first item so the second one is queued
+        thread::sleep(Duration::from_millis(100));
+        pool.execute(2).unwrap();
+        assert_eq!(pool.execute(3), Err(3));
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+    }
+}
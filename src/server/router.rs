@@ -0,0 +1,205 @@
+use super::http::{Request, Response, percent_decode};
+
+pub type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+struct Route {
+    method: String,
+    segments: Vec<Segment>,
+    handler: Handler,
+}
+
+impl Route {
+    /// `path` is still percent-encoded, each segment is decoded once split off.
+    fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let parts = split_path(path);
+        if parts.len() != self.segments.len() {
+            return None;
+        }
+        let mut params = vec![];
+        for (segment, part) in self.segments.iter().zip(parts) {
+            let part = percent_decode(part, false).ok()?;
+            match segment {
+                Segment::Literal(text) if *text == part => {}
+                Segment::Param(name) if !part.is_empty() => params.push((name.clone(), part)),
+                _ => return None,
+            }
+        }
+        Some(params)
+    }
+}
+
+/// Dispatches on method and path. Patterns are plain paths where a `{name}`
+/// segment matches anything and is exposed through `Request::param`.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route<F>(&mut self, method: &str, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let segments = split_path(pattern)
+            .into_iter()
+            .map(
+                |part| match part.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(part.to_string()),
+                },
+            )
+            .collect();
+        self.routes.push(Route {
+            method: method.to_string(),
+            segments,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// HEAD is served by the GET handler, 405 lists the allowed methods when only the path matches.
+    pub fn handle(&self, req: &mut Request) -> Response {
+        let method = if req.method == "HEAD" {
+            "GET"
+        } else {
+            req.method.as_str()
+        };
+        let mut allowed: Vec<&str> = vec![];
+        for route in &self.routes {
+            let Some(params) = route.matches(&req.path) else {
+                continue;
+            };
+            if route.method == method {
+                req.params = params;
+                return (route.handler)(req);
+            }
+            if !allowed.contains(&route.method.as_str()) {
+                allowed.push(&route.method);
+            }
+        }
+        if allowed.is_empty() {
+            return Response::text(404, format!("No route for {}", req.path));
+        }
+        if allowed.contains(&"GET") {
+            allowed.push("HEAD");
+        }
+        Response::text(405, format!("{} is not allowed here", req.method))
+            .with_header("Allow", allowed.join(", "))
+    }
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.trim_start_matches('/').split('/').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str) -> Request {
+        let raw = format!("{method} {path} HTTP/1.1\r\n\r\n");
+        Request::read_from(&mut raw.as_bytes()).unwrap().unwrap()
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router
+            .route("GET", "/api/shortcuts", |_| Response::text(200, "list"))
+            .route("POST", "/api/shortcuts/{name}/run", |req| {
+                Response::text(200, format!("run {}", req.param("name").unwrap()))
+            })
+            .route("GET", "/devices/{device}/{shortcut}", |req| {
+                let params = format!("{:?}", req.params);
+                Response::text(200, params)
+            })
+            .route("POST", "/api/reload", |_| Response::text(200, "reloaded"));
+        router
+    }
+
+    fn handle(method: &str, path: &str) -> Response {
+        router().handle(&mut request(method, path))
+    }
+
+    fn body(res: &Response) -> &str {
+        std::str::from_utf8(&res.body).unwrap()
+    }
+
+    fn allow(res: &Response) -> Option<&str> {
+        res.headers
+            .iter()
+            .find(|(name, _)| name == "Allow")
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn matches_literal_and_param_segments() {
+        let res = handle("GET", "/api/shortcuts");
+        assert_eq!((res.status, body(&res)), (200, "list"));
+        let res = handle("POST", "/api/shortcuts/night%20light/run");
+        assert_eq!((res.status, body(&res)), (200, "run night light"));
+        let res = handle("GET", "/devices/tv/wake");
+        assert_eq!(body(&res), r#"[("device", "tv"), ("shortcut", "wake")]"#);
+    }
+
+    #[test]
+    fn decodes_each_segment() {
+        let res = handle("POST", "/api/shortcuts/a%2Fb/run");
+        assert_eq!((res.status, body(&res)), (200, "run a/b"));
+        let res = handle("GET", "/devices/living%20room/tv%2Fwake");
+        assert_eq!(
+            body(&res),
+            r#"[("device", "living room"), ("shortcut", "tv/wake")]"#
+        );
+        assert_eq!(handle("GET", "/api/%73hortcuts").status, 200);
+        // a decoded slash doesn't make a literal segment match
+        assert_eq!(handle("GET", "/api%2Fshortcuts").status, 404);
+    }
+
+    #[test]
+    fn unknown_path_is_404() {
+        assert_eq!(handle("GET", "/missing").status, 404);
+        assert_eq!(handle("GET", "/api/shortcuts/extra").status, 404);
+        // a parameter doesn't match an empty segment
+        assert_eq!(handle("POST", "/api/shortcuts//run").status, 404);
+        assert_eq!(handle("GET", "/api").status, 404);
+    }
+
+    #[test]
+    fn wrong_method_is_405_with_allow() {
+        let res = handle("DELETE", "/api/shortcuts");
+        assert_eq!(res.status, 405);
+        assert_eq!(allow(&res), Some("GET, HEAD"));
+        let res = handle("GET", "/api/reload");
+        assert_eq!(res.status, 405);
+        assert_eq!(allow(&res), Some("POST"));
+    }
+
+    #[test]
+    fn head_uses_the_get_handler() {
+        let res = handle("HEAD", "/api/shortcuts");
+        assert_eq!((res.status, body(&res)), (200, "list"));
+        let res = handle("HEAD", "/api/reload");
+        assert_eq!(res.status, 405);
+    }
+
+    #[test]
+    fn first_route_wins() {
+        let mut router = router();
+        router.route("GET", "/api/{anything}", |_| {
+            Response::text(200, "fallback")
+        });
+        let res = router.handle(&mut request("GET", "/api/shortcuts"));
+        assert_eq!(body(&res), "list");
+        let res = router.handle(&mut request("GET", "/api/other"));
+        assert_eq!(body(&res), "fallback");
+    }
+}
@@ -1,133 +1,313 @@
-use std::{sync::OnceLock, thread, time};
+pub mod action;
+pub mod job;
+pub mod step;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::{
-    alert,
-    constants::APP_CONFIG,
-    screen::{modes::CaptureMode, take_screenshot_for_windows},
-    utils::{
-        adb::{
-            capture_screen_adb, connect_tv_adb, sleep_tv_adb, switch_to_home, switch_to_port_4,
-            wakeup_tv_adb,
-        },
-        clipboard::clear_clipboard,
-        explorer::kill_explorer,
-        inputs::close_top_window,
-        magic_packet::MagicPacket,
-        monitors::{set_external_display, set_internal_display},
-        night_light::disable_night_light,
-    },
+    config::{DEFAULT_DEVICE, ShortcutConfig, app_config},
+    constants::IDM_SHORTCUTS,
+    error::Error,
+    utils::keys::Hotkey,
 };
 
+use job::{Concurrency, Job};
+use step::{Background, Step, StepResult};
+
+/// What a shortcut produced, shown by the tray and returned to HTTP callers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ActionOutput {
+    #[default]
+    None,
+    Text(String),
+    File(PathBuf),
+}
+
 #[derive(Clone)]
 pub struct Shortcut {
     pub id: Option<usize>,
-    pub func: fn() -> (),
+    pub name: String,
+    /// The device it targets when run without one.
+    pub device: Option<String>,
+    pub steps: Vec<Step>,
     pub is_left_click: bool,
     pub menu_name: Option<String>,
     pub web_req_url: Option<String>,
+    pub hotkey: Option<Hotkey>,
+    pub concurrency: Concurrency,
+}
+
+impl Shortcut {
+    /// The device a run targets: `device` if given, else the shortcut's own, else `[tv]`.
+    pub fn device_for<'a>(&'a self, device: Option<&'a str>) -> &'a str {
+        device.or(self.device.as_deref()).unwrap_or(DEFAULT_DEVICE)
+    }
+
+    /// Runs the steps in order and stops at the first one that fails and isn't
+    /// set to continue, or when the job is cancelled. Returns the output of the
+    /// last step that produced one.
+    fn run_job(&self, job: &Job, background: &mut Background) -> Result<ActionOutput, Error> {
+        let started = Instant::now();
+        let mut steps = vec![];
+        let result = self.run_steps(job, &mut steps, background);
+        let record = RunRecord {
+            name: self.name.clone(),
+            device: job.device.clone(),
+            finished_at: SystemTime::now(),
+            duration: started.elapsed(),
+            result: result
+                .as_ref()
+                .map(Clone::clone)
+                .map_err(ToString::to_string),
+            steps,
+        };
+        LAST_RUNS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .insert((self.name.clone(), job.device.clone()), record);
+        result
+    }
+
+    fn run_steps(
+        &self,
+        job: &Job,
+        results: &mut Vec<StepResult>,
+        background: &mut Background,
+    ) -> Result<ActionOutput, Error> {
+        let config = app_config();
+        let device = config
+            .device(&job.device)
+            .ok_or_else(|| Error::config(format!("Unknown device '{}'", job.device)))?;
+        let mut output = ActionOutput::None;
+        for step in &self.steps {
+            if job.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let res = step.run(&config, device, results, background);
+            job.progress(results);
+            match res? {
+                ActionOutput::None => {}
+                res => output = res,
+            }
+        }
+        Ok(output)
+    }
 }
 
-pub static SHORTCUTS: OnceLock<Vec<Shortcut>> = OnceLock::new();
+/// Outcome of the latest run of a shortcut.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub name: String,
+    pub device: String,
+    pub finished_at: SystemTime,
+    pub duration: Duration,
+    pub result: Result<ActionOutput, String>,
+    pub steps: Vec<StepResult>,
+}
+
+static SHORTCUTS: RwLock<Option<Arc<Vec<Shortcut>>>> = RwLock::new(None);
+/// By shortcut and device name.
+static LAST_RUNS: OnceLock<Mutex<HashMap<(String, String), RunRecord>>> = OnceLock::new();
+
+/// The latest run of every shortcut on every device since start, oldest first.
+pub fn last_runs() -> Vec<RunRecord> {
+    let mut res = LAST_RUNS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    res.sort_by_key(|x| x.finished_at);
+    res
+}
+
+/// The shortcuts built from the config in effect.
+pub fn shortcuts() -> Arc<Vec<Shortcut>> {
+    SHORTCUTS.read().unwrap().clone().unwrap_or_default()
+}
+
+/// Looks a shortcut up by name, or by menu id.
+pub fn find_shortcut(id: &str) -> Option<Shortcut> {
+    let shortcuts = shortcuts();
+    let found = shortcuts.iter().find(|x| x.name == id).or_else(|| {
+        let id = id.parse::<usize>().ok()?;
+        shortcuts.iter().find(|x| x.id == Some(id))
+    });
+    found.cloned()
+}
 
+/// Rebuilds the shortcuts from the config in effect.
 pub fn build_shortcuts() {
-    thread::spawn(|| {
-        let _ = SHORTCUTS.get_or_init(|| {
-            vec![
-                Shortcut {
-                    id: Some(8),
-                    func: || {
-                        let txt = APP_CONFIG.get().unwrap().screen_dir.to_owned();
-                        alert!("{}", txt);
-                    },
-                    is_left_click: false,
-                    menu_name: Some("Test".to_string()),
-                    web_req_url: Some("/test_connection".to_string()),
-                },
-                Shortcut {
-                    id: Some(9),
-                    func: || {
-                        let ip = APP_CONFIG.get().unwrap().tv_ip_addr.to_owned();
-                        let dir = APP_CONFIG.get().unwrap().screen_dir.to_owned();
-                        connect_tv_adb(&ip);
-                        capture_screen_adb(&dir);
-                    },
-                    is_left_click: false,
-                    menu_name: Some("Capture Screen".to_string()),
-                    web_req_url: Some("/capture_screen".to_string()),
-                },
-                Shortcut {
-                    id: Some(19),
-                    func: || {
-                        let dir = APP_CONFIG.get().unwrap().screen_dir.to_owned();
-                        let _ = take_screenshot_for_windows(&dir, CaptureMode::Primary);
-                    },
-                    is_left_click: false,
-                    menu_name: Some("Capture Windows Screen".to_string()),
-                    web_req_url: Some("/capture_windows_screen".to_string()),
-                },
-                Shortcut {
-                    id: Some(10),
-                    func: || {
-                        let mac = APP_CONFIG.get().unwrap().tv_mac_addr;
-                        let ip = &APP_CONFIG.get().unwrap().tv_ip_addr;
-                        thread::spawn(move || {
-                            let magic_p = MagicPacket::new(&mac);
-                            let res = magic_p.send();
-                            if let Ok(_) = res {
-                                thread::sleep(time::Duration::from_millis(1000));
-                                connect_tv_adb(ip);
-                                thread::sleep(time::Duration::from_millis(200));
-                                wakeup_tv_adb();
-                                thread::sleep(time::Duration::from_millis(200));
-                                switch_to_port_4();
-                                thread::sleep(time::Duration::from_millis(200));
-                                set_external_display();
-                                disable_night_light().unwrap();
-                            }
-                        });
-                    },
-                    is_left_click: false,
-                    menu_name: Some("Switch to TV".to_string()),
-                    web_req_url: Some("/switch_to_tv".to_string()),
-                },
-                Shortcut {
-                    id: Some(11),
-                    func: || {
-                        let ip = &APP_CONFIG.get().unwrap().tv_ip_addr;
-                        thread::spawn(move || {
-                            connect_tv_adb(&ip);
-                            thread::sleep(time::Duration::from_millis(200));
-                            switch_to_home();
-                            thread::sleep(time::Duration::from_millis(200));
-                            // enable_night_light().unwrap();
-                            set_internal_display();
-                            sleep_tv_adb();
-                        });
-                    },
-                    is_left_click: false,
-                    menu_name: Some("Switch to Monitor".to_string()),
-                    web_req_url: Some("/switch_to_monitor".to_string()),
-                },
-                Shortcut {
-                    id: None,
-                    func: || {
-                        clear_clipboard();
-                        kill_explorer();
-                    },
-                    is_left_click: true,
-                    menu_name: None,
-                    web_req_url: None,
-                },
-                Shortcut {
-                    id: None,
-                    func: || close_top_window(),
-                    is_left_click: false,
-                    menu_name: None,
-                    web_req_url: Some("/close_top_window".to_string()),
-                },
-            ]
-        });
-    })
-    .join()
-    .unwrap();
+    let shortcuts = from_config(&app_config().shortcuts);
+    *SHORTCUTS.write().unwrap() = Some(Arc::new(shortcuts));
+}
+
+/// Turns the configured definitions into shortcuts, giving menu entries consecutive ids.
+pub fn from_config(definitions: &[ShortcutConfig]) -> Vec<Shortcut> {
+    let mut next_id = IDM_SHORTCUTS;
+    definitions
+        .iter()
+        .map(|def| {
+            let id = def.menu.as_ref().map(|_| {
+                next_id += 1;
+                next_id - 1
+            });
+            Shortcut {
+                id,
+                name: def.name.clone(),
+                device: def.device.clone(),
+                steps: def.steps.clone(),
+                is_left_click: def.left_click,
+                menu_name: def.menu.clone(),
+                web_req_url: def.route.clone(),
+                hotkey: def.hotkey,
+                concurrency: def.concurrency,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::platform::mock;
+    use crate::utils::monitors::DisplayTopology;
+
+    const CONFIG: &str = r#"
+[capture]
+dir = "/shots"
+
+[[shortcuts]]
+name = "desk"
+route = "/desk"
+actions = ["send_keys ctrl+alt+t", "display internal", "clear_clipboard", "screenshot primary"]
+
+[[shortcuts]]
+name = "restart_explorer"
+route = "/restart_explorer"
+actions = ["kill_explorer", "send_keys f5"]
+
+[[shortcuts]]
+name = "tidy"
+actions = ["kill_explorer | on_error continue", "clear_clipboard | retry 1 0"]
+"#;
+
+    fn run(name: &str) -> (Arc<Job>, Result<ActionOutput, String>) {
+        let shortcut = find_shortcut(name).unwrap();
+        let job = job::submit(&shortcut, None).ok().expect("job refused");
+        let res = job.wait();
+        (job, res)
+    }
+
+    #[test]
+    fn runs_actions_on_the_platform() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        let (job, res) = run("desk");
+        assert_eq!(
+            res,
+            Ok(ActionOutput::File(PathBuf::from("/shots/mock-0.png")))
+        );
+        let state = mock.state();
+        assert_eq!(state.keys, [vec![0x11, 0x12, 0x54]]);
+        assert_eq!(state.topology, Some(DisplayTopology::Internal));
+        assert_eq!(state.clipboard_clears, 1);
+        assert_eq!(state.screenshots, [PathBuf::from("/shots/mock-0.png")]);
+        let status = job.status();
+        assert_eq!(status.state, job::JobState::Succeeded);
+        assert_eq!(status.steps.len(), 4);
+        let record = last_runs().into_iter().find(|x| x.name == "desk").unwrap();
+        assert_eq!(record.device, "tv");
+        assert!(record.result.is_ok());
+    }
+
+    #[test]
+    fn failed_step_stops_the_shortcut() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        mock.update(|x| x.fail_next = Some("access denied".to_string()));
+        let (job, res) = run("restart_explorer");
+        assert_eq!(res, Err("Fail to kill explorer, access denied".to_string()));
+        assert_eq!(job.status().state, job::JobState::Failed);
+        assert_eq!(job.status().steps.len(), 1);
+        // send_keys never ran
+        assert!(mock.state().keys.is_empty());
+    }
+
+    #[test]
+    fn failed_step_can_continue_or_retry() {
+        let (_guard, mock) = mock::install(Config::from_toml(CONFIG).unwrap());
+        mock.update(|x| x.fail_next = Some("access denied".to_string()));
+        assert_eq!(run("tidy").1, Ok(ActionOutput::None));
+        assert_eq!(mock.state().explorer_kills, 0);
+        assert_eq!(mock.state().clipboard_clears, 1);
+
+        // the first clear fails, its retry succeeds
+        let shortcut = find_shortcut("tidy").unwrap();
+        mock.update(|x| x.fail_next = Some("busy".to_string()));
+        let steps = Shortcut {
+            steps: shortcut.steps[1..].to_vec(),
+            ..shortcut
+        };
+        let job = job::submit(&steps, None).ok().unwrap();
+        assert_eq!(job.wait(), Ok(ActionOutput::None));
+        assert_eq!(mock.state().clipboard_clears, 2);
+        assert_eq!(job.status().steps[0].attempts, 2);
+    }
+
+    #[test]
+    fn menu_ids_are_stable() {
+        const MENUS: &str = r#"
+[[shortcuts]]
+name = "first"
+menu = "First"
+actions = ["echo 1"]
+
+[[shortcuts]]
+name = "hidden"
+actions = ["echo 2"]
+
+[[shortcuts]]
+name = "second"
+menu = "Second"
+actions = ["echo 3"]
+"#;
+        let config = Config::from_toml(MENUS).unwrap();
+        let (_guard, _mock) = mock::install(config.clone());
+        let ids = |shortcuts: &[Shortcut]| {
+            shortcuts
+                .iter()
+                .map(|x| (x.name.clone(), x.id))
+                .collect::<HashMap<_, _>>()
+        };
+        let before = ids(&shortcuts());
+        // after the menu entries of the default shortcuts
+        let defaults = Config::default()
+            .shortcuts
+            .iter()
+            .filter(|x| x.menu.is_some())
+            .count();
+        assert_eq!(before["first"], Some(IDM_SHORTCUTS + defaults));
+        assert_eq!(before["hidden"], None);
+        assert_eq!(before["second"], Some(IDM_SHORTCUTS + defaults + 1));
+        assert_eq!(
+            find_shortcut(&before["second"].unwrap().to_string())
+                .unwrap()
+                .name,
+            "second"
+        );
+
+        // a reload of the same config hands out the same ids
+        crate::config::set_app_config(config);
+        build_shortcuts();
+        assert_eq!(ids(&shortcuts()), before);
+        let defs = &app_config().shortcuts;
+        assert_eq!(ids(&from_config(defs)), ids(&from_config(defs)));
+    }
 }
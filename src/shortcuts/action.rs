@@ -0,0 +1,450 @@
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, DeviceConfig};
+use crate::error::Error;
+use crate::events::{EventKind, publish};
+use crate::log;
+use crate::platform::platform;
+use crate::screen::modes::CaptureMode;
+use crate::utils::adb::{
+    AndroidKey, capture_screen_adb, connect_tv_adb, ensure_power, input_text, long_press,
+    send_keyevent, swipe, tap, wait_for_device,
+};
+use crate::utils::keys::parse_key_combo;
+use crate::utils::magic_packet::MagicPacket;
+use crate::utils::monitors::DisplayTopology;
+use crate::utils::night_light::{disable_night_light, enable_night_light};
+
+use super::ActionOutput;
+
+/// How long `adb_swipe` takes when no duration is given.
+const DEFAULT_SWIPE: Duration = Duration::from_millis(300);
+
+/// A built-in step of a shortcut, written in config as `name [argument]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// `wake_on_lan`, sends a magic packet to the device
+    WakeOnLan,
+    /// `wait_for_device`, until adb answers, resending the magic packet meanwhile
+    WaitForDevice,
+    /// `adb_connect`
+    AdbConnect,
+    /// `adb_key KEYCODE_HOME`, `adb_key home` or `adb_key 3`
+    AdbKey(AndroidKey),
+    /// `adb_long_press KEYCODE_POWER`
+    AdbLongPress(AndroidKey),
+    /// `adb_text <text>`, types into the focused field
+    AdbText(String),
+    /// `adb_tap 540 960`
+    AdbTap(u32, u32),
+    /// `adb_swipe 540 1500 540 500 [ms]`
+    AdbSwipe {
+        from: (u32, u32),
+        to: (u32, u32),
+        duration: Duration,
+    },
+    /// `adb_wake`, sends the device's wake keys
+    AdbWake,
+    /// `adb_sleep`, sends the device's sleep keys
+    AdbSleep,
+    /// `adb_ensure_on`, sends the wake keys unless the device is already on
+    AdbEnsureOn,
+    /// `adb_ensure_off`, sends the sleep keys unless the device is already off
+    AdbEnsureOff,
+    /// `adb_input`, switches the device to this PC's input
+    AdbInput,
+    /// `adb_screenshot`, saves the device screen to the screenshot folder
+    AdbScreenshot,
+    /// `display internal|external|extend|clone`
+    Display(DisplayTopology),
+    /// `night_light on|off`
+    NightLight(bool),
+    /// `screenshot primary`, `screenshot monitor 2` or `screenshot window <title>`
+    Screenshot(CaptureMode),
+    /// `send_keys alt+f4`
+    SendKeys(String),
+    /// `delay 200`, in milliseconds
+    Delay(Duration),
+    /// `clear_clipboard`
+    ClearClipboard,
+    /// `kill_explorer`
+    KillExplorer,
+    /// `echo <text>`, `{screen_dir}`, `{tv_ip}`, `{device}` and `{device_ip}` are replaced
+    Echo(String),
+}
+
+impl Action {
+    /// Runs the action, adb and wake-on-lan ones against `device`.
+    pub fn run(&self, config: &Config, device: &DeviceConfig) -> Result<ActionOutput, Error> {
+        let serial = device.adb_serial();
+        match self {
+            Action::WakeOnLan => {
+                let mac = device.mac.as_ref().ok_or_else(|| {
+                    Error::config(format!("Device '{}' has no MAC address", device.name))
+                })?;
+                magic_packet(mac, device)
+                    .send()
+                    .map_err(|err| Error::network("Fail to send magic packet", err))?;
+            }
+            Action::WaitForDevice => {
+                let mut last_sent = Instant::now();
+                let resend = || {
+                    let interval = device.wol_interval;
+                    if let Some(mac) = &device.mac
+                        && !interval.is_zero()
+                        && last_sent.elapsed() >= interval
+                    {
+                        if let Err(err) = magic_packet(mac, device).send() {
+                            log!("Fail to resend magic packet to {}, {err}", device.name);
+                        }
+                        last_sent = Instant::now();
+                    }
+                };
+                let waited =
+                    wait_for_device(serial, device.ready_timeout, resend).map_err(|err| {
+                        let secs = device.ready_timeout.as_secs();
+                        Error::network(
+                            format!("Device '{}' did not come up within {secs}s", device.name),
+                            err,
+                        )
+                    })?;
+                log!("{} is up after {:.1}s", device.name, waited.as_secs_f32());
+            }
+            Action::AdbConnect => connect_tv_adb(serial)?,
+            Action::AdbKey(key) => send_keyevent(serial, *key)?,
+            Action::AdbLongPress(key) => long_press(serial, *key)?,
+            Action::AdbText(text) => input_text(serial, text)?,
+            Action::AdbTap(x, y) => tap(serial, *x, *y)?,
+            Action::AdbSwipe { from, to, duration } => swipe(serial, *from, *to, *duration)?,
+            Action::AdbWake => {
+                for key in &device.wake_keys {
+                    send_keyevent(serial, *key)?;
+                }
+            }
+            Action::AdbSleep => {
+                for key in &device.sleep_keys {
+                    send_keyevent(serial, *key)?;
+                }
+            }
+            Action::AdbEnsureOn => {
+                ensure_power(serial, true, &device.wake_keys)?;
+            }
+            Action::AdbEnsureOff => {
+                ensure_power(serial, false, &device.sleep_keys)?;
+            }
+            Action::AdbInput => {
+                let key = device.input.ok_or_else(|| {
+                    Error::config(format!("Device '{}' has no input keycode", device.name))
+                })?;
+                send_keyevent(serial, key)?;
+            }
+            Action::AdbScreenshot => {
+                let file = capture_screen_adb(serial, &config.screen_dir)?;
+                publish(EventKind::ScreenshotSaved { path: file.clone() });
+                return Ok(ActionOutput::File(file));
+            }
+            Action::Display(topology) => platform().set_display_topology(*topology)?,
+            Action::NightLight(true) => enable_night_light()?,
+            Action::NightLight(false) => disable_night_light()?,
+            Action::Screenshot(mode) => {
+                let file = platform().take_screenshot(&config.screen_dir, mode)?;
+                publish(EventKind::ScreenshotSaved { path: file.clone() });
+                return Ok(ActionOutput::File(file));
+            }
+            Action::SendKeys(combo) => {
+                let keys = parse_key_combo(combo).map_err(Error::config)?;
+                platform().send_keys(&keys)?;
+            }
+            Action::Delay(duration) => thread::sleep(*duration),
+            Action::ClearClipboard => platform().clear_clipboard()?,
+            Action::KillExplorer => platform().kill_explorer()?,
+            Action::Echo(text) => {
+                let text = text
+                    .replace("{screen_dir}", &config.screen_dir)
+                    .replace("{tv_ip}", &config.tv().ip)
+                    .replace("{device}", &device.name)
+                    .replace("{device_ip}", &device.ip);
+                return Ok(ActionOutput::Text(text));
+            }
+        }
+        Ok(ActionOutput::None)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, arg) = match s.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (s, ""),
+        };
+        let no_arg = |action: Action| {
+            if arg.is_empty() {
+                Ok(action)
+            } else {
+                Err(format!("'{name}' takes no argument"))
+            }
+        };
+        match name {
+            "wake_on_lan" => no_arg(Action::WakeOnLan),
+            "wait_for_device" => no_arg(Action::WaitForDevice),
+            "adb_connect" => no_arg(Action::AdbConnect),
+            "adb_key" => Ok(Action::AdbKey(arg.parse()?)),
+            "adb_long_press" => Ok(Action::AdbLongPress(arg.parse()?)),
+            "adb_text" if !arg.is_empty() => Ok(Action::AdbText(arg.to_string())),
+            "adb_text" => Err("'adb_text' needs the text to type".to_string()),
+            "adb_tap" => match numbers(arg)?[..] {
+                [x, y] => Ok(Action::AdbTap(x, y)),
+                _ => Err(format!("Expected 'adb_tap x y', got '{arg}'")),
+            },
+            "adb_swipe" => {
+                let (from, to, ms) = match numbers(arg)?[..] {
+                    [x1, y1, x2, y2] => ((x1, y1), (x2, y2), None),
+                    [x1, y1, x2, y2, ms] => ((x1, y1), (x2, y2), Some(ms)),
+                    _ => {
+                        return Err(format!(
+                            "Expected 'adb_swipe x1 y1 x2 y2 [ms]', got '{arg}'"
+                        ));
+                    }
+                };
+                let duration = ms.map_or(DEFAULT_SWIPE, |ms| Duration::from_millis(ms.into()));
+                Ok(Action::AdbSwipe { from, to, duration })
+            }
+            "adb_wake" => no_arg(Action::AdbWake),
+            "adb_sleep" => no_arg(Action::AdbSleep),
+            "adb_ensure_on" => no_arg(Action::AdbEnsureOn),
+            "adb_ensure_off" => no_arg(Action::AdbEnsureOff),
+            "adb_input" => no_arg(Action::AdbInput),
+            "adb_screenshot" => no_arg(Action::AdbScreenshot),
+            "display" => Ok(Action::Display(arg.parse()?)),
+            "night_light" => match arg {
+                "on" => Ok(Action::NightLight(true)),
+                "off" => Ok(Action::NightLight(false)),
+                _ => Err(format!("Expected on or off, got '{arg}'")),
+            },
+            "screenshot" => {
+                let (kind, target) = match arg.split_once(char::is_whitespace) {
+                    Some((kind, target)) => (kind, target.trim()),
+                    None => (arg, ""),
+                };
+                let mode = match (kind, target) {
+                    ("" | "primary", "") => CaptureMode::Primary,
+                    ("monitor", id) => CaptureMode::Monitor(
+                        id.parse()
+                            .map_err(|_| format!("Invalid monitor id '{id}'"))?,
+                    ),
+                    ("window", title) if !title.is_empty() => {
+                        CaptureMode::Window(title.to_string())
+                    }
+                    _ => return Err(format!("Invalid screenshot target '{arg}'")),
+                };
+                Ok(Action::Screenshot(mode))
+            }
+            "send_keys" => {
+                parse_key_combo(arg)?;
+                Ok(Action::SendKeys(arg.to_string()))
+            }
+            "delay" => {
+                let ms = arg
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid delay '{arg}', expected milliseconds"))?;
+                Ok(Action::Delay(Duration::from_millis(ms)))
+            }
+            "clear_clipboard" => no_arg(Action::ClearClipboard),
+            "kill_explorer" => no_arg(Action::KillExplorer),
+            "echo" => Ok(Action::Echo(arg.to_string())),
+            _ => Err(format!("Unknown action '{name}'")),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::WakeOnLan => write!(f, "wake_on_lan"),
+            Action::WaitForDevice => write!(f, "wait_for_device"),
+            Action::AdbConnect => write!(f, "adb_connect"),
+            Action::AdbKey(key) => write!(f, "adb_key {key}"),
+            Action::AdbLongPress(key) => write!(f, "adb_long_press {key}"),
+            Action::AdbText(text) => write!(f, "adb_text {text}"),
+            Action::AdbTap(x, y) => write!(f, "adb_tap {x} {y}"),
+            Action::AdbSwipe { from, to, duration } => write!(
+                f,
+                "adb_swipe {} {} {} {} {}",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                duration.as_millis()
+            ),
+            Action::AdbWake => write!(f, "adb_wake"),
+            Action::AdbSleep => write!(f, "adb_sleep"),
+            Action::AdbEnsureOn => write!(f, "adb_ensure_on"),
+            Action::AdbEnsureOff => write!(f, "adb_ensure_off"),
+            Action::AdbInput => write!(f, "adb_input"),
+            Action::AdbScreenshot => write!(f, "adb_screenshot"),
+            Action::Display(topology) => write!(f, "display {topology}"),
+            Action::NightLight(on) => write!(f, "night_light {}", if *on { "on" } else { "off" }),
+            Action::Screenshot(CaptureMode::Primary) => write!(f, "screenshot primary"),
+            Action::Screenshot(CaptureMode::Monitor(id)) => write!(f, "screenshot monitor {id}"),
+            Action::Screenshot(CaptureMode::Window(title)) => {
+                write!(f, "screenshot window {title}")
+            }
+            Action::SendKeys(combo) => write!(f, "send_keys {combo}"),
+            Action::Delay(duration) => write!(f, "delay {}", duration.as_millis()),
+            Action::ClearClipboard => write!(f, "clear_clipboard"),
+            Action::KillExplorer => write!(f, "kill_explorer"),
+            Action::Echo(text) => write!(f, "echo {text}"),
+        }
+    }
+}
+
+fn magic_packet(mac: &[u8; 6], device: &DeviceConfig) -> MagicPacket {
+    let packet = MagicPacket::new(mac);
+    match &device.wol_password {
+        Some(password) => packet.with_password(password),
+        None => packet,
+    }
+}
+
+/// Screen coordinates and durations, separated by spaces.
+fn numbers(arg: &str) -> Result<Vec<u32>, String> {
+    arg.split_whitespace()
+        .map(|x| x.parse().map_err(|_| format!("Invalid number '{x}'")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Action, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let actions = [
+            "wake_on_lan",
+            "wait_for_device",
+            "adb_connect",
+            "adb_key KEYCODE_HOME",
+            "adb_long_press KEYCODE_POWER",
+            "adb_text hello world",
+            "adb_tap 540 960",
+            "adb_swipe 540 1500 540 500 250",
+            "adb_wake",
+            "adb_sleep",
+            "adb_ensure_on",
+            "adb_ensure_off",
+            "adb_input",
+            "adb_screenshot",
+            "display internal",
+            "display external",
+            "display extend",
+            "display clone",
+            "night_light on",
+            "night_light off",
+            "screenshot primary",
+            "screenshot monitor 2",
+            "screenshot window Notepad - notes.txt",
+            "send_keys alt+f4",
+            "delay 200",
+            "clear_clipboard",
+            "kill_explorer",
+            "echo {device} at {device_ip}",
+        ];
+        for text in actions {
+            let action = parse(text).unwrap();
+            assert_eq!(action.to_string(), text);
+            assert_eq!(parse(&action.to_string()), Ok(action));
+        }
+    }
+
+    #[test]
+    fn display_is_canonical() {
+        let cases = [
+            ("  adb_key home ", "adb_key KEYCODE_HOME"),
+            ("adb_key 3", "adb_key KEYCODE_HOME"),
+            ("adb_swipe 1 2 3 4", "adb_swipe 1 2 3 4 300"),
+            ("screenshot", "screenshot primary"),
+            ("screenshot window   Notepad", "screenshot window Notepad"),
+            ("echo", "echo "),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text).unwrap().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let cases = [
+            ("", "Unknown action ''"),
+            ("reboot", "Unknown action 'reboot'"),
+            ("adb_connect now", "'adb_connect' takes no argument"),
+            ("kill_explorer -f", "'kill_explorer' takes no argument"),
+            ("adb_key NOPE", "Unknown Android key 'NOPE'"),
+            ("night_light dim", "Expected on or off, got 'dim'"),
+            ("screenshot monitor one", "Invalid monitor id 'one'"),
+            ("screenshot window", "Invalid screenshot target 'window'"),
+            ("screenshot all", "Invalid screenshot target 'all'"),
+            ("send_keys ctrl+nope", "Unknown key 'nope'"),
+            ("send_keys", "Empty key combination"),
+            ("delay soon", "Invalid delay 'soon', expected milliseconds"),
+            ("delay -5", "Invalid delay '-5', expected milliseconds"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text), Err(expected.to_string()), "{text}");
+        }
+        assert!(parse("display sideways").is_err());
+    }
+
+    #[test]
+    fn parses_adb_input() {
+        assert_eq!(
+            parse("adb_text  it's \"on\"; 50% "),
+            Ok(Action::AdbText("it's \"on\"; 50%".to_string()))
+        );
+        assert_eq!(parse("adb_tap 540  960"), Ok(Action::AdbTap(540, 960)));
+        assert_eq!(
+            parse("adb_swipe 540 1500 540 500"),
+            Ok(Action::AdbSwipe {
+                from: (540, 1500),
+                to: (540, 500),
+                duration: DEFAULT_SWIPE,
+            })
+        );
+        assert_eq!(
+            parse("adb_swipe 1 2 3 4 1000"),
+            Ok(Action::AdbSwipe {
+                from: (1, 2),
+                to: (3, 4),
+                duration: Duration::from_secs(1),
+            })
+        );
+        let cases = [
+            ("adb_text", "'adb_text' needs the text to type"),
+            ("adb_tap 540", "Expected 'adb_tap x y', got '540'"),
+            ("adb_tap 1 2 3", "Expected 'adb_tap x y', got '1 2 3'"),
+            ("adb_tap 540 -1", "Invalid number '-1'"),
+            ("adb_tap x 1", "Invalid number 'x'"),
+            (
+                "adb_swipe 1 2 3",
+                "Expected 'adb_swipe x1 y1 x2 y2 [ms]', got '1 2 3'",
+            ),
+            (
+                "adb_swipe 1 2 3 4 5 6",
+                "Expected 'adb_swipe x1 y1 x2 y2 [ms]', got '1 2 3 4 5 6'",
+            ),
+            ("adb_swipe 1 2 3 4 fast", "Invalid number 'fast'"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text), Err(expected.to_string()), "{text}");
+        }
+    }
+}
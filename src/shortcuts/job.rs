@@ -0,0 +1,412 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::error::Error;
+use crate::events::{EventKind, publish};
+use crate::log;
+
+use super::step::{Background, StepResult};
+use super::{ActionOutput, Shortcut};
+
+/// Finished jobs kept for `GET /api/jobs`, the oldest are dropped first.
+const MAX_FINISHED: usize = 100;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+/// Oldest first.
+static JOBS: Mutex<VecDeque<Arc<Job>>> = Mutex::new(VecDeque::new());
+/// Feeds the worker running the queued jobs of a shortcut on a device, by shortcut and device name.
+type Queue = mpsc::Sender<(Shortcut, Arc<Job>)>;
+static QUEUES: Mutex<Option<HashMap<(String, String), Queue>>> = Mutex::new(None);
+
+/// What happens when a shortcut is started while it is already running on the same device.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Concurrency {
+    /// Waits for the earlier runs to finish.
+    #[default]
+    Queue,
+    /// Refuses to start.
+    Reject,
+    /// Runs alongside them.
+    Parallel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Running)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub state: JobState,
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+    /// How the steps run so far went.
+    pub steps: Vec<StepResult>,
+    /// Set once the job finished.
+    pub result: Option<Result<ActionOutput, String>>,
+}
+
+/// One run of a shortcut, from the tray, HTTP or anywhere else.
+pub struct Job {
+    pub id: u64,
+    pub shortcut: String,
+    pub device: String,
+    pub total_steps: usize,
+    pub created_at: SystemTime,
+    cancelled: AtomicBool,
+    status: Mutex<JobStatus>,
+    finished: Condvar,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Stops the job before its next step. Returns false when it already finished.
+    pub fn cancel(&self) -> bool {
+        let status = self.status.lock().unwrap();
+        if status.state.is_finished() {
+            return false;
+        }
+        self.cancelled.store(true, Ordering::Relaxed);
+        true
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until the job finished and returns its result.
+    pub fn wait(&self) -> Result<ActionOutput, String> {
+        let mut status = self.status.lock().unwrap();
+        loop {
+            if let Some(result) = &status.result {
+                return result.clone();
+            }
+            status = self.finished.wait(status).unwrap();
+        }
+    }
+
+    pub(super) fn progress(&self, steps: &[StepResult]) {
+        self.status.lock().unwrap().steps = steps.to_vec();
+    }
+
+    /// Runs the shortcut, then waits for its timed out actions so the next
+    /// queued job doesn't run alongside them.
+    fn run(&self, shortcut: &Shortcut) {
+        let mut background = Background::default();
+        let result = if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            {
+                let mut status = self.status.lock().unwrap();
+                status.state = JobState::Running;
+                status.started_at = Some(SystemTime::now());
+            }
+            publish(EventKind::ShortcutStarted {
+                job: self.id,
+                shortcut: self.shortcut.clone(),
+                device: self.device.clone(),
+            });
+            catch_unwind(AssertUnwindSafe(|| shortcut.run_job(self, &mut background)))
+                .unwrap_or_else(|payload| Err(Error::panicked(payload)))
+        };
+        let state = match &result {
+            Ok(_) => JobState::Succeeded,
+            Err(Error::Cancelled) => JobState::Cancelled,
+            Err(err) => {
+                log!("{} failed, {err}", self.shortcut);
+                JobState::Failed
+            }
+        };
+        let (job, shortcut, device) = (self.id, self.shortcut.clone(), self.device.clone());
+        publish(match &result {
+            Ok(output) => EventKind::ShortcutFinished {
+                job,
+                shortcut,
+                device,
+                output: output.clone(),
+            },
+            Err(err) => EventKind::ShortcutFailed {
+                job,
+                shortcut,
+                device,
+                error: err.to_string(),
+            },
+        });
+        let mut status = self.status.lock().unwrap();
+        status.state = state;
+        status.finished_at = Some(SystemTime::now());
+        status.result = Some(result.map_err(|err| err.to_string()));
+        self.finished.notify_all();
+        drop(status);
+        background.wait();
+    }
+}
+
+/// Starts `shortcut` as a new job on `device`, or on its own device when `None`.
+/// Fails with the job already queued or running when the shortcut rejects concurrent runs.
+pub fn submit(shortcut: &Shortcut, device: Option<&str>) -> Result<Arc<Job>, Arc<Job>> {
+    let device = shortcut.device_for(device).to_string();
+    let mut jobs = JOBS.lock().unwrap();
+    if shortcut.concurrency == Concurrency::Reject
+        && let Some(running) = jobs.iter().find(|x| {
+            x.shortcut == shortcut.name && x.device == device && !x.status().state.is_finished()
+        })
+    {
+        return Err(running.clone());
+    }
+    let job = Arc::new(Job {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        shortcut: shortcut.name.clone(),
+        device: device.clone(),
+        total_steps: shortcut.steps.len(),
+        created_at: SystemTime::now(),
+        cancelled: AtomicBool::new(false),
+        status: Mutex::new(JobStatus {
+            state: JobState::Queued,
+            started_at: None,
+            finished_at: None,
+            steps: vec![],
+            result: None,
+        }),
+        finished: Condvar::new(),
+    });
+    jobs.push_back(job.clone());
+    prune(&mut jobs);
+    drop(jobs);
+    let work = (shortcut.clone(), job.clone());
+    match shortcut.concurrency {
+        Concurrency::Parallel => {
+            thread::spawn(move || work.1.run(&work.0));
+        }
+        Concurrency::Queue | Concurrency::Reject => {
+            let mut queues = QUEUES.lock().unwrap();
+            let queues = queues.get_or_insert_default();
+            let key = (shortcut.name.clone(), device);
+            let queue = queues.entry(key.clone()).or_insert_with(spawn_queue);
+            // a worker that died hands the job back, its queue is replaced
+            if let Err(mpsc::SendError(work)) = queue.send(work) {
+                log!("The queue of {} on {} stopped, restarting it", key.0, key.1);
+                let queue = spawn_queue();
+                let _ = queue.send(work);
+                queues.insert(key, queue);
+            }
+        }
+    }
+    Ok(job)
+}
+
+fn spawn_queue() -> Queue {
+    let (tx, rx) = mpsc::channel::<(Shortcut, Arc<Job>)>();
+    thread::spawn(move || {
+        for (shortcut, job) in rx {
+            job.run(&shortcut);
+        }
+    });
+    tx
+}
+
+/// The jobs still queued or running and the latest finished ones, oldest first.
+pub fn jobs() -> Vec<Arc<Job>> {
+    JOBS.lock().unwrap().iter().cloned().collect()
+}
+
+pub fn find_job(id: u64) -> Option<Arc<Job>> {
+    JOBS.lock().unwrap().iter().find(|x| x.id == id).cloned()
+}
+
+fn prune(jobs: &mut VecDeque<Arc<Job>>) {
+    let mut finished = jobs
+        .iter()
+        .filter(|x| x.status().state.is_finished())
+        .count();
+    jobs.retain(|x| {
+        if finished > MAX_FINISHED && x.status().state.is_finished() {
+            finished -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+impl FromStr for Concurrency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "queue" => Ok(Concurrency::Queue),
+            "reject" => Ok(Concurrency::Reject),
+            "parallel" => Ok(Concurrency::Parallel),
+            _ => Err(format!(
+                "Invalid concurrency '{s}', expected queue, reject or parallel"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Concurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Concurrency::Queue => write!(f, "queue"),
+            Concurrency::Reject => write!(f, "reject"),
+            Concurrency::Parallel => write!(f, "parallel"),
+        }
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Queued => write!(f, "queued"),
+            JobState::Running => write!(f, "running"),
+            JobState::Succeeded => write!(f, "succeeded"),
+            JobState::Failed => write!(f, "failed"),
+            JobState::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::platform::mock;
+
+    fn shortcut(name: &str, steps: &[&str]) -> Shortcut {
+        Shortcut {
+            id: None,
+            name: name.to_string(),
+            device: None,
+            steps: steps.iter().map(|x| x.parse().unwrap()).collect(),
+            is_left_click: false,
+            menu_name: None,
+            web_req_url: None,
+            hotkey: None,
+            concurrency: Concurrency::Queue,
+        }
+    }
+
+    fn start(shortcut: &Shortcut) -> Arc<Job> {
+        submit(shortcut, None).ok().expect("job refused")
+    }
+
+    #[test]
+    fn panicking_action_fails_the_job() {
+        let (_guard, mock) = mock::install(Config::default());
+        let shortcut = shortcut("panics", &["clear_clipboard"]);
+        mock.update(|x| x.panic_next = Some("boom".to_string()));
+        let job = start(&shortcut);
+        let err = job.wait().unwrap_err();
+        assert!(err.contains("boom"), "{err}");
+        assert_eq!(job.status().state, JobState::Failed);
+
+        // the queue still runs the next job
+        let job = start(&shortcut);
+        assert_eq!(job.wait(), Ok(ActionOutput::None));
+        assert_eq!(mock.state().clipboard_clears, 1);
+    }
+
+    #[test]
+    fn panicking_action_with_timeout_fails_the_step() {
+        let (_guard, mock) = mock::install(Config::default());
+        let shortcut = shortcut("panics with timeout", &["clear_clipboard | timeout 5"]);
+        mock.update(|x| x.panic_next = Some("boom".to_string()));
+        let job = start(&shortcut);
+        let err = job.wait().unwrap_err();
+        assert!(err.contains("boom"), "{err}");
+        assert!(!err.contains("timed out"), "{err}");
+    }
+
+    #[test]
+    fn dead_queue_is_replaced() {
+        let (_guard, mock) = mock::install(Config::default());
+        let shortcut = shortcut("dead queue", &["clear_clipboard"]);
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        QUEUES
+            .lock()
+            .unwrap()
+            .get_or_insert_default()
+            .insert((shortcut.name.clone(), "tv".to_string()), tx);
+        let job = start(&shortcut);
+        assert_eq!(job.wait(), Ok(ActionOutput::None));
+        assert_eq!(mock.state().clipboard_clears, 1);
+    }
+
+    #[test]
+    fn reject_refuses_a_second_run() {
+        let (_guard, _mock) = mock::install(Config::default());
+        let mut shortcut = shortcut("reject", &["delay 200"]);
+        shortcut.concurrency = Concurrency::Reject;
+        let first = start(&shortcut);
+        let second = submit(&shortcut, None).err().expect("second run started");
+        assert_eq!(first.id, second.id);
+        first.wait().unwrap();
+        start(&shortcut).wait().unwrap();
+    }
+
+    #[test]
+    fn cancelled_queued_job_never_runs() {
+        let (_guard, mock) = mock::install(Config::default());
+        let running = start(&shortcut("cancel queued", &["delay 300"]));
+        let queued = start(&shortcut("cancel queued", &["clear_clipboard"]));
+        assert_eq!(queued.status().state, JobState::Queued);
+        assert!(queued.cancel());
+        assert!(queued.wait().is_err());
+        let status = queued.status();
+        assert_eq!(status.state, JobState::Cancelled);
+        assert_eq!(status.started_at, None);
+        assert!(status.steps.is_empty());
+        assert_eq!(running.wait(), Ok(ActionOutput::None));
+        assert_eq!(mock.state().clipboard_clears, 0);
+    }
+
+    #[test]
+    fn cancel_stops_before_the_next_step() {
+        let (_guard, mock) = mock::install(Config::default());
+        let job = start(&shortcut(
+            "cancel running",
+            &["delay 300", "clear_clipboard"],
+        ));
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(job.status().state, JobState::Running);
+        assert!(job.cancel());
+        assert!(job.wait().is_err());
+        let status = job.status();
+        assert_eq!(status.state, JobState::Cancelled);
+        // the step running was let finish
+        assert_eq!(status.steps.len(), 1);
+        assert!(status.steps[0].result.is_ok());
+        assert_eq!(mock.state().clipboard_clears, 0);
+    }
+
+    #[test]
+    fn finished_job_cannot_be_cancelled() {
+        let (_guard, _mock) = mock::install(Config::default());
+        let job = start(&shortcut("cancel finished", &["clear_clipboard"]));
+        job.wait().unwrap();
+        assert!(!job.cancel());
+        assert!(!job.is_cancelled());
+        assert_eq!(job.status().state, JobState::Succeeded);
+    }
+}
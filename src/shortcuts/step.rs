@@ -0,0 +1,349 @@
+use std::fmt;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::str::FromStr;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, DeviceConfig};
+use crate::error::Error;
+use crate::log;
+
+use super::ActionOutput;
+use super::action::Action;
+
+/// Separates an action from its options, e.g. `adb_connect | retry 3 | timeout 10`.
+const SEPARATOR: &str = " | ";
+const OPTIONS: [&str; 4] = ["delay", "retry", "timeout", "on_error"];
+/// Wait between attempts when `retry` gives none.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// What a step does once its action failed every attempt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OnError {
+    /// Stops the shortcut with the error.
+    #[default]
+    Abort,
+    /// Goes on with the next step.
+    Continue,
+    /// Runs this action instead, and stops the shortcut if that fails too.
+    Fallback(Action),
+}
+
+/// An action and how to run it, written in config as `action [| option]...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub action: Action,
+    /// `delay 500`, waits that many milliseconds before the action
+    pub delay: Duration,
+    /// `retry 3 [ms]`, tries again up to 3 times, `ms` apart
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// `timeout 10`, gives up on an attempt after 10 seconds. The action itself
+    /// can't be stopped and keeps running, see `Background`.
+    pub timeout: Option<Duration>,
+    /// `on_error abort`, `on_error continue` or `on_error <action>`
+    pub on_error: OnError,
+}
+
+/// How one step of a run went.
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub step: String,
+    pub attempts: u32,
+    pub duration: Duration,
+    pub result: Result<ActionOutput, String>,
+    /// The result of the `on_error` action, when it ran.
+    pub fallback: Option<Result<ActionOutput, String>>,
+}
+
+/// Actions that timed out but are still running, since a thread can't be stopped.
+/// Nothing else runs on the device until they end: not a retry, not the
+/// `on_error` action, not the next step, and not the next queued job.
+#[derive(Default)]
+pub struct Background {
+    running: Vec<(String, mpsc::Receiver<()>)>,
+}
+
+impl Background {
+    pub fn wait(&mut self) {
+        for (action, done) in self.running.drain(..) {
+            log!("Waiting for {action}, which timed out, to end");
+            let _ = done.recv();
+        }
+    }
+}
+
+impl From<Action> for Step {
+    fn from(action: Action) -> Self {
+        Step {
+            action,
+            delay: Duration::ZERO,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            timeout: None,
+            on_error: OnError::Abort,
+        }
+    }
+}
+
+impl Step {
+    /// Runs the step against `device` and adds how it went to `results`.
+    /// Fails only when the shortcut should stop. An action that timed out is
+    /// added to `background`.
+    pub fn run(
+        &self,
+        config: &Arc<Config>,
+        device: &DeviceConfig,
+        results: &mut Vec<StepResult>,
+        background: &mut Background,
+    ) -> Result<ActionOutput, Error> {
+        let started = Instant::now();
+        if !self.delay.is_zero() {
+            thread::sleep(self.delay);
+        }
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            match self.attempt(&self.action, config, device, background) {
+                Err(err) if attempts <= self.retries => {
+                    log!(
+                        "{} failed, attempt {attempts} of {}, {err}",
+                        self.action,
+                        self.retries + 1
+                    );
+                    thread::sleep(self.retry_delay);
+                }
+                res => break res,
+            }
+        };
+        let mut record = StepResult {
+            step: self.to_string(),
+            attempts,
+            duration: Duration::ZERO,
+            result: result
+                .as_ref()
+                .map(Clone::clone)
+                .map_err(ToString::to_string),
+            fallback: None,
+        };
+        let result = match (result, &self.on_error) {
+            (Ok(output), _) => Ok(output),
+            (Err(err), OnError::Abort) => Err(err),
+            (Err(err), OnError::Continue) => {
+                log!("{} failed, continuing, {err}", self.action);
+                Ok(ActionOutput::None)
+            }
+            (Err(err), OnError::Fallback(action)) => {
+                log!("{} failed, running {action}, {err}", self.action);
+                let res = self.attempt(action, config, device, background);
+                record.fallback = Some(res.as_ref().map(Clone::clone).map_err(ToString::to_string));
+                res
+            }
+        };
+        record.duration = started.elapsed();
+        results.push(record);
+        result
+    }
+
+    /// Runs `action` once, on a worker when there is a timeout, after the
+    /// actions still in the `background` ended. A timed out action is added to it.
+    fn attempt(
+        &self,
+        action: &Action,
+        config: &Arc<Config>,
+        device: &DeviceConfig,
+        background: &mut Background,
+    ) -> Result<ActionOutput, Error> {
+        background.wait();
+        let Some(timeout) = self.timeout else {
+            return action.run(config, device);
+        };
+        let (result_tx, result_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+        let worker = (action.clone(), config.clone(), device.clone());
+        thread::spawn(move || {
+            let (action, config, device) = worker;
+            let res = catch_unwind(AssertUnwindSafe(|| action.run(&config, &device)));
+            let _ = result_tx.send(res.unwrap_or_else(|payload| Err(Error::panicked(payload))));
+            drop(done_tx);
+        });
+        match result_rx.recv_timeout(timeout) {
+            Ok(res) => res,
+            Err(_) => {
+                background.running.push((action.to_string(), done_rx));
+                Err(Error::timeout(action.to_string(), timeout))
+            }
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Only trailing parts that start like an option are options, so `echo a | b` stays an echo
+        let mut action = s.trim();
+        let mut options = vec![];
+        while let Some((rest, option)) = action.rsplit_once(SEPARATOR)
+            && OPTIONS.contains(&option.split_whitespace().next().unwrap_or_default())
+        {
+            options.push(option.trim());
+            action = rest.trim_end();
+        }
+        let mut step = Step::from(action.parse::<Action>()?);
+        for option in options.into_iter().rev() {
+            let (name, arg) = match option.split_once(char::is_whitespace) {
+                Some((name, arg)) => (name, arg.trim()),
+                None => (option, ""),
+            };
+            match name {
+                "delay" => step.delay = Duration::from_millis(number(name, arg)?),
+                "retry" => {
+                    let (times, ms) = match arg.split_once(char::is_whitespace) {
+                        Some((times, ms)) => (times, Some(ms.trim())),
+                        None => (arg, None),
+                    };
+                    step.retries = number(name, times)?
+                        .try_into()
+                        .map_err(|_| format!("Too many retries '{times}'"))?;
+                    if let Some(ms) = ms {
+                        step.retry_delay = Duration::from_millis(number(name, ms)?);
+                    }
+                }
+                "timeout" => match number(name, arg)? {
+                    0 => return Err("'timeout' must be at least 1 second".to_string()),
+                    secs => step.timeout = Some(Duration::from_secs(secs)),
+                },
+                _ => {
+                    step.on_error = match arg {
+                        "abort" => OnError::Abort,
+                        "continue" => OnError::Continue,
+                        "" => {
+                            return Err("'on_error' needs abort, continue or an action".to_string());
+                        }
+                        action => OnError::Fallback(action.parse()?),
+                    }
+                }
+            }
+        }
+        Ok(step)
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.action)?;
+        if !self.delay.is_zero() {
+            write!(f, "{SEPARATOR}delay {}", self.delay.as_millis())?;
+        }
+        if self.retries > 0 {
+            write!(f, "{SEPARATOR}retry {}", self.retries)?;
+            if self.retry_delay != DEFAULT_RETRY_DELAY {
+                write!(f, " {}", self.retry_delay.as_millis())?;
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            write!(f, "{SEPARATOR}timeout {}", timeout.as_secs())?;
+        }
+        match &self.on_error {
+            OnError::Abort => Ok(()),
+            OnError::Continue => write!(f, "{SEPARATOR}on_error continue"),
+            OnError::Fallback(action) => write!(f, "{SEPARATOR}on_error {action}"),
+        }
+    }
+}
+
+fn number(option: &str, arg: &str) -> Result<u64, String> {
+    arg.parse()
+        .map_err(|_| format!("Invalid '{option}' value '{arg}', expected a number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(step: &str) -> (Result<ActionOutput, Error>, Vec<StepResult>, Background) {
+        let config = Arc::new(Config::default());
+        let device = config.tv().clone();
+        let mut results = vec![];
+        let mut background = Background::default();
+        let step = step.parse::<Step>().unwrap();
+        let res = step.run(&config, &device, &mut results, &mut background);
+        (res, results, background)
+    }
+
+    #[test]
+    fn parses_options() {
+        let step = "adb_connect | delay 500 | retry 3 250 | timeout 10 | on_error continue"
+            .parse::<Step>()
+            .unwrap();
+        assert_eq!(step.action, Action::AdbConnect);
+        assert_eq!(step.delay, Duration::from_millis(500));
+        assert_eq!(step.retries, 3);
+        assert_eq!(step.retry_delay, Duration::from_millis(250));
+        assert_eq!(step.timeout, Some(Duration::from_secs(10)));
+        assert_eq!(step.on_error, OnError::Continue);
+        assert_eq!(
+            step.to_string(),
+            "adb_connect | delay 500 | retry 3 250 | timeout 10 | on_error continue"
+        );
+
+        let step = "display internal | on_error display external"
+            .parse::<Step>()
+            .unwrap();
+        assert_eq!(
+            step.on_error,
+            OnError::Fallback("display external".parse().unwrap())
+        );
+        // not an option, so part of the echo
+        let step = "echo a | b".parse::<Step>().unwrap();
+        assert_eq!(step.action, Action::Echo("a | b".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        for step in [
+            "adb_connect | timeout 0",
+            "adb_connect | timeout x",
+            "adb_connect | retry",
+            "adb_connect | delay -1",
+            "adb_connect | on_error",
+            "adb_connect | on_error unknown_action",
+        ] {
+            assert!(step.parse::<Step>().is_err(), "{step}");
+        }
+    }
+
+    #[test]
+    fn timed_out_attempt_ends_before_the_retry() {
+        let started = Instant::now();
+        let (res, results, mut background) = run("delay 1500 | retry 1 0 | timeout 1");
+        assert!(matches!(res, Err(Error::Timeout { .. })), "{res:?}");
+        assert_eq!(results[0].attempts, 2);
+        // the retry started once the first delay ended at 1.5s, and timed out 1s later
+        assert!(started.elapsed() >= Duration::from_millis(2400));
+        assert_eq!(background.running.len(), 1);
+        background.wait();
+        assert!(started.elapsed() >= Duration::from_millis(3000));
+    }
+
+    #[test]
+    fn timed_out_attempt_ends_before_the_fallback() {
+        let started = Instant::now();
+        let (res, results, background) = run("delay 1500 | timeout 1 | on_error echo late");
+        assert!(matches!(res, Ok(ActionOutput::Text(ref text)) if text == "late"));
+        assert!(started.elapsed() >= Duration::from_millis(1500));
+        assert!(results[0].result.is_err());
+        assert!(results[0].fallback.as_ref().unwrap().is_ok());
+        assert!(background.running.is_empty());
+    }
+
+    #[test]
+    fn fast_action_is_within_timeout() {
+        let (res, results, background) = run("echo hi | timeout 5");
+        assert!(matches!(res, Ok(ActionOutput::Text(ref text)) if text == "hi"));
+        assert_eq!(results[0].attempts, 1);
+        assert!(background.running.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use windows::Win32::System::Registry::{REG_SZ, RegDeleteValueW, RegSetValueExW};
 use windows::core::{PCWSTR, w};
 
+use crate::error::Error;
 use crate::utils::others::get_exe_path;
 use crate::utils::registry::{get_key, get_value};
 
@@ -13,12 +14,12 @@ pub struct Startup {
 }
 
 impl Startup {
-    pub fn init() -> Result<Self, String> {
+    pub fn init() -> Result<Self, Error> {
         let enable = Self::detect()?;
         Ok(Self { is_enable: enable })
     }
 
-    pub fn toggle(&mut self) -> Result<(), String> {
+    pub fn toggle(&mut self) -> Result<(), Error> {
         let is_enable = self.is_enable;
         if is_enable {
             Self::disable()?;
@@ -30,7 +31,7 @@ impl Startup {
         Ok(())
     }
 
-    fn detect() -> Result<bool, String> {
+    fn detect() -> Result<bool, Error> {
         let key = get_key(HKEY_RUN)?;
         let value = match get_value(&key.hkey, HKEY_NAME)? {
             Some(value) => value,
@@ -40,25 +41,19 @@ impl Startup {
         Ok(value == path)
     }
 
-    fn enable() -> Result<(), String> {
+    fn enable() -> Result<(), Error> {
         let key = get_key(HKEY_RUN)?;
         let path = get_exe_path();
         let path_u8 = unsafe { path.align_to::<u8>().1 };
         let ret = unsafe { RegSetValueExW(key.hkey, HKEY_NAME, Some(0), REG_SZ, Some(path_u8)) };
-        if ret.is_err() {
-            let err = format!("Fail to write reg value, {:?}", ret);
-            return Err(err);
-        }
-        Ok(())
+        ret.ok()
+            .map_err(|err| Error::registry("Fail to write reg value", err))
     }
 
-    fn disable() -> Result<(), String> {
+    fn disable() -> Result<(), Error> {
         let key = get_key(HKEY_RUN)?;
         let ret = unsafe { RegDeleteValueW(key.hkey, HKEY_NAME) };
-        if ret.is_err() {
-            let err = format!("Fail to delele reg value, {:?}", ret);
-            return Err(err);
-        }
-        Ok(())
+        ret.ok()
+            .map_err(|err| Error::registry("Fail to delete reg value", err))
     }
 }
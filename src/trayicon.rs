@@ -1,5 +1,5 @@
-use crate::constants::{APP_NAME, IDM_EXIT, IDM_STARTUP, WM_USER_TRAYICON};
-use crate::shortcuts::{SHORTCUTS, Shortcut};
+use crate::constants::{APP_NAME, IDM_EXIT, IDM_RELOAD, IDM_STARTUP, WM_USER_TRAYICON};
+use crate::shortcuts::shortcuts;
 
 use windows::Win32::Foundation::{HWND, POINT};
 use windows::Win32::UI::Shell::{
@@ -14,6 +14,7 @@ use windows::core::{HSTRING, PCWSTR, w};
 
 const ICON_BYTES: &[u8] = include_bytes!("../windows.ico");
 const TEXT_STARTUP: PCWSTR = w!("Startup");
+const TEXT_RELOAD: PCWSTR = w!("Reload config");
 const TEXT_EXIT: PCWSTR = w!("Exit");
 
 pub struct TrayIcon {
@@ -86,19 +87,12 @@ impl TrayIcon {
             let hmenu = CreatePopupMenu().map_err(|err| format!("Failed to create menu, {err}"))?;
             let _ = AppendMenuW(hmenu, startup_flags, IDM_STARTUP as usize, TEXT_STARTUP);
 
-            let scs = SHORTCUTS
-                .get()
-                .unwrap()
-                .to_vec()
-                .into_iter()
-                .filter(|x| x.id.is_some() && x.menu_name.is_some())
-                .collect::<Vec<Shortcut>>();
-
-            for ele in scs {
-                let name = ele.menu_name.unwrap();
-                let name = &HSTRING::from(name);
-                let _ = AppendMenuW(hmenu, MF_STRING, ele.id.unwrap(), name);
+            for ele in shortcuts().iter() {
+                if let (Some(id), Some(name)) = (ele.id, &ele.menu_name) {
+                    let _ = AppendMenuW(hmenu, MF_STRING, id, &HSTRING::from(name));
+                }
             }
+            let _ = AppendMenuW(hmenu, MF_STRING, IDM_RELOAD as usize, TEXT_RELOAD);
 
             let _ = AppendMenuW(hmenu, MF_STRING, IDM_EXIT as usize, TEXT_EXIT);
             Ok(hmenu)
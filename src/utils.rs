@@ -1,12 +1,21 @@
 pub mod adb;
+pub mod base64;
+#[cfg(windows)]
 pub mod clipboard;
+#[cfg(windows)]
 pub mod errors;
+#[cfg(windows)]
 pub mod explorer;
+#[cfg(windows)]
 pub mod inputs;
+#[cfg(windows)]
 pub mod instance;
+pub mod keys;
 pub mod macros;
 pub mod magic_packet;
 pub mod monitors;
 pub mod night_light;
 pub mod others;
+#[cfg(windows)]
 pub mod registry;
+pub mod sha256;
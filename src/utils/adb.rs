@@ -1,91 +1,459 @@
-#![allow(unused)]
-
-use std::{
-    fs,
-    process::{Command, Stdio},
-    time::SystemTime,
-};
-
-use crate::constants::{KEYCODE_CEC_HDMI4, KEYCODE_HOME, KEYCODE_SLEEP, KEYCODE_WAKEUP};
-
-pub fn wakeup_tv_adb() {
-    Command::new("adb")
-        .arg("shell")
-        .arg("input")
-        .arg("keyevent")
-        .arg(KEYCODE_WAKEUP)
-        .arg("VORBOSE")
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to wake up tv");
-}
-
-pub fn sleep_tv_adb() {
-    Command::new("adb")
-        .arg("shell")
-        .arg("input")
-        .arg("keyevent")
-        .arg(KEYCODE_SLEEP)
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to sleep tv");
-}
-
-pub fn connect_tv_adb(ip: &str) {
-    Command::new("adb")
-        .arg("connect")
-        .arg(ip)
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to connect tv");
-}
-
-pub fn switch_to_port_4() {
-    Command::new("adb")
-        .arg("shell")
-        .arg("input")
-        .arg("keyevent")
-        .arg(KEYCODE_CEC_HDMI4.to_string())
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to sleep tv");
-}
-
-pub fn switch_to_home() {
-    Command::new("adb")
-        .arg("shell")
-        .arg("input")
-        .arg("keyevent")
-        .arg(KEYCODE_HOME)
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to switch to home");
-}
-
-pub fn capture_screen_adb(dir: &str) {
-    let time = std::time::SystemTime::now()
+mod bignum;
+mod device;
+mod keycode;
+mod message;
+mod rsa;
+mod state;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+pub use device::AdbDevice;
+pub use keycode::AndroidKey;
+use rsa::RsaKey;
+pub use state::{DeviceState, PowerState, Wakefulness};
+
+const DEFAULT_ADB_PORT: u16 = 5555;
+/// How long a device gets to turn on or off after the keys were sent.
+const POWER_TIMEOUT: Duration = Duration::from_secs(5);
+const POWER_POLL: Duration = Duration::from_millis(250);
+/// Readiness probes start this far apart and back off up to `PROBE_MAX_DELAY`.
+const PROBE_MIN_DELAY: Duration = Duration::from_millis(250);
+const PROBE_MAX_DELAY: Duration = Duration::from_secs(2);
+const PROBE_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+static ADB_KEY: OnceLock<RsaKey> = OnceLock::new();
+/// Connected devices by `ip:port`, the in-process replacement for the adb server.
+/// Each has its own lock, so a device that is slow to connect doesn't hold up the others.
+static DEVICES: OnceLock<Mutex<HashMap<String, DeviceSlot>>> = OnceLock::new();
+
+type DeviceSlot = Arc<Mutex<Option<AdbDevice>>>;
+
+#[derive(Debug)]
+pub enum AdbError {
+    Io(std::io::Error),
+    Protocol(String),
+    Key(String),
+    /// The device refused to open the service.
+    Closed(String),
+    /// A shell command ran but reported a failure.
+    Command(String),
+}
+
+impl fmt::Display for AdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdbError::Io(err) => write!(f, "adb connection failed, {err}"),
+            AdbError::Protocol(msg) => write!(f, "adb protocol error, {msg}"),
+            AdbError::Key(msg) => write!(f, "adb key error, {msg}"),
+            AdbError::Closed(service) => write!(f, "adb service '{service}' was refused"),
+            AdbError::Command(msg) => write!(f, "adb command failed, {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AdbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AdbError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AdbError {
+    fn from(err: std::io::Error) -> Self {
+        AdbError::Io(err)
+    }
+}
+
+/// Connects (or reconnects) to the device, like `adb connect`.
+pub fn connect_tv_adb(serial: &str) -> Result<(), AdbError> {
+    let addr = device_addr(serial);
+    let slot = device_slot(&addr);
+    let mut device = lock_device(&slot);
+    *device = None;
+    *device = Some(AdbDevice::connect(&addr, adb_key()?)?);
+    Ok(())
+}
+
+pub fn capture_screen_adb(serial: &str, dir: &str) -> Result<PathBuf, AdbError> {
+    let time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
-    let mut file_name = dir.to_string();
-    file_name.push_str(r"\");
-    file_name.push_str(&time.to_string());
-    file_name.push_str(".png");
-    let output = Command::new("adb")
-        .arg("exec-out")
-        .arg("screencap")
-        .arg("-p")
-        .output()
-        .expect("Failed to execute command");
-
-    fs::write(file_name, output.stdout).expect("Unable to write file");
-}
-
-pub fn reconnect_offline() {
-    Command::new("adb")
-        .arg("reconnect")
-        .arg("offline")
-        .stdout(Stdio::piped())
-        .output()
-        .expect("Failed to reconnect offline");
+    let png = with_device(serial, |device| device.exec_out("screencap -p"))?;
+    let mut file_name = PathBuf::from(dir);
+    file_name.push(format!("{time}.png"));
+    fs::write(&file_name, png)?;
+    Ok(file_name)
+}
+
+pub fn send_keyevent(serial: &str, key: AndroidKey) -> Result<(), AdbError> {
+    input(serial, &format!("keyevent {}", key.code()))
+}
+
+/// Holds the key down, e.g. to bring up the power menu.
+pub fn long_press(serial: &str, key: AndroidKey) -> Result<(), AdbError> {
+    input(serial, &format!("keyevent --longpress {}", key.code()))
+}
+
+/// Types `text` into the focused field.
+pub fn input_text(serial: &str, text: &str) -> Result<(), AdbError> {
+    input(serial, &text_args(text))
+}
+
+/// The `input` arguments typing `text`, single-quoted for the device shell.
+fn text_args(text: &str) -> String {
+    // `input text` reads spaces as argument separators and `%s` as a space
+    let text = text.replace(' ', "%s").replace('\'', "'\\''");
+    format!("text '{text}'")
+}
+
+pub fn tap(serial: &str, x: u32, y: u32) -> Result<(), AdbError> {
+    input(serial, &format!("tap {x} {y}"))
+}
+
+pub fn swipe(
+    serial: &str,
+    from: (u32, u32),
+    to: (u32, u32),
+    duration: Duration,
+) -> Result<(), AdbError> {
+    let ((x1, y1), (x2, y2)) = (from, to);
+    let ms = duration.as_millis();
+    input(serial, &format!("swipe {x1} {y1} {x2} {y2} {ms}"))
+}
+
+pub fn power_state(serial: &str) -> Result<PowerState, AdbError> {
+    state::parse_power(&shell(serial, "dumpsys power")?).map_err(AdbError::Command)
+}
+
+/// Power, foreground app and HDMI input. Only the power state is required,
+/// the others are left out when the device doesn't report them.
+pub fn device_state(serial: &str) -> Result<DeviceState, AdbError> {
+    let power = power_state(serial)?;
+    let foreground = state::parse_foreground(&shell(serial, "dumpsys activity activities")?)
+        .or_else(|| state::parse_foreground(&shell(serial, "dumpsys window").ok()?));
+    let hdmi_input = shell(serial, "dumpsys hdmi_control")
+        .ok()
+        .and_then(|x| state::parse_hdmi_input(&x));
+    Ok(DeviceState {
+        power,
+        foreground,
+        hdmi_input,
+    })
+}
+
+/// Sends `keys` only if the device isn't already `on` or off, then waits for it to switch.
+/// Returns whether the keys were sent.
+pub fn ensure_power(serial: &str, on: bool, keys: &[AndroidKey]) -> Result<bool, AdbError> {
+    if power_state(serial)?.is_on() == on {
+        return Ok(false);
+    }
+    for key in keys {
+        send_keyevent(serial, *key)?;
+    }
+    let deadline = Instant::now() + POWER_TIMEOUT;
+    loop {
+        thread::sleep(POWER_POLL);
+        let state = power_state(serial)?;
+        if state.is_on() == on {
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            return Err(AdbError::Command(format!(
+                "Device is still {} after {}s",
+                state.wakefulness,
+                POWER_TIMEOUT.as_secs()
+            )));
+        }
+    }
+}
+
+/// Waits until the adb port accepts connections, e.g. while a TV boots, and
+/// returns how long that took. `between` runs after every failed attempt.
+pub fn wait_for_device(
+    serial: &str,
+    timeout: Duration,
+    mut between: impl FnMut(),
+) -> io::Result<Duration> {
+    let started = Instant::now();
+    let addr = device_addr(serial)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("No address for {serial}"))
+        })?;
+    let mut delay = PROBE_MIN_DELAY;
+    loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+        let attempt = PROBE_CONNECT_TIMEOUT
+            .min(remaining)
+            .max(Duration::from_millis(1));
+        let err = match TcpStream::connect_timeout(&addr, attempt) {
+            Ok(_) => return Ok(started.elapsed()),
+            Err(err) => err,
+        };
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(err);
+        }
+        between();
+        thread::sleep(delay.min(remaining));
+        delay = (delay * 2).min(PROBE_MAX_DELAY);
+    }
+}
+
+/// Runs `input <args>`, which reports bad arguments on its output rather than failing.
+fn input(serial: &str, args: &str) -> Result<(), AdbError> {
+    let output = shell(serial, &format!("input {args}"))?;
+    match output
+        .lines()
+        .find(|x| x.contains("Error") || x.contains("Exception"))
+    {
+        Some(line) => Err(AdbError::Command(format!("input {args}: {}", line.trim()))),
+        None => Ok(()),
+    }
+}
+
+pub fn shell(serial: &str, command: &str) -> Result<String, AdbError> {
+    with_device(serial, |device| device.shell(command))
+}
+
+/// Runs `f` on the cached connection to `serial`, connecting first if needed.
+/// A connection that fails is dropped and retried once, e.g. after the TV went to sleep,
+/// but only if the device never got the command, so it doesn't run twice.
+fn with_device<T, F>(serial: &str, f: F) -> Result<T, AdbError>
+where
+    F: Fn(&mut AdbDevice) -> Result<T, AdbError>,
+{
+    let addr = device_addr(serial);
+    let slot = device_slot(&addr);
+    let mut cached = lock_device(&slot);
+    if let Some(device) = cached.as_mut().filter(|x| x.is_alive()) {
+        let opened = device.opened();
+        match f(device) {
+            Err(err @ (AdbError::Io(_) | AdbError::Protocol(_))) => {
+                let sent = device.opened() != opened;
+                *cached = None;
+                if sent {
+                    return Err(err);
+                }
+            }
+            res => return res,
+        }
+    }
+    *cached = None;
+    let mut device = AdbDevice::connect(&addr, adb_key()?)?;
+    let res = f(&mut device);
+    *cached = Some(device);
+    res
+}
+
+/// The connection slot for `addr`, only the map is locked while looking it up.
+fn device_slot(addr: &str) -> DeviceSlot {
+    devices()
+        .lock()
+        .unwrap()
+        .entry(addr.to_string())
+        .or_default()
+        .clone()
+}
+
+/// A connection that was in use when a thread panicked is in an unknown state, so it is dropped.
+fn lock_device(slot: &Mutex<Option<AdbDevice>>) -> MutexGuard<'_, Option<AdbDevice>> {
+    slot.lock().unwrap_or_else(|poisoned| {
+        slot.clear_poison();
+        let mut device = poisoned.into_inner();
+        *device = None;
+        device
+    })
+}
+
+/// The serial of a network device is `ip:port`, a bare ip means the default port.
+fn device_addr(serial: &str) -> String {
+    if serial.contains(':') {
+        serial.to_string()
+    } else {
+        format!("{serial}:{DEFAULT_ADB_PORT}")
+    }
+}
+
+fn devices() -> &'static Mutex<HashMap<String, DeviceSlot>> {
+    DEVICES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Uses the same key as the platform tools so an already authorized PC stays authorized.
+fn adb_key() -> Result<&'static RsaKey, AdbError> {
+    if let Some(key) = ADB_KEY.get() {
+        return Ok(key);
+    }
+    let key = RsaKey::load_or_create(&adb_key_path()?)?;
+    Ok(ADB_KEY.get_or_init(|| key))
+}
+
+fn adb_key_path() -> Result<PathBuf, AdbError> {
+    let home = std::env::var_os("ANDROID_USER_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("USERPROFILE")
+                .or_else(|| std::env::var_os("HOME"))
+                .map(|home| PathBuf::from(home).join(".android"))
+        })
+        .ok_or_else(|| AdbError::Key("Fail to locate the user profile folder".to_string()))?;
+    Ok(home.join("adbkey"))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::net::TcpListener;
+
+    pub use super::device::tests::{accept_signed, serve};
+
+    use super::device::tests::{key, read};
+    use super::message::A_OPEN;
+    use super::*;
+
+    /// A loopback port to play a device on, with the test key installed.
+    pub fn listen() -> (TcpListener, String) {
+        ADB_KEY.get_or_init(key);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        (listener, addr)
+    }
+
+    #[test]
+    fn reconnects_when_the_cached_connection_was_closed() {
+        let (listener, addr) = listen();
+        let daemon = thread::spawn(move || {
+            for n in 1..=2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                accept_signed(&mut stream);
+                let output = format!("{n}\n");
+                serve(
+                    &mut stream,
+                    &format!("shell:echo {n}"),
+                    &[output.as_bytes()],
+                );
+            }
+        });
+        assert_eq!(shell(&addr, "echo 1").unwrap(), "1\n");
+        // the daemon closes the first connection like a TV going to sleep
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(shell(&addr, "echo 2").unwrap(), "2\n");
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn does_not_rerun_a_command_the_device_received() {
+        let (listener, addr) = listen();
+        let daemon = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_signed(&mut stream);
+            serve(&mut stream, "shell:true", &[]);
+            let open = read(&mut stream);
+            assert_eq!(open.command, A_OPEN);
+            drop(stream);
+            // a retry would show up as a second connection
+            listener.set_nonblocking(true).unwrap();
+            thread::sleep(Duration::from_millis(500));
+            assert!(listener.accept().is_err());
+        });
+        shell(&addr, "true").unwrap();
+        let res = send_keyevent(&addr, AndroidKey::Power);
+        assert!(matches!(res, Err(AdbError::Io(_))), "{res:?}");
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn a_stuck_device_does_not_block_the_others() {
+        // accepts the connection but never answers, like a TV showing the key prompt
+        let (stuck, stuck_addr) = listen();
+        let waiting = thread::spawn(move || shell(&stuck_addr, "true"));
+        let (stream, _) = stuck.accept().unwrap();
+
+        let (listener, addr) = listen();
+        let daemon = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_signed(&mut stream);
+            serve(&mut stream, "shell:echo ok", &[b"ok\n"]);
+        });
+        let started = Instant::now();
+        assert_eq!(shell(&addr, "echo ok").unwrap(), "ok\n");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        daemon.join().unwrap();
+
+        drop(stream);
+        assert!(waiting.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn quotes_typed_text() {
+        assert_eq!(text_args("hello"), "text 'hello'");
+        assert_eq!(text_args("a b  c"), "text 'a%sb%s%sc'");
+        assert_eq!(text_args("it's \"on\""), r#"text 'it'\''s%s"on"'"#);
+        // no expansion inside single quotes
+        assert_eq!(
+            text_args("$HOME; reboot `id`"),
+            "text '$HOME;%sreboot%s`id`'"
+        );
+        // `%s` stands for a space, a lone `%` is typed as is
+        assert_eq!(text_args("50% off"), "text '50%%soff'");
+    }
+
+    #[test]
+    fn types_text_on_the_device() {
+        let (listener, addr) = listen();
+        let daemon = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_signed(&mut stream);
+            serve(&mut stream, "shell:input text 'don'\\''t%sstop'", &[]);
+        });
+        input_text(&addr, "don't stop").unwrap();
+        daemon.join().unwrap();
+    }
+
+    /// A loopback port nothing listens on, at least right now.
+    fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn gives_up_waiting_at_the_deadline() {
+        let addr = closed_port();
+        let mut attempts = 0;
+        let started = Instant::now();
+        let res = wait_for_device(&addr, Duration::from_millis(700), || attempts += 1);
+        let elapsed = started.elapsed();
+        assert!(res.is_err(), "{res:?}");
+        assert!(elapsed >= Duration::from_millis(700), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1500), "{elapsed:?}");
+        assert!(attempts > 1);
+    }
+
+    #[test]
+    fn waits_until_the_device_listens() {
+        let addr = closed_port();
+        let booting = addr.clone();
+        let device = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            let listener = TcpListener::bind(&booting).unwrap();
+            listener.accept().unwrap();
+        });
+        let mut resent = 0;
+        let waited = wait_for_device(&addr, Duration::from_secs(10), || resent += 1).unwrap();
+        device.join().unwrap();
+        assert!(waited >= Duration::from_millis(500), "{waited:?}");
+        assert!(waited < Duration::from_secs(5), "{waited:?}");
+        assert!(resent > 0);
+    }
 }
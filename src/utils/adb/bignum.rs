@@ -0,0 +1,400 @@
+use std::cmp::Ordering;
+
+/// Unsigned big integer with little-endian 32-bit limbs, just enough for RSA.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self { limbs: vec![] }
+    }
+
+    pub fn from_u32(value: u32) -> Self {
+        let mut res = Self { limbs: vec![value] };
+        res.normalize();
+        res
+    }
+
+    pub fn from_bytes_be(bytes: &[u8]) -> Self {
+        let mut limbs = Vec::with_capacity(bytes.len().div_ceil(4));
+        for chunk in bytes.rchunks(4) {
+            let mut limb = 0u32;
+            for &b in chunk {
+                limb = (limb << 8) | b as u32;
+            }
+            limbs.push(limb);
+        }
+        let mut res = Self { limbs };
+        res.normalize();
+        res
+    }
+
+    /// Big-endian bytes, left padded with zeros to `len` (or minimal if `len` is too small).
+    pub fn to_bytes_be(&self, len: usize) -> Vec<u8> {
+        let mut res = self.to_bytes_le(len);
+        res.reverse();
+        res
+    }
+
+    /// Little-endian bytes, right padded with zeros to `len` (or minimal if `len` is too small).
+    pub fn to_bytes_le(&self, len: usize) -> Vec<u8> {
+        let mut res = Vec::with_capacity(len.max(self.limbs.len() * 4));
+        for limb in &self.limbs {
+            res.extend_from_slice(&limb.to_le_bytes());
+        }
+        while res.len() > len && res.last() == Some(&0) {
+            res.pop();
+        }
+        res.resize(len.max(res.len()), 0);
+        res
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.limbs.first().is_some_and(|x| x & 1 == 1)
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn bit(&self, index: usize) -> bool {
+        self.limbs
+            .get(index / 32)
+            .is_some_and(|limb| (limb >> (index % 32)) & 1 == 1)
+    }
+
+    pub fn set_bit(&mut self, index: usize) {
+        if self.limbs.len() <= index / 32 {
+            self.limbs.resize(index / 32 + 1, 0);
+        }
+        self.limbs[index / 32] |= 1 << (index % 32);
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len {
+            let sum = self.limb(i) as u64 + other.limb(i) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        let mut res = Self { limbs };
+        res.normalize();
+        res
+    }
+
+    /// `self - other`, the caller guarantees `self >= other`.
+    pub fn sub(&self, other: &BigUint) -> BigUint {
+        debug_assert!(*self >= *other);
+        let mut limbs = self.limbs.clone();
+        sub_in_place(&mut limbs, &other.limbs);
+        let mut res = Self { limbs };
+        res.normalize();
+        res
+    }
+
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        let mut res = Self { limbs };
+        res.normalize();
+        res
+    }
+
+    pub fn shl(&self, bits: usize) -> BigUint {
+        let words = bits / 32;
+        let shift = bits % 32;
+        let mut limbs = vec![0u32; words];
+        let mut carry = 0u32;
+        for &limb in &self.limbs {
+            if shift == 0 {
+                limbs.push(limb);
+            } else {
+                limbs.push((limb << shift) | carry);
+                carry = limb >> (32 - shift);
+            }
+        }
+        limbs.push(carry);
+        let mut res = Self { limbs };
+        res.normalize();
+        res
+    }
+
+    pub fn div_rem_u32(&self, divisor: u32) -> (BigUint, u32) {
+        let mut limbs = vec![0u32; self.limbs.len()];
+        let mut rem = 0u64;
+        for i in (0..self.limbs.len()).rev() {
+            let cur = (rem << 32) | self.limbs[i] as u64;
+            limbs[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        let mut res = Self { limbs };
+        res.normalize();
+        (res, rem as u32)
+    }
+
+    /// Schoolbook binary long division, slow but only used while creating keys.
+    pub fn div_rem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+        assert!(!divisor.is_zero(), "division by zero");
+        let mut quot = BigUint::zero();
+        let mut rem = BigUint::zero();
+        for i in (0..self.bits()).rev() {
+            rem = rem.shl(1);
+            if self.bit(i) {
+                rem.set_bit(0);
+            }
+            if rem >= *divisor {
+                rem = rem.sub(divisor);
+                quot.set_bit(i);
+            }
+        }
+        quot.normalize();
+        (quot, rem)
+    }
+
+    fn limb(&self, index: usize) -> u32 {
+        self.limbs.get(index).copied().unwrap_or(0)
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_limbs(&self.limbs, &other.limbs)
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Modular arithmetic in Montgomery form for an odd modulus.
+pub struct Montgomery {
+    modulus: Vec<u32>,
+    /// `-modulus^-1 mod 2^32`
+    m_inv: u32,
+    /// `R^2 mod modulus` where `R = 2^(32 * len)`
+    rr: BigUint,
+}
+
+impl Montgomery {
+    pub fn new(modulus: &BigUint) -> Self {
+        assert!(modulus.is_odd(), "montgomery modulus must be odd");
+        let len = modulus.limbs.len();
+        let rr = BigUint::from_u32(1).shl(64 * len).div_rem(modulus).1;
+        Self {
+            modulus: modulus.limbs.clone(),
+            m_inv: inv_mod_word(modulus.limbs[0]).wrapping_neg(),
+            rr,
+        }
+    }
+
+    pub fn m_inv(&self) -> u32 {
+        self.m_inv
+    }
+
+    pub fn rr(&self) -> &BigUint {
+        &self.rr
+    }
+
+    /// `base^exp mod modulus`
+    pub fn pow(&self, base: &BigUint, exp: &BigUint) -> BigUint {
+        let len = self.modulus.len();
+        let base = if cmp_limbs(&base.limbs, &self.modulus) == Ordering::Less {
+            base.clone()
+        } else {
+            base.div_rem(&BigUint {
+                limbs: self.modulus.clone(),
+            })
+            .1
+        };
+        let base = self.mul(&padded(&base.limbs, len), &padded(&self.rr.limbs, len));
+        let mut acc = self.mul(&padded(&[1], len), &padded(&self.rr.limbs, len));
+        for i in (0..exp.bits()).rev() {
+            acc = self.mul(&acc, &acc);
+            if exp.bit(i) {
+                acc = self.mul(&acc, &base);
+            }
+        }
+        let mut res = BigUint {
+            limbs: self.mul(&acc, &padded(&[1], len)),
+        };
+        res.normalize();
+        res
+    }
+
+    /// Montgomery product `a * b * R^-1 mod modulus` (CIOS).
+    fn mul(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let n = self.modulus.len();
+        let mut t = vec![0u32; n + 2];
+        for &bi in b.iter().take(n) {
+            let mut carry = 0u64;
+            for j in 0..n {
+                let cur = t[j] as u64 + a[j] as u64 * bi as u64 + carry;
+                t[j] = cur as u32;
+                carry = cur >> 32;
+            }
+            let cur = t[n] as u64 + carry;
+            t[n] = cur as u32;
+            t[n + 1] = (cur >> 32) as u32;
+
+            let m = t[0].wrapping_mul(self.m_inv);
+            let mut carry = (t[0] as u64 + m as u64 * self.modulus[0] as u64) >> 32;
+            for j in 1..n {
+                let cur = t[j] as u64 + m as u64 * self.modulus[j] as u64 + carry;
+                t[j - 1] = cur as u32;
+                carry = cur >> 32;
+            }
+            let cur = t[n] as u64 + carry;
+            t[n - 1] = cur as u32;
+            t[n] = t[n + 1] + (cur >> 32) as u32;
+        }
+        if t[n] != 0 || cmp_limbs(&t[..n], &self.modulus) != Ordering::Less {
+            sub_in_place(&mut t, &self.modulus);
+        }
+        t.truncate(n);
+        t
+    }
+}
+
+fn padded(limbs: &[u32], len: usize) -> Vec<u32> {
+    let mut res = limbs.to_vec();
+    res.resize(len, 0);
+    res
+}
+
+fn cmp_limbs(a: &[u32], b: &[u32]) -> Ordering {
+    let len = a.len().max(b.len());
+    for i in (0..len).rev() {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        match x.cmp(&y) {
+            Ordering::Equal => continue,
+            ord => return ord,
+        }
+    }
+    Ordering::Equal
+}
+
+fn sub_in_place(a: &mut [u32], b: &[u32]) {
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let cur = *limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        if cur < 0 {
+            *limb = (cur + (1i64 << 32)) as u32;
+            borrow = 1;
+        } else {
+            *limb = cur as u32;
+            borrow = 0;
+        }
+    }
+}
+
+/// Inverse of an odd word modulo 2^32 by Newton iteration.
+fn inv_mod_word(x: u32) -> u32 {
+    let mut inv = x;
+    for _ in 0..4 {
+        inv = inv.wrapping_mul(2u32.wrapping_sub(x.wrapping_mul(inv)));
+    }
+    inv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> BigUint {
+        let text = if text.len() % 2 == 1 {
+            format!("0{text}")
+        } else {
+            text.to_string()
+        };
+        let bytes = (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect::<Vec<u8>>();
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    const A: &str = "fedcba9876543210fedcba9876543210ffffffff";
+    const B: &str = "1234567890abcdef1234567";
+
+    #[test]
+    fn bytes_round_trip() {
+        let a = hex(A);
+        assert_eq!(BigUint::from_bytes_be(&a.to_bytes_be(32)), a);
+        assert_eq!(a.to_bytes_be(32).len(), 32);
+        assert_eq!(a.to_bytes_be(1).len(), 20);
+        assert_eq!(a.to_bytes_le(20)[0], 0xff);
+        assert_eq!(BigUint::from_bytes_be(&[0, 0, 1]), BigUint::from_u32(1));
+        assert!(BigUint::from_bytes_be(&[0, 0]).is_zero());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (a, b) = (hex(A), hex(B));
+        assert_eq!(a.add(&b), hex("fedcba9876543210ffffffffff5eeeeff1234566"));
+        assert_eq!(a.sub(&b), hex("fedcba9876543210fdb97530ed4975320edcba98"));
+        assert_eq!(
+            a.mul(&b),
+            hex("121fa00acd77d74247acc90b69643345a0a8c6791a63d379da6fcf80edcba99")
+        );
+        assert_eq!(
+            a.div_rem(&b),
+            (hex("e00000007bde000d82"), hex("907108223a07ef6f9086b1"))
+        );
+        assert_eq!(
+            a.div_rem_u32(65537),
+            (hex("fedbbbbcba97777987633335431eeef2110d"), 61170)
+        );
+        assert_eq!(b.div_rem(&a), (BigUint::zero(), b.clone()));
+        assert_eq!(BigUint::from_u32(1).shl(64), hex("10000000000000000"));
+        assert_eq!(a.bits(), 160);
+        assert!(a.bit(0) && !a.bit(32));
+    }
+
+    #[test]
+    fn montgomery_pow() {
+        let m = hex("c7f1bd2f8b4f9e3a0f2d93b1c45e7a19f3d2b5a7c9e1f0a3b5d7e9f1a3c5e7f9");
+        let mont = Montgomery::new(&m);
+        assert_eq!(
+            mont.pow(&hex(A), &hex(B)),
+            hex("5f0150e019a1d063ee0e5e423c13d20ecbf1d21ab4655bd264f7860013d65485")
+        );
+        assert_eq!(
+            mont.pow(&BigUint::from_u32(7), &BigUint::zero()),
+            BigUint::from_u32(1)
+        );
+        // bases at or above the modulus are reduced first
+        assert_eq!(
+            mont.pow(&m.add(&BigUint::from_u32(2)), &BigUint::from_u32(3)),
+            BigUint::from_u32(8)
+        );
+    }
+}
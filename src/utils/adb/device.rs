@@ -0,0 +1,313 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::AdbError;
+use super::message::{
+    A_AUTH, A_CLSE, A_CNXN, A_OKAY, A_OPEN, A_VERSION, A_WRTE, AUTH_RSAPUBLICKEY, AUTH_SIGNATURE,
+    AUTH_TOKEN, MAX_PAYLOAD, Message,
+};
+use super::rsa::RsaKey;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the user has to accept the "Allow USB debugging?" prompt on the device.
+const ACCEPT_KEY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An authenticated adb transport to one device.
+pub struct AdbDevice {
+    stream: TcpStream,
+    max_payload: u32,
+    next_local_id: u32,
+    /// Number of services opened so far, see `opened`.
+    opened: u64,
+}
+
+impl AdbDevice {
+    pub fn connect(addr: &str, key: &RsaKey) -> Result<Self, AdbError> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| AdbError::Protocol(format!("Fail to resolve {addr}")))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        Self::handshake(stream, key)
+    }
+
+    /// Runs the CNXN/AUTH exchange on an already connected stream.
+    fn handshake(mut stream: TcpStream, key: &RsaKey) -> Result<Self, AdbError> {
+        Message::with_str(A_CNXN, A_VERSION, MAX_PAYLOAD, "host::").write_to(&mut stream)?;
+        let mut signed = false;
+        loop {
+            let msg = Message::read_from(&mut stream, MAX_PAYLOAD)?;
+            match (msg.command, msg.arg0) {
+                (A_CNXN, _) => {
+                    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                    return Ok(Self {
+                        stream,
+                        max_payload: msg.arg1.clamp(1, MAX_PAYLOAD),
+                        next_local_id: 1,
+                        opened: 0,
+                    });
+                }
+                (A_AUTH, AUTH_TOKEN) if !signed => {
+                    signed = true;
+                    let signature = key.sign(&msg.payload)?;
+                    Message::new(A_AUTH, AUTH_SIGNATURE, 0, signature).write_to(&mut stream)?;
+                }
+                (A_AUTH, AUTH_TOKEN) => {
+                    // The device doesn't know our key yet, offer it and wait for the user
+                    let public_key = key.android_public_key();
+                    Message::with_str(A_AUTH, AUTH_RSAPUBLICKEY, 0, &public_key)
+                        .write_to(&mut stream)?;
+                    stream.set_read_timeout(Some(ACCEPT_KEY_TIMEOUT))?;
+                }
+                (command, _) => {
+                    return Err(AdbError::Protocol(format!(
+                        "Unexpected command {command:#010x} during handshake"
+                    )));
+                }
+            }
+        }
+    }
+
+    /// How many OPEN messages were sent, a failed call that didn't change it
+    /// never reached the device and is safe to retry.
+    pub fn opened(&self) -> u64 {
+        self.opened
+    }
+
+    /// Whether the device hasn't closed or reset the connection, e.g. while the TV slept.
+    pub fn is_alive(&self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let alive = match self.stream.peek(&mut [0u8]) {
+            Ok(n) => n > 0,
+            Err(err) => err.kind() == std::io::ErrorKind::WouldBlock,
+        };
+        self.stream.set_nonblocking(false).is_ok() && alive
+    }
+
+    /// Equivalent of `adb shell <command>`, returns stdout and stderr.
+    pub fn shell(&mut self, command: &str) -> Result<String, AdbError> {
+        let output = self.run_service(&format!("shell:{command}"))?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Equivalent of `adb exec-out <command>`, returns raw stdout.
+    pub fn exec_out(&mut self, command: &str) -> Result<Vec<u8>, AdbError> {
+        self.run_service(&format!("exec:{command}"))
+    }
+
+    /// Opens a stream to `service` and collects everything written until the device closes it.
+    fn run_service(&mut self, service: &str) -> Result<Vec<u8>, AdbError> {
+        let local_id = self.next_local_id;
+        self.next_local_id = self.next_local_id.wrapping_add(1).max(1);
+        Message::with_str(A_OPEN, local_id, 0, service).write_to(&mut self.stream)?;
+        self.opened += 1;
+
+        let mut remote_id = None;
+        let mut output = vec![];
+        loop {
+            let msg = Message::read_from(&mut self.stream, self.max_payload)?;
+            if msg.arg1 != local_id {
+                // leftovers from an earlier stream
+                continue;
+            }
+            match msg.command {
+                A_OKAY => remote_id = Some(msg.arg0),
+                A_WRTE => {
+                    output.extend_from_slice(&msg.payload);
+                    Message::new(A_OKAY, local_id, msg.arg0, vec![]).write_to(&mut self.stream)?;
+                }
+                A_CLSE => {
+                    return match remote_id {
+                        Some(remote_id) => {
+                            Message::new(A_CLSE, local_id, remote_id, vec![])
+                                .write_to(&mut self.stream)?;
+                            Ok(output)
+                        }
+                        None => Err(AdbError::Closed(service.to_string())),
+                    };
+                }
+                command => {
+                    return Err(AdbError::Protocol(format!(
+                        "Unexpected command {command:#010x} on stream {local_id}"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    const TOKEN: &[u8; 20] = b"0123456789abcdefghij";
+    const BANNER: &str = "device::ro.product.name=test;";
+
+    pub fn key() -> RsaKey {
+        RsaKey::from_pem(include_str!("testdata/adbkey")).unwrap()
+    }
+
+    /// Accepts one connection on a loopback port and plays the device side with `script`.
+    fn fake_adbd<F>(script: F) -> (String, JoinHandle<()>)
+    where
+        F: FnOnce(&mut TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            script(&mut stream);
+        });
+        (addr, handle)
+    }
+
+    pub fn read(stream: &mut TcpStream) -> Message {
+        Message::read_from(stream, MAX_PAYLOAD).unwrap()
+    }
+
+    fn send(stream: &mut TcpStream, msg: Message) {
+        msg.write_to(stream).unwrap();
+    }
+
+    /// Expects the host's CNXN, asks for a signature and accepts it.
+    pub fn accept_signed(stream: &mut TcpStream) {
+        let msg = read(stream);
+        assert_eq!((msg.command, msg.arg0), (A_CNXN, A_VERSION));
+        assert_eq!(msg.payload, b"host::\0");
+        send(stream, Message::new(A_AUTH, AUTH_TOKEN, 0, TOKEN.to_vec()));
+        let msg = read(stream);
+        assert_eq!((msg.command, msg.arg0), (A_AUTH, AUTH_SIGNATURE));
+        assert_eq!(msg.payload, key().sign(TOKEN).unwrap());
+        send(stream, Message::with_str(A_CNXN, A_VERSION, 4096, BANNER));
+    }
+
+    /// Answers one OPEN for `service` with `chunks` of output, then closes the stream.
+    pub fn serve(stream: &mut TcpStream, service: &str, chunks: &[&[u8]]) {
+        const REMOTE_ID: u32 = 42;
+        let open = read(stream);
+        assert_eq!(open.command, A_OPEN);
+        assert_eq!(open.payload, format!("{service}\0").as_bytes());
+        let local_id = open.arg0;
+        send(stream, Message::new(A_OKAY, REMOTE_ID, local_id, vec![]));
+        for chunk in chunks {
+            send(
+                stream,
+                Message::new(A_WRTE, REMOTE_ID, local_id, chunk.to_vec()),
+            );
+            let ack = read(stream);
+            assert_eq!(
+                (ack.command, ack.arg0, ack.arg1),
+                (A_OKAY, local_id, REMOTE_ID)
+            );
+        }
+        send(stream, Message::new(A_CLSE, REMOTE_ID, local_id, vec![]));
+        let close = read(stream);
+        assert_eq!(
+            (close.command, close.arg0, close.arg1),
+            (A_CLSE, local_id, REMOTE_ID)
+        );
+    }
+
+    #[test]
+    fn handshake_with_known_key() {
+        let (addr, daemon) = fake_adbd(accept_signed);
+        let device = AdbDevice::connect(&addr, &key()).unwrap();
+        assert_eq!(device.max_payload, 4096);
+        assert_eq!(device.opened(), 0);
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_offers_public_key_when_signature_is_refused() {
+        let (addr, daemon) = fake_adbd(|stream| {
+            read(stream);
+            send(stream, Message::new(A_AUTH, AUTH_TOKEN, 0, TOKEN.to_vec()));
+            assert_eq!(read(stream).arg0, AUTH_SIGNATURE);
+            // a new token means the signature didn't match a known key
+            send(stream, Message::new(A_AUTH, AUTH_TOKEN, 0, TOKEN.to_vec()));
+            let msg = read(stream);
+            assert_eq!((msg.command, msg.arg0), (A_AUTH, AUTH_RSAPUBLICKEY));
+            let public_key = String::from_utf8(msg.payload).unwrap();
+            assert!(public_key.starts_with(key().android_public_key().split(' ').next().unwrap()));
+            assert!(public_key.ends_with('\0'));
+            send(
+                stream,
+                Message::with_str(A_CNXN, A_VERSION, MAX_PAYLOAD, BANNER),
+            );
+        });
+        AdbDevice::connect(&addr, &key()).unwrap();
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_rejects_oversized_token() {
+        let (addr, daemon) = fake_adbd(|stream| {
+            read(stream);
+            send(stream, Message::new(A_AUTH, AUTH_TOKEN, 0, vec![1; 1024]));
+        });
+        let res = AdbDevice::connect(&addr, &key()).err();
+        assert!(matches!(res, Some(AdbError::Protocol(_))), "{res:?}");
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_rejects_unexpected_command() {
+        let (addr, daemon) = fake_adbd(|stream| {
+            read(stream);
+            send(stream, Message::new(A_OKAY, 1, 1, vec![]));
+        });
+        let res = AdbDevice::connect(&addr, &key()).err();
+        assert!(matches!(res, Some(AdbError::Protocol(_))), "{res:?}");
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn run_service_collects_output() {
+        let (addr, daemon) = fake_adbd(|stream| {
+            accept_signed(stream);
+            serve(stream, "shell:echo hello", &[b"hel", b"lo\n"]);
+            serve(stream, "exec:screencap -p", &[&[0x89, b'P', b'N', b'G']]);
+        });
+        let mut device = AdbDevice::connect(&addr, &key()).unwrap();
+        assert_eq!(device.shell("echo hello").unwrap(), "hello\n");
+        assert_eq!(
+            device.exec_out("screencap -p").unwrap(),
+            [0x89, b'P', b'N', b'G']
+        );
+        assert_eq!(device.opened(), 2);
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn run_service_reports_refused_service() {
+        let (addr, daemon) = fake_adbd(|stream| {
+            accept_signed(stream);
+            let open = read(stream);
+            send(stream, Message::new(A_CLSE, 0, open.arg0, vec![]));
+        });
+        let mut device = AdbDevice::connect(&addr, &key()).unwrap();
+        let res = device.shell("missing");
+        assert!(matches!(res, Err(AdbError::Closed(_))), "{res:?}");
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn closed_connection_is_not_alive() {
+        let (addr, daemon) = fake_adbd(accept_signed);
+        let mut device = AdbDevice::connect(&addr, &key()).unwrap();
+        daemon.join().unwrap();
+        // the daemon dropped its end, give the FIN a moment to arrive
+        thread::sleep(Duration::from_millis(50));
+        assert!(!device.is_alive());
+        assert!(device.shell("true").is_err());
+    }
+}
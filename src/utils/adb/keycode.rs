@@ -0,0 +1,421 @@
+//! Android `KeyEvent` keycodes, as sent by `input keyevent`.
+
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! android_keys {
+    ($($variant:ident = $code:literal, $name:literal;)*) => {
+        /// A key of the Android `KeyEvent` table, up to API level 34.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum AndroidKey {
+            $($variant,)*
+        }
+
+        impl AndroidKey {
+            pub const ALL: &[AndroidKey] = &[$(AndroidKey::$variant,)*];
+
+            pub fn code(self) -> u16 {
+                match self {
+                    $(AndroidKey::$variant => $code,)*
+                }
+            }
+
+            /// The name without the `KEYCODE_` prefix.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(AndroidKey::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+android_keys! {
+    Unknown = 0, "UNKNOWN";
+    SoftLeft = 1, "SOFT_LEFT";
+    SoftRight = 2, "SOFT_RIGHT";
+    Home = 3, "HOME";
+    Back = 4, "BACK";
+    Call = 5, "CALL";
+    Endcall = 6, "ENDCALL";
+    Digit0 = 7, "0";
+    Digit1 = 8, "1";
+    Digit2 = 9, "2";
+    Digit3 = 10, "3";
+    Digit4 = 11, "4";
+    Digit5 = 12, "5";
+    Digit6 = 13, "6";
+    Digit7 = 14, "7";
+    Digit8 = 15, "8";
+    Digit9 = 16, "9";
+    Star = 17, "STAR";
+    Pound = 18, "POUND";
+    DpadUp = 19, "DPAD_UP";
+    DpadDown = 20, "DPAD_DOWN";
+    DpadLeft = 21, "DPAD_LEFT";
+    DpadRight = 22, "DPAD_RIGHT";
+    DpadCenter = 23, "DPAD_CENTER";
+    VolumeUp = 24, "VOLUME_UP";
+    VolumeDown = 25, "VOLUME_DOWN";
+    Power = 26, "POWER";
+    Camera = 27, "CAMERA";
+    Clear = 28, "CLEAR";
+    A = 29, "A";
+    B = 30, "B";
+    C = 31, "C";
+    D = 32, "D";
+    E = 33, "E";
+    F = 34, "F";
+    G = 35, "G";
+    H = 36, "H";
+    I = 37, "I";
+    J = 38, "J";
+    K = 39, "K";
+    L = 40, "L";
+    M = 41, "M";
+    N = 42, "N";
+    O = 43, "O";
+    P = 44, "P";
+    Q = 45, "Q";
+    R = 46, "R";
+    S = 47, "S";
+    T = 48, "T";
+    U = 49, "U";
+    V = 50, "V";
+    W = 51, "W";
+    X = 52, "X";
+    Y = 53, "Y";
+    Z = 54, "Z";
+    Comma = 55, "COMMA";
+    Period = 56, "PERIOD";
+    AltLeft = 57, "ALT_LEFT";
+    AltRight = 58, "ALT_RIGHT";
+    ShiftLeft = 59, "SHIFT_LEFT";
+    ShiftRight = 60, "SHIFT_RIGHT";
+    Tab = 61, "TAB";
+    Space = 62, "SPACE";
+    Sym = 63, "SYM";
+    Explorer = 64, "EXPLORER";
+    Envelope = 65, "ENVELOPE";
+    Enter = 66, "ENTER";
+    Del = 67, "DEL";
+    Grave = 68, "GRAVE";
+    Minus = 69, "MINUS";
+    Equals = 70, "EQUALS";
+    LeftBracket = 71, "LEFT_BRACKET";
+    RightBracket = 72, "RIGHT_BRACKET";
+    Backslash = 73, "BACKSLASH";
+    Semicolon = 74, "SEMICOLON";
+    Apostrophe = 75, "APOSTROPHE";
+    Slash = 76, "SLASH";
+    At = 77, "AT";
+    Num = 78, "NUM";
+    Headsethook = 79, "HEADSETHOOK";
+    Focus = 80, "FOCUS";
+    Plus = 81, "PLUS";
+    Menu = 82, "MENU";
+    Notification = 83, "NOTIFICATION";
+    Search = 84, "SEARCH";
+    MediaPlayPause = 85, "MEDIA_PLAY_PAUSE";
+    MediaStop = 86, "MEDIA_STOP";
+    MediaNext = 87, "MEDIA_NEXT";
+    MediaPrevious = 88, "MEDIA_PREVIOUS";
+    MediaRewind = 89, "MEDIA_REWIND";
+    MediaFastForward = 90, "MEDIA_FAST_FORWARD";
+    Mute = 91, "MUTE";
+    PageUp = 92, "PAGE_UP";
+    PageDown = 93, "PAGE_DOWN";
+    Pictsymbols = 94, "PICTSYMBOLS";
+    SwitchCharset = 95, "SWITCH_CHARSET";
+    ButtonA = 96, "BUTTON_A";
+    ButtonB = 97, "BUTTON_B";
+    ButtonC = 98, "BUTTON_C";
+    ButtonX = 99, "BUTTON_X";
+    ButtonY = 100, "BUTTON_Y";
+    ButtonZ = 101, "BUTTON_Z";
+    ButtonL1 = 102, "BUTTON_L1";
+    ButtonR1 = 103, "BUTTON_R1";
+    ButtonL2 = 104, "BUTTON_L2";
+    ButtonR2 = 105, "BUTTON_R2";
+    ButtonThumbl = 106, "BUTTON_THUMBL";
+    ButtonThumbr = 107, "BUTTON_THUMBR";
+    ButtonStart = 108, "BUTTON_START";
+    ButtonSelect = 109, "BUTTON_SELECT";
+    ButtonMode = 110, "BUTTON_MODE";
+    Escape = 111, "ESCAPE";
+    ForwardDel = 112, "FORWARD_DEL";
+    CtrlLeft = 113, "CTRL_LEFT";
+    CtrlRight = 114, "CTRL_RIGHT";
+    CapsLock = 115, "CAPS_LOCK";
+    ScrollLock = 116, "SCROLL_LOCK";
+    MetaLeft = 117, "META_LEFT";
+    MetaRight = 118, "META_RIGHT";
+    Function = 119, "FUNCTION";
+    Sysrq = 120, "SYSRQ";
+    Break = 121, "BREAK";
+    MoveHome = 122, "MOVE_HOME";
+    MoveEnd = 123, "MOVE_END";
+    Insert = 124, "INSERT";
+    Forward = 125, "FORWARD";
+    MediaPlay = 126, "MEDIA_PLAY";
+    MediaPause = 127, "MEDIA_PAUSE";
+    MediaClose = 128, "MEDIA_CLOSE";
+    MediaEject = 129, "MEDIA_EJECT";
+    MediaRecord = 130, "MEDIA_RECORD";
+    F1 = 131, "F1";
+    F2 = 132, "F2";
+    F3 = 133, "F3";
+    F4 = 134, "F4";
+    F5 = 135, "F5";
+    F6 = 136, "F6";
+    F7 = 137, "F7";
+    F8 = 138, "F8";
+    F9 = 139, "F9";
+    F10 = 140, "F10";
+    F11 = 141, "F11";
+    F12 = 142, "F12";
+    NumLock = 143, "NUM_LOCK";
+    Numpad0 = 144, "NUMPAD_0";
+    Numpad1 = 145, "NUMPAD_1";
+    Numpad2 = 146, "NUMPAD_2";
+    Numpad3 = 147, "NUMPAD_3";
+    Numpad4 = 148, "NUMPAD_4";
+    Numpad5 = 149, "NUMPAD_5";
+    Numpad6 = 150, "NUMPAD_6";
+    Numpad7 = 151, "NUMPAD_7";
+    Numpad8 = 152, "NUMPAD_8";
+    Numpad9 = 153, "NUMPAD_9";
+    NumpadDivide = 154, "NUMPAD_DIVIDE";
+    NumpadMultiply = 155, "NUMPAD_MULTIPLY";
+    NumpadSubtract = 156, "NUMPAD_SUBTRACT";
+    NumpadAdd = 157, "NUMPAD_ADD";
+    NumpadDot = 158, "NUMPAD_DOT";
+    NumpadComma = 159, "NUMPAD_COMMA";
+    NumpadEnter = 160, "NUMPAD_ENTER";
+    NumpadEquals = 161, "NUMPAD_EQUALS";
+    NumpadLeftParen = 162, "NUMPAD_LEFT_PAREN";
+    NumpadRightParen = 163, "NUMPAD_RIGHT_PAREN";
+    VolumeMute = 164, "VOLUME_MUTE";
+    Info = 165, "INFO";
+    ChannelUp = 166, "CHANNEL_UP";
+    ChannelDown = 167, "CHANNEL_DOWN";
+    ZoomIn = 168, "ZOOM_IN";
+    ZoomOut = 169, "ZOOM_OUT";
+    Tv = 170, "TV";
+    Window = 171, "WINDOW";
+    Guide = 172, "GUIDE";
+    Dvr = 173, "DVR";
+    Bookmark = 174, "BOOKMARK";
+    Captions = 175, "CAPTIONS";
+    Settings = 176, "SETTINGS";
+    TvPower = 177, "TV_POWER";
+    TvInput = 178, "TV_INPUT";
+    StbPower = 179, "STB_POWER";
+    StbInput = 180, "STB_INPUT";
+    AvrPower = 181, "AVR_POWER";
+    AvrInput = 182, "AVR_INPUT";
+    ProgRed = 183, "PROG_RED";
+    ProgGreen = 184, "PROG_GREEN";
+    ProgYellow = 185, "PROG_YELLOW";
+    ProgBlue = 186, "PROG_BLUE";
+    AppSwitch = 187, "APP_SWITCH";
+    Button1 = 188, "BUTTON_1";
+    Button2 = 189, "BUTTON_2";
+    Button3 = 190, "BUTTON_3";
+    Button4 = 191, "BUTTON_4";
+    Button5 = 192, "BUTTON_5";
+    Button6 = 193, "BUTTON_6";
+    Button7 = 194, "BUTTON_7";
+    Button8 = 195, "BUTTON_8";
+    Button9 = 196, "BUTTON_9";
+    Button10 = 197, "BUTTON_10";
+    Button11 = 198, "BUTTON_11";
+    Button12 = 199, "BUTTON_12";
+    Button13 = 200, "BUTTON_13";
+    Button14 = 201, "BUTTON_14";
+    Button15 = 202, "BUTTON_15";
+    Button16 = 203, "BUTTON_16";
+    LanguageSwitch = 204, "LANGUAGE_SWITCH";
+    MannerMode = 205, "MANNER_MODE";
+    ThreeDMode = 206, "3D_MODE";
+    Contacts = 207, "CONTACTS";
+    Calendar = 208, "CALENDAR";
+    Music = 209, "MUSIC";
+    Calculator = 210, "CALCULATOR";
+    ZenkakuHankaku = 211, "ZENKAKU_HANKAKU";
+    Eisu = 212, "EISU";
+    Muhenkan = 213, "MUHENKAN";
+    Henkan = 214, "HENKAN";
+    KatakanaHiragana = 215, "KATAKANA_HIRAGANA";
+    Yen = 216, "YEN";
+    Ro = 217, "RO";
+    Kana = 218, "KANA";
+    Assist = 219, "ASSIST";
+    BrightnessDown = 220, "BRIGHTNESS_DOWN";
+    BrightnessUp = 221, "BRIGHTNESS_UP";
+    MediaAudioTrack = 222, "MEDIA_AUDIO_TRACK";
+    Sleep = 223, "SLEEP";
+    Wakeup = 224, "WAKEUP";
+    Pairing = 225, "PAIRING";
+    MediaTopMenu = 226, "MEDIA_TOP_MENU";
+    Digit11 = 227, "11";
+    Digit12 = 228, "12";
+    LastChannel = 229, "LAST_CHANNEL";
+    TvDataService = 230, "TV_DATA_SERVICE";
+    VoiceAssist = 231, "VOICE_ASSIST";
+    TvRadioService = 232, "TV_RADIO_SERVICE";
+    TvTeletext = 233, "TV_TELETEXT";
+    TvNumberEntry = 234, "TV_NUMBER_ENTRY";
+    TvTerrestrialAnalog = 235, "TV_TERRESTRIAL_ANALOG";
+    TvTerrestrialDigital = 236, "TV_TERRESTRIAL_DIGITAL";
+    TvSatellite = 237, "TV_SATELLITE";
+    TvSatelliteBs = 238, "TV_SATELLITE_BS";
+    TvSatelliteCs = 239, "TV_SATELLITE_CS";
+    TvSatelliteService = 240, "TV_SATELLITE_SERVICE";
+    TvNetwork = 241, "TV_NETWORK";
+    TvAntennaCable = 242, "TV_ANTENNA_CABLE";
+    TvInputHdmi1 = 243, "TV_INPUT_HDMI_1";
+    TvInputHdmi2 = 244, "TV_INPUT_HDMI_2";
+    TvInputHdmi3 = 245, "TV_INPUT_HDMI_3";
+    TvInputHdmi4 = 246, "TV_INPUT_HDMI_4";
+    TvInputComposite1 = 247, "TV_INPUT_COMPOSITE_1";
+    TvInputComposite2 = 248, "TV_INPUT_COMPOSITE_2";
+    TvInputComponent1 = 249, "TV_INPUT_COMPONENT_1";
+    TvInputComponent2 = 250, "TV_INPUT_COMPONENT_2";
+    TvInputVga1 = 251, "TV_INPUT_VGA_1";
+    TvAudioDescription = 252, "TV_AUDIO_DESCRIPTION";
+    TvAudioDescriptionMixUp = 253, "TV_AUDIO_DESCRIPTION_MIX_UP";
+    TvAudioDescriptionMixDown = 254, "TV_AUDIO_DESCRIPTION_MIX_DOWN";
+    TvZoomMode = 255, "TV_ZOOM_MODE";
+    TvContentsMenu = 256, "TV_CONTENTS_MENU";
+    TvMediaContextMenu = 257, "TV_MEDIA_CONTEXT_MENU";
+    TvTimerProgramming = 258, "TV_TIMER_PROGRAMMING";
+    Help = 259, "HELP";
+    NavigatePrevious = 260, "NAVIGATE_PREVIOUS";
+    NavigateNext = 261, "NAVIGATE_NEXT";
+    NavigateIn = 262, "NAVIGATE_IN";
+    NavigateOut = 263, "NAVIGATE_OUT";
+    StemPrimary = 264, "STEM_PRIMARY";
+    Stem1 = 265, "STEM_1";
+    Stem2 = 266, "STEM_2";
+    Stem3 = 267, "STEM_3";
+    DpadUpLeft = 268, "DPAD_UP_LEFT";
+    DpadDownLeft = 269, "DPAD_DOWN_LEFT";
+    DpadUpRight = 270, "DPAD_UP_RIGHT";
+    DpadDownRight = 271, "DPAD_DOWN_RIGHT";
+    MediaSkipForward = 272, "MEDIA_SKIP_FORWARD";
+    MediaSkipBackward = 273, "MEDIA_SKIP_BACKWARD";
+    MediaStepForward = 274, "MEDIA_STEP_FORWARD";
+    MediaStepBackward = 275, "MEDIA_STEP_BACKWARD";
+    SoftSleep = 276, "SOFT_SLEEP";
+    Cut = 277, "CUT";
+    Copy = 278, "COPY";
+    Paste = 279, "PASTE";
+    SystemNavigationUp = 280, "SYSTEM_NAVIGATION_UP";
+    SystemNavigationDown = 281, "SYSTEM_NAVIGATION_DOWN";
+    SystemNavigationLeft = 282, "SYSTEM_NAVIGATION_LEFT";
+    SystemNavigationRight = 283, "SYSTEM_NAVIGATION_RIGHT";
+    AllApps = 284, "ALL_APPS";
+    Refresh = 285, "REFRESH";
+    ThumbsUp = 286, "THUMBS_UP";
+    ThumbsDown = 287, "THUMBS_DOWN";
+    ProfileSwitch = 288, "PROFILE_SWITCH";
+    VideoApp1 = 289, "VIDEO_APP_1";
+    VideoApp2 = 290, "VIDEO_APP_2";
+    VideoApp3 = 291, "VIDEO_APP_3";
+    VideoApp4 = 292, "VIDEO_APP_4";
+    VideoApp5 = 293, "VIDEO_APP_5";
+    VideoApp6 = 294, "VIDEO_APP_6";
+    VideoApp7 = 295, "VIDEO_APP_7";
+    VideoApp8 = 296, "VIDEO_APP_8";
+    FeaturedApp1 = 297, "FEATURED_APP_1";
+    FeaturedApp2 = 298, "FEATURED_APP_2";
+    FeaturedApp3 = 299, "FEATURED_APP_3";
+    FeaturedApp4 = 300, "FEATURED_APP_4";
+    DemoApp1 = 301, "DEMO_APP_1";
+    DemoApp2 = 302, "DEMO_APP_2";
+    DemoApp3 = 303, "DEMO_APP_3";
+    DemoApp4 = 304, "DEMO_APP_4";
+    KeyboardBacklightDown = 305, "KEYBOARD_BACKLIGHT_DOWN";
+    KeyboardBacklightUp = 306, "KEYBOARD_BACKLIGHT_UP";
+    KeyboardBacklightToggle = 307, "KEYBOARD_BACKLIGHT_TOGGLE";
+    StylusButtonPrimary = 308, "STYLUS_BUTTON_PRIMARY";
+    StylusButtonSecondary = 309, "STYLUS_BUTTON_SECONDARY";
+    StylusButtonTertiary = 310, "STYLUS_BUTTON_TERTIARY";
+    StylusButtonTail = 311, "STYLUS_BUTTON_TAIL";
+    RecentApps = 312, "RECENT_APPS";
+    Macro1 = 313, "MACRO_1";
+    Macro2 = 314, "MACRO_2";
+    Macro3 = 315, "MACRO_3";
+    Macro4 = 316, "MACRO_4";
+}
+
+impl AndroidKey {
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.iter().copied().find(|x| x.code() == code)
+    }
+}
+
+/// Accepts `KEYCODE_HOME`, `home` or `3`.
+impl FromStr for AndroidKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_ascii_uppercase();
+        let name = upper.strip_prefix("KEYCODE_").unwrap_or(&upper);
+        let found = match name.parse::<u16>() {
+            Ok(code) if name == upper => Self::from_code(code),
+            _ => Self::ALL.iter().copied().find(|x| x.name() == name),
+        };
+        found.ok_or_else(|| format!("Unknown Android key '{s}'"))
+    }
+}
+
+impl fmt::Display for AndroidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KEYCODE_{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_codes() {
+        for text in ["KEYCODE_HOME", "keycode_home", "HOME", " home ", "3"] {
+            assert_eq!(text.parse::<AndroidKey>(), Ok(AndroidKey::Home), "{text}");
+        }
+        assert_eq!("KEYCODE_3".parse::<AndroidKey>(), Ok(AndroidKey::Digit3));
+        assert_eq!(
+            "KEYCODE_MEDIA_PLAY_PAUSE".parse::<AndroidKey>(),
+            Ok(AndroidKey::MediaPlayPause)
+        );
+        assert_eq!(AndroidKey::VolumeUp.to_string(), "KEYCODE_VOLUME_UP");
+        assert_eq!(AndroidKey::Power.code(), 26);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        for text in ["", "NOPE", "KEYCODE_", "KEYCODE_26", "100000", "-1"] {
+            assert_eq!(
+                text.parse::<AndroidKey>(),
+                Err(format!("Unknown Android key '{text}'"))
+            );
+        }
+        assert_eq!(AndroidKey::from_code(9999), None);
+    }
+
+    #[test]
+    fn names_and_codes_round_trip() {
+        for key in AndroidKey::ALL {
+            assert_eq!(key.to_string().parse::<AndroidKey>(), Ok(*key));
+            // a bare number is a code, `KEYCODE_3` is the digit
+            if key.name().parse::<u16>().is_err() {
+                assert_eq!(key.name().parse::<AndroidKey>(), Ok(*key));
+            }
+            assert_eq!(key.code().to_string().parse::<AndroidKey>(), Ok(*key));
+            assert_eq!(AndroidKey::from_code(key.code()), Some(*key));
+        }
+    }
+}
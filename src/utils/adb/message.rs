@@ -0,0 +1,87 @@
+// https://android.googlesource.com/platform/packages/modules/adb/+/refs/heads/main/protocol.txt
+
+use std::io::{Read, Write};
+
+use super::AdbError;
+
+pub const A_CNXN: u32 = 0x4e584e43;
+pub const A_AUTH: u32 = 0x48545541;
+pub const A_OPEN: u32 = 0x4e45504f;
+pub const A_OKAY: u32 = 0x59414b4f;
+pub const A_CLSE: u32 = 0x45534c43;
+pub const A_WRTE: u32 = 0x45545257;
+
+pub const A_VERSION: u32 = 0x01000001;
+pub const MAX_PAYLOAD: u32 = 256 * 1024;
+
+pub const AUTH_TOKEN: u32 = 1;
+pub const AUTH_SIGNATURE: u32 = 2;
+pub const AUTH_RSAPUBLICKEY: u32 = 3;
+
+const HEADER_LEN: usize = 24;
+
+#[derive(Debug)]
+pub struct Message {
+    pub command: u32,
+    pub arg0: u32,
+    pub arg1: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(command: u32, arg0: u32, arg1: u32, payload: Vec<u8>) -> Self {
+        Self {
+            command,
+            arg0,
+            arg1,
+            payload,
+        }
+    }
+
+    /// Builds a message whose payload is a NUL terminated string.
+    pub fn with_str(command: u32, arg0: u32, arg1: u32, payload: &str) -> Self {
+        let mut bytes = payload.as_bytes().to_vec();
+        bytes.push(0);
+        Self::new(command, arg0, arg1, bytes)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), AdbError> {
+        let checksum = self.payload.iter().map(|&b| b as u32).sum::<u32>();
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        for field in [
+            self.command,
+            self.arg0,
+            self.arg1,
+            self.payload.len() as u32,
+            checksum,
+            self.command ^ 0xffffffff,
+        ] {
+            buf.extend_from_slice(&field.to_le_bytes());
+        }
+        buf.extend_from_slice(&self.payload);
+        writer.write_all(&buf)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R, max_payload: u32) -> Result<Self, AdbError> {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let field = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+        let command = field(0);
+        if field(5) != command ^ 0xffffffff {
+            return Err(AdbError::Protocol(format!(
+                "Bad magic for command {command:#010x}"
+            )));
+        }
+        let len = field(3);
+        if len > max_payload {
+            return Err(AdbError::Protocol(format!(
+                "Payload of {len} bytes exceeds the maximum of {max_payload}"
+            )));
+        }
+        let mut payload = vec![0u8; len as usize];
+        reader.read_exact(&mut payload)?;
+        Ok(Self::new(command, field(1), field(2), payload))
+    }
+}
@@ -0,0 +1,204 @@
+//! Reads what a device is doing from `dumpsys` output.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wakefulness {
+    Awake,
+    Asleep,
+    Dreaming,
+    Dozing,
+}
+
+impl fmt::Display for Wakefulness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Wakefulness::Awake => "awake",
+            Wakefulness::Asleep => "asleep",
+            Wakefulness::Dreaming => "dreaming",
+            Wakefulness::Dozing => "dozing",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// From `dumpsys power`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState {
+    pub wakefulness: Wakefulness,
+    /// `Display Power: state=ON`, missing on some builds.
+    pub display_on: Option<bool>,
+}
+
+impl PowerState {
+    /// Awake with the screen on, a dreaming or dozing TV shows nothing useful.
+    pub fn is_on(&self) -> bool {
+        self.wakefulness == Wakefulness::Awake && self.display_on != Some(false)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceState {
+    pub power: PowerState,
+    /// `package/activity` in front.
+    pub foreground: Option<String>,
+    /// The HDMI port of the active source, on TVs with HDMI-CEC.
+    pub hdmi_input: Option<u8>,
+}
+
+pub fn parse_power(dumpsys: &str) -> Result<PowerState, String> {
+    // builds with display power groups print mWakefulnessRaw instead
+    let wakefulness =
+        value_after(dumpsys, "mWakefulness=").or_else(|| value_after(dumpsys, "mWakefulnessRaw="));
+    let wakefulness = match wakefulness {
+        Some("Awake") => Wakefulness::Awake,
+        Some("Asleep") => Wakefulness::Asleep,
+        Some("Dreaming") => Wakefulness::Dreaming,
+        Some("Dozing") => Wakefulness::Dozing,
+        Some(other) => return Err(format!("Unknown wakefulness '{other}'")),
+        None => return Err("No mWakefulness in dumpsys power".to_string()),
+    };
+    let display_on = value_after(dumpsys, "Display Power: state=").map(|x| x == "ON");
+    Ok(PowerState {
+        wakefulness,
+        display_on,
+    })
+}
+
+/// Reads `dumpsys activity activities`, or `dumpsys window` with its `mCurrentFocus`.
+pub fn parse_foreground(dumpsys: &str) -> Option<String> {
+    // mResumedActivity before Android 12, topResumedActivity and ResumedActivity after
+    let record = dumpsys.lines().map(str::trim).find(|x| {
+        x.starts_with("mResumedActivity")
+            || x.starts_with("topResumedActivity")
+            || x.starts_with("ResumedActivity")
+            || x.starts_with("mCurrentFocus")
+    })?;
+    // ActivityRecord{5f3a2c1 u0 com.example/.MainActivity t42}
+    record
+        .split(|c: char| c.is_whitespace() || c == '}')
+        .find(|x| x.contains('/'))
+        .map(ToString::to_string)
+}
+
+/// Reads `dumpsys hdmi_control`. `mActivePortId` is the port when the TV reports it,
+/// else the port is the first digit of the active source's physical address,
+/// `(0x4, 0x2000)` being HDMI 2.
+pub fn parse_hdmi_input(dumpsys: &str) -> Option<u8> {
+    if let Some(port) = value_after(dumpsys, "mActivePortId: ") {
+        return port.parse().ok().filter(|x| *x > 0);
+    }
+    let source = dumpsys
+        .lines()
+        .find_map(|x| x.split_once("mActiveSource: "))?
+        .1;
+    let address = source
+        .trim()
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .nth(1)?
+        .trim()
+        .trim_start_matches("0x");
+    // 0xffff means no active source
+    let address = u16::from_str_radix(address, 16)
+        .ok()
+        .filter(|x| *x != 0xffff)?;
+    Some((address >> 12) as u8).filter(|x| *x > 0)
+}
+
+/// The word after the first `prefix` found on a line.
+fn value_after<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (_, rest) = line.split_once(prefix)?;
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == ',')
+            .unwrap_or(rest.len());
+        Some(&rest[..end]).filter(|x| !x.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_before_android_12() {
+        let power = parse_power(include_str!("testdata/dumpsys_power_android11.txt")).unwrap();
+        assert_eq!(power.wakefulness, Wakefulness::Asleep);
+        assert_eq!(power.display_on, Some(false));
+        assert!(!power.is_on());
+    }
+
+    #[test]
+    fn power_after_android_12() {
+        let power = parse_power(include_str!("testdata/dumpsys_power_android13.txt")).unwrap();
+        assert_eq!(power.wakefulness, Wakefulness::Awake);
+        assert_eq!(power.display_on, Some(true));
+        assert!(power.is_on());
+    }
+
+    #[test]
+    fn power_without_display_state() {
+        let power = parse_power("  mWakefulness=Dreaming\n").unwrap();
+        assert_eq!(power.wakefulness, Wakefulness::Dreaming);
+        assert_eq!(power.display_on, None);
+        assert!(!power.is_on());
+        assert!(parse_power("  mWakefulness=Awake\n").unwrap().is_on());
+        assert!(parse_power("  mWakefulness=Sleepy\n").is_err());
+        assert!(parse_power("Can't find service: power").is_err());
+    }
+
+    #[test]
+    fn foreground_before_android_12() {
+        assert_eq!(
+            parse_foreground(include_str!("testdata/dumpsys_activity_android11.txt")).as_deref(),
+            Some("com.netflix.ninja/.MainActivity")
+        );
+    }
+
+    #[test]
+    fn foreground_after_android_12() {
+        assert_eq!(
+            parse_foreground(include_str!("testdata/dumpsys_activity_android13.txt")).as_deref(),
+            Some(
+                "com.google.android.youtube.tv/com.google.android.apps.youtube.tv.activity.ShellActivity"
+            )
+        );
+    }
+
+    #[test]
+    fn foreground_from_window_focus() {
+        assert_eq!(
+            parse_foreground(include_str!("testdata/dumpsys_window.txt")).as_deref(),
+            Some("com.netflix.ninja/com.netflix.ninja.MainActivity")
+        );
+        assert_eq!(parse_foreground("  mCurrentFocus=null\n"), None);
+        assert_eq!(parse_foreground(""), None);
+    }
+
+    #[test]
+    fn hdmi_input_from_active_port() {
+        let dumpsys = include_str!("testdata/dumpsys_hdmi_control_tv.txt");
+        assert_eq!(parse_hdmi_input(dumpsys), Some(2));
+        assert_eq!(parse_hdmi_input("    mActivePortId: -1\n"), None);
+    }
+
+    #[test]
+    fn hdmi_input_from_active_source() {
+        let dumpsys = include_str!("testdata/dumpsys_hdmi_control_playback.txt");
+        assert_eq!(parse_hdmi_input(dumpsys), Some(3));
+        assert_eq!(
+            parse_hdmi_input("    mActiveSource: (0x4, 0x1100)\n"),
+            Some(1)
+        );
+        // the TV itself is 0.0.0.0
+        assert_eq!(parse_hdmi_input("    mActiveSource: (0x0, 0x0000)\n"), None);
+    }
+
+    #[test]
+    fn hdmi_input_without_active_source() {
+        let dumpsys = include_str!("testdata/dumpsys_hdmi_control_no_source.txt");
+        assert_eq!(parse_hdmi_input(dumpsys), None);
+        assert_eq!(parse_hdmi_input("Can't find service: hdmi_control"), None);
+    }
+}
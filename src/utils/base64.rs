@@ -0,0 +1,98 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+/// Decodes standard base64, ignoring whitespace.
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut res = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut count = 0;
+    let mut padding = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' if padding < 2 => {
+                padding += 1;
+                0
+            }
+            _ => return Err(format!("Invalid base64 character '{}'", c as char)),
+        };
+        if padding > 0 && c != b'=' {
+            return Err("Invalid base64 padding".to_string());
+        }
+        acc = acc << 6 | value as u32;
+        count += 1;
+        if count == 4 {
+            res.extend_from_slice(&acc.to_be_bytes()[1..4 - padding]);
+            acc = 0;
+            count = 0;
+        }
+    }
+    if count != 0 {
+        return Err("Invalid base64 length".to_string());
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4648 section 10
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn encode_rfc4648() {
+        for (data, text) in VECTORS {
+            assert_eq!(encode(data.as_bytes()), text);
+        }
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn decode_rfc4648() {
+        for (data, text) in VECTORS {
+            assert_eq!(decode(text).unwrap(), data.as_bytes());
+        }
+        assert_eq!(decode("Zm9v\r\nYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("+/8=").unwrap(), [0xfb, 0xff]);
+    }
+
+    #[test]
+    fn decode_rejects_invalid() {
+        assert!(decode("Zm9").is_err());
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Zg=a").is_err());
+        assert!(decode("Z===").is_err());
+    }
+}
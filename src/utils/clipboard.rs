@@ -1,9 +1,12 @@
 use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard};
 
-pub fn clear_clipboard() {
+use crate::error::Error;
+
+pub fn clear_clipboard() -> Result<(), Error> {
     unsafe {
-        let _ = OpenClipboard(None);
-        let _ = EmptyClipboard();
+        OpenClipboard(None).map_err(|err| Error::input("Fail to open clipboard", err))?;
+        let res = EmptyClipboard().map_err(|err| Error::input("Fail to empty clipboard", err));
         let _ = CloseClipboard();
+        res
     }
 }
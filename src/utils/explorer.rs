@@ -3,11 +3,11 @@ use windows::Win32::System::Diagnostics::ToolHelp::{
 };
 use windows::Win32::System::Threading::{OpenProcess, PROCESS_TERMINATE, TerminateProcess};
 
-pub fn kill_explorer() {
-    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }.unwrap();
-    if snapshot.is_invalid() {
-        panic!("CreateToolhelp32Snapshot failed");
-    }
+use crate::error::Error;
+
+pub fn kill_explorer() -> Result<(), Error> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }
+        .map_err(|err| Error::process("Fail to list processes", err))?;
 
     unsafe {
         let pe: *mut PROCESSENTRY32 = &mut std::mem::zeroed();
@@ -19,10 +19,10 @@ pub fn kill_explorer() {
                 let file_string = convert_sz_to_string((*pe).szExeFile);
                 if file_string.starts_with("explorer.exe") {
                     let pid = (*pe).th32ProcessID;
-                    let h_proc = OpenProcess(PROCESS_TERMINATE, false, pid);
-                    if h_proc.is_ok() {
-                        let _ = TerminateProcess(h_proc.unwrap(), 0);
-                    }
+                    let h_proc = OpenProcess(PROCESS_TERMINATE, false, pid)
+                        .map_err(|err| Error::process("Fail to open explorer.exe", err))?;
+                    TerminateProcess(h_proc, 0)
+                        .map_err(|err| Error::process("Fail to terminate explorer.exe", err))?;
                     break;
                 }
 
@@ -34,6 +34,7 @@ pub fn kill_explorer() {
             }
         }
     }
+    Ok(())
 }
 
 fn convert_sz_to_string(file: [i8; 260]) -> String {
@@ -2,125 +2,44 @@ use std::mem::size_of;
 
 use windows::Win32::UI::Input::KeyboardAndMouse::{
     INPUT, INPUT_0, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP, SendInput,
-    VK_F4, VK_MENU, VK_TAB,
+    VIRTUAL_KEY, VK_F4, VK_MENU, VK_TAB,
 };
 
-pub fn switch_windows() {
-    let input_0 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_MENU,
-                wScan: 1,
-                dwFlags: KEYBD_EVENT_FLAGS(0),
-                time: 0,
-                dwExtraInfo: 0,
-            },
-        },
-    };
+use crate::error::Error;
 
-    let input_1 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_TAB,
-                wScan: 1,
-                dwFlags: KEYBD_EVENT_FLAGS(0),
-                time: 10,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-
-    let input_2 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_TAB,
-                wScan: 1,
-                dwFlags: KEYEVENTF_KEYUP,
-                time: 20,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-
-    let input_3 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_MENU,
-                wScan: 1,
-                dwFlags: KEYEVENTF_KEYUP,
-                time: 30,
-                dwExtraInfo: 0,
-            },
-        },
-    };
+pub fn switch_windows() -> Result<(), Error> {
+    send_keys(&[VK_MENU.0, VK_TAB.0])
+}
 
-    let inputs = [input_0, input_1, input_2, input_3];
-    unsafe {
-        let res = SendInput(&inputs, size_of::<INPUT>() as i32);
-        println!("{:?}", res);
-    }
+pub fn close_top_window() -> Result<(), Error> {
+    send_keys(&[VK_MENU.0, VK_F4.0])
 }
 
-pub fn close_top_window() {
-    let input_0 = INPUT {
+/// Presses the virtual keys in order, then releases them in reverse order.
+pub fn send_keys(keys: &[u16]) -> Result<(), Error> {
+    let key_input = |vk: u16, flags: KEYBD_EVENT_FLAGS| INPUT {
         r#type: INPUT_KEYBOARD,
         Anonymous: INPUT_0 {
             ki: KEYBDINPUT {
-                wVk: VK_MENU,
+                wVk: VIRTUAL_KEY(vk),
                 wScan: 1,
-                dwFlags: KEYBD_EVENT_FLAGS(0),
+                dwFlags: flags,
                 time: 0,
                 dwExtraInfo: 0,
             },
         },
     };
-
-    let input_1 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_F4,
-                wScan: 1,
-                dwFlags: KEYBD_EVENT_FLAGS(0),
-                time: 10,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-
-    let input_2 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_F4,
-                wScan: 1,
-                dwFlags: KEYEVENTF_KEYUP,
-                time: 20,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-
-    let input_3 = INPUT {
-        r#type: INPUT_KEYBOARD,
-        Anonymous: INPUT_0 {
-            ki: KEYBDINPUT {
-                wVk: VK_MENU,
-                wScan: 1,
-                dwFlags: KEYEVENTF_KEYUP,
-                time: 30,
-                dwExtraInfo: 0,
-            },
-        },
-    };
-
-    let inputs = [input_0, input_1, input_2, input_3];
-    unsafe {
-        let res = SendInput(&inputs, size_of::<INPUT>() as i32);
-        println!("{:?}", res);
+    let inputs = keys
+        .iter()
+        .map(|&vk| key_input(vk, KEYBD_EVENT_FLAGS(0)))
+        .chain(keys.iter().rev().map(|&vk| key_input(vk, KEYEVENTF_KEYUP)))
+        .collect::<Vec<INPUT>>();
+    let sent = unsafe { SendInput(&inputs, size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        return Err(Error::input(
+            "Fail to send input",
+            windows::core::Error::from_win32(),
+        ));
     }
+    Ok(())
 }
@@ -0,0 +1,271 @@
+// https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Virtual key names accepted in configuration, matched case-insensitively.
+const NAMED_KEYS: &[(&str, u16)] = &[
+    ("backspace", 0x08),
+    ("tab", 0x09),
+    ("enter", 0x0d),
+    ("return", 0x0d),
+    ("shift", 0x10),
+    ("ctrl", 0x11),
+    ("control", 0x11),
+    ("alt", 0x12),
+    ("menu", 0x12),
+    ("pause", 0x13),
+    ("capslock", 0x14),
+    ("esc", 0x1b),
+    ("escape", 0x1b),
+    ("space", 0x20),
+    ("pageup", 0x21),
+    ("pagedown", 0x22),
+    ("end", 0x23),
+    ("home", 0x24),
+    ("left", 0x25),
+    ("up", 0x26),
+    ("right", 0x27),
+    ("down", 0x28),
+    ("printscreen", 0x2c),
+    ("insert", 0x2d),
+    ("delete", 0x2e),
+    ("del", 0x2e),
+    ("win", 0x5b),
+    ("lwin", 0x5b),
+    ("rwin", 0x5c),
+    ("apps", 0x5d),
+    ("numlock", 0x90),
+    ("scrolllock", 0x91),
+    ("volumemute", 0xad),
+    ("volumedown", 0xae),
+    ("volumeup", 0xaf),
+    ("nexttrack", 0xb0),
+    ("prevtrack", 0xb1),
+    ("stop", 0xb2),
+    ("playpause", 0xb3),
+];
+
+/// Shift, ctrl, alt and the windows keys, which a hotkey only holds.
+const MODIFIER_KEYS: [u16; 5] = [0x10, 0x11, 0x12, 0x5b, 0x5c];
+
+/// Looks up a virtual key code by name, e.g. `alt`, `f4`, `a` or `7`.
+pub fn vk_from_name(name: &str) -> Option<u16> {
+    let name = name.trim().to_ascii_lowercase();
+    if let Some((_, vk)) = NAMED_KEYS.iter().find(|(key, _)| *key == name) {
+        return Some(*vk);
+    }
+    let bytes = name.as_bytes();
+    match bytes {
+        [c @ b'a'..=b'z'] => Some(c.to_ascii_uppercase() as u16),
+        [c @ b'0'..=b'9'] => Some(*c as u16),
+        [b'f', rest @ ..] => match std::str::from_utf8(rest).ok()?.parse::<u16>().ok()? {
+            n @ 1..=24 => Some(0x70 + n - 1),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Parses a `+` separated key combination such as `alt+f4` into virtual key codes,
+/// in the order they should be pressed.
+pub fn parse_key_combo(text: &str) -> Result<Vec<u16>, String> {
+    if text.trim().is_empty() {
+        return Err("Empty key combination".to_string());
+    }
+    text.split('+')
+        .map(|name| vk_from_name(name).ok_or_else(|| format!("Unknown key '{}'", name.trim())))
+        .collect()
+}
+
+/// A global hotkey such as `ctrl+alt+t`: one key pressed with the modifiers held.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+    pub vk: u16,
+}
+
+impl Hotkey {
+    fn has_modifier(&self) -> bool {
+        self.ctrl || self.alt || self.shift || self.win
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hotkey = Hotkey::default();
+        let mut key = None;
+        for name in s.split('+') {
+            let name = name.trim().to_ascii_lowercase();
+            let modifier = match name.as_str() {
+                "ctrl" | "control" => &mut hotkey.ctrl,
+                "alt" => &mut hotkey.alt,
+                "shift" => &mut hotkey.shift,
+                "win" => &mut hotkey.win,
+                _ => {
+                    let vk = vk_from_name(&name).ok_or_else(|| format!("Unknown key '{name}'"))?;
+                    if MODIFIER_KEYS.contains(&vk) {
+                        return Err(format!("'{name}' can only be used as a modifier"));
+                    }
+                    if key.replace(vk).is_some() {
+                        return Err(format!("Hotkey '{s}' has more than one key"));
+                    }
+                    continue;
+                }
+            };
+            if *modifier {
+                return Err(format!("Hotkey '{s}' repeats '{name}'"));
+            }
+            *modifier = true;
+        }
+        hotkey.vk = key.ok_or_else(|| format!("Hotkey '{s}' has no key besides the modifiers"))?;
+        // A bare letter or arrow would be swallowed from every app
+        let standalone = matches!(hotkey.vk, 0x70..=0x87 | 0xad..=0xb3);
+        if !hotkey.has_modifier() && !standalone {
+            return Err(format!(
+                "Hotkey '{s}' needs ctrl, alt, shift or win unless it is a function or media key"
+            ));
+        }
+        Ok(hotkey)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.ctrl, "ctrl+"),
+            (self.alt, "alt+"),
+            (self.shift, "shift+"),
+            (self.win, "win+"),
+        ];
+        for (held, name) in modifiers {
+            if held {
+                write!(f, "{name}")?;
+            }
+        }
+        match self.vk {
+            vk @ (0x30..=0x39 | 0x41..=0x5a) => {
+                write!(f, "{}", (vk as u8 as char).to_ascii_lowercase())
+            }
+            vk @ 0x70..=0x87 => write!(f, "f{}", vk - 0x70 + 1),
+            vk => match NAMED_KEYS.iter().find(|(_, code)| *code == vk) {
+                Some((name, _)) => write!(f, "{name}"),
+                None => write!(f, "{vk:#04x}"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkey(s: &str) -> Result<Hotkey, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn key_combo() {
+        assert_eq!(parse_key_combo("alt+f4"), Ok(vec![0x12, 0x73]));
+        assert_eq!(
+            parse_key_combo(" Ctrl + Shift + Esc "),
+            Ok(vec![0x11, 0x10, 0x1b])
+        );
+        assert!(parse_key_combo("").is_err());
+        assert!(parse_key_combo("ctrl+nope").is_err());
+        assert_eq!(vk_from_name("f24"), Some(0x87));
+        assert_eq!(vk_from_name("f25"), None);
+    }
+
+    #[test]
+    fn hotkey_with_modifiers() {
+        assert_eq!(
+            hotkey("Ctrl+Alt+T"),
+            Ok(Hotkey {
+                ctrl: true,
+                alt: true,
+                vk: 0x54,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            hotkey("win + shift + 7"),
+            Ok(Hotkey {
+                shift: true,
+                win: true,
+                vk: 0x37,
+                ..Default::default()
+            })
+        );
+        assert_eq!(hotkey("control+left").unwrap().vk, 0x25);
+    }
+
+    #[test]
+    fn hotkey_rejects_repeats_and_extra_keys() {
+        assert!(hotkey("ctrl+control+t").unwrap_err().contains("repeats"));
+        assert!(hotkey("alt+alt+t").unwrap_err().contains("repeats"));
+        assert!(
+            hotkey("ctrl+a+b")
+                .unwrap_err()
+                .contains("more than one key")
+        );
+        assert!(hotkey("ctrl+nope").unwrap_err().contains("Unknown key"));
+    }
+
+    #[test]
+    fn hotkey_needs_a_key() {
+        assert!(hotkey("ctrl+alt").unwrap_err().contains("no key"));
+        assert!(
+            hotkey("ctrl+menu")
+                .unwrap_err()
+                .contains("only be used as a modifier")
+        );
+        assert!(
+            hotkey("ctrl+rwin")
+                .unwrap_err()
+                .contains("only be used as a modifier")
+        );
+    }
+
+    #[test]
+    fn bare_keys() {
+        assert!(hotkey("a").is_err());
+        assert!(hotkey("left").is_err());
+        assert_eq!(hotkey("f13").unwrap().vk, 0x7c);
+        assert_eq!(hotkey("playpause").unwrap().vk, 0xb3);
+        assert_eq!(hotkey("volumeup").unwrap().vk, 0xaf);
+    }
+
+    #[test]
+    fn hotkey_display_round_trips() {
+        for text in [
+            "ctrl+alt+t",
+            "shift+win+7",
+            "ctrl+shift+f12",
+            "alt+space",
+            "f9",
+            "volumemute",
+        ] {
+            let parsed = hotkey(text).unwrap();
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(hotkey(&parsed.to_string()), Ok(parsed));
+        }
+        // modifiers print in a fixed order and names in their canonical form
+        assert_eq!(
+            hotkey("Win+Control+Del").unwrap().to_string(),
+            "ctrl+win+delete"
+        );
+        // keys without a name print as their code
+        let semicolon = Hotkey {
+            ctrl: true,
+            vk: 0xba,
+            ..Default::default()
+        };
+        assert_eq!(semicolon.to_string(), "ctrl+0xba");
+    }
+}
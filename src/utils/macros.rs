@@ -1,18 +1,47 @@
-use windows::Win32::UI::WindowsAndMessaging::{MB_ICONERROR, MB_OK, MessageBoxW};
-use windows::core::{PCWSTR, w};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
 
-use crate::utils::others::to_wstring;
+use crate::platform::platform;
+
+/// Set when running from a console, where messages are logged instead of shown.
+static CONSOLE: AtomicBool = AtomicBool::new(false);
+
+pub fn use_console() {
+    CONSOLE.store(true, Ordering::Relaxed);
+}
+
+pub fn has_console() -> bool {
+    CONSOLE.load(Ordering::Relaxed)
+}
 
 pub fn message_box(text: &str) {
-    let text = to_wstring(text);
-    unsafe {
-        MessageBoxW(
-            None,
-            PCWSTR(text.as_ptr() as _),
-            w!("Window Switcher Error"),
-            MB_OK | MB_ICONERROR,
-        )
-    };
+    if has_console() {
+        crate::log!("{text}");
+    } else {
+        platform().alert(text);
+    }
+}
+
+pub fn info_box(text: &str) {
+    if has_console() {
+        crate::log!("{text}");
+    } else {
+        platform().inform(text);
+    }
+}
+
+/// `HH:MM:SS` in UTC, good enough to correlate log lines.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
 }
 
 #[macro_export]
@@ -21,3 +50,17 @@ macro_rules! alert {
         $crate::utils::macros::message_box(&format!($($arg)*))
     };
 }
+
+#[macro_export]
+macro_rules! inform {
+    ($($arg:tt)*) => {
+        $crate::utils::macros::info_box(&format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        eprintln!("[{}] {}", $crate::utils::macros::timestamp(), format!($($arg)*))
+    };
+}
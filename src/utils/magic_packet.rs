@@ -1,56 +1,294 @@
 // https://github.com/TeemuRemes/wake-on-lan-rust/blob/master/src/lib.rs
 
 use std::{
-    mem::MaybeUninit,
-    net::{Ipv4Addr, ToSocketAddrs, UdpSocket},
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    str::FromStr,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::constants::APP_CONFIG;
+use crate::config::app_config;
+use crate::utils::others::parse_mac_addr;
 
+/// Gap between the repeats of a packet.
+const REPEAT_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a sent packet is remembered.
+const SENT_KEPT: Duration = Duration::from_secs(60);
+
+/// The packets sent lately with the address they left from, so the listener
+/// can tell our own packets coming back from other senders.
+static SENT: Mutex<Vec<Sent>> = Mutex::new(vec![]);
+
+struct Sent {
+    from: SocketAddr,
+    bytes: Vec<u8>,
+    at: Instant,
+}
+
+/// Six 0xff bytes, the target MAC sixteen times, then the SecureOn password if any.
 pub struct MagicPacket {
-    magic_bytes: [u8; 102],
+    magic_bytes: Vec<u8>,
 }
 
 impl MagicPacket {
     pub fn new(mac_address: &[u8; 6]) -> MagicPacket {
-        let magic_bytes: [u8; 102];
+        let mut magic_bytes = Vec::with_capacity(108);
+        magic_bytes.extend_from_slice(&[0xff; 6]);
+        for _ in 0..16 {
+            magic_bytes.extend_from_slice(mac_address);
+        }
+        MagicPacket { magic_bytes }
+    }
 
-        let mut arr: [MaybeUninit<u8>; 102] = unsafe { MaybeUninit::uninit().assume_init() };
+    /// Appends a SecureOn password, which is 4 or 6 bytes.
+    pub fn with_password(mut self, password: &[u8]) -> MagicPacket {
+        self.magic_bytes.extend_from_slice(password);
+        self
+    }
 
-        for (i, element) in arr.iter_mut().enumerate() {
-            if i < 6 {
-                *element = MaybeUninit::new(0xff);
-            } else {
-                let u = i % 6;
-                let tmp = mac_address[u];
-                *element = MaybeUninit::new(tmp);
-            }
+    /// Reads a received packet, the magic bytes optionally followed by a password.
+    pub fn parse(bytes: &[u8]) -> Option<MagicPacket> {
+        let (sync, rest) = bytes.split_first_chunk::<6>()?;
+        if *sync != [0xff; 6] || !matches!(rest.len(), 96 | 100 | 102) {
+            return None;
         }
-        unsafe {
-            magic_bytes = std::mem::transmute::<_, [u8; 102]>(arr);
+        let mac = &rest[..6];
+        if !rest[..96].chunks(6).all(|x| x == mac) {
+            return None;
         }
+        Some(MagicPacket {
+            magic_bytes: bytes.to_vec(),
+        })
+    }
 
-        MagicPacket { magic_bytes }
+    pub fn mac(&self) -> [u8; 6] {
+        self.magic_bytes[6..12].try_into().unwrap()
+    }
+
+    pub fn password(&self) -> Option<&[u8]> {
+        Some(&self.magic_bytes[102..]).filter(|x| !x.is_empty())
     }
 
-    #[allow(unused)]
-    pub fn print_sth(&self) {
-        println!("{:?}", self.magic_bytes);
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.magic_bytes
     }
 
+    /// Sends the packet as configured in `[wol]`: to each port, `repeat` times,
+    /// to the interface's subnet broadcast or 255.255.255.255.
     pub fn send(&self) -> std::io::Result<()> {
-        let local = APP_CONFIG.get().unwrap().server_addr;
-        self.send_to(
-            (Ipv4Addr::new(255, 255, 255, 255), 9),
-            (Ipv4Addr::new(local[0], local[1], local[2], local[3]), 0),
-        )
+        let config = app_config();
+        let wol = &config.wol;
+        let (to, from) = match &wol.interface {
+            Some(interface) => (interface.broadcast(), interface.addr),
+            None => (Ipv4Addr::BROADCAST, Ipv4Addr::from(config.server_addr)),
+        };
+        let to = wol.broadcast.unwrap_or(to);
+        for round in 0..wol.repeat {
+            if round > 0 {
+                thread::sleep(REPEAT_DELAY);
+            }
+            for port in &wol.ports {
+                self.send_to((to, *port), (from, 0))?;
+            }
+        }
+        Ok(())
     }
 
     pub fn send_to<A: ToSocketAddrs>(&self, to_addr: A, from_addr: A) -> std::io::Result<()> {
         let socket = UdpSocket::bind(from_addr)?;
         socket.set_broadcast(true)?;
         socket.send_to(&self.magic_bytes, to_addr)?;
-
+        let mut sent = SENT.lock().unwrap();
+        sent.retain(|x| x.at.elapsed() < SENT_KEPT);
+        sent.push(Sent {
+            from: socket.local_addr()?,
+            bytes: self.magic_bytes.clone(),
+            at: Instant::now(),
+        });
         Ok(())
     }
 }
+
+/// Whether this app sent `bytes` from `from` in the last `within`. A socket bound
+/// to any address sends from one of this host's, so then any local one matches.
+pub fn sent_from_here(from: SocketAddr, bytes: &[u8], within: Duration) -> bool {
+    SENT.lock().unwrap().iter().any(|x| {
+        x.at.elapsed() < within
+            && x.bytes == bytes
+            && x.from.port() == from.port()
+            && (x.from.ip() == from.ip() || x.from.ip().is_unspecified() && is_local(from.ip()))
+    })
+}
+
+/// Only this host's own addresses can be bound.
+fn is_local(ip: IpAddr) -> bool {
+    UdpSocket::bind((ip, 0)).is_ok()
+}
+
+/// This PC's address on the network to wake, with its prefix length, e.g. `192.168.1.10/24`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interface {
+    pub addr: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl Interface {
+    /// The subnet-directed broadcast address, which routers can forward unlike 255.255.255.255.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        let host_bits = u32::MAX.checked_shr(self.prefix as u32).unwrap_or(0);
+        Ipv4Addr::from(u32::from(self.addr) | host_bits)
+    }
+}
+
+impl FromStr for Interface {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("Invalid interface '{s}', expected an address like 192.168.1.10/24");
+        let (addr, prefix) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let addr = addr.parse::<Ipv4Addr>().map_err(|_| invalid())?;
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|x| *x <= 32)
+            .ok_or_else(invalid)?;
+        Ok(Interface { addr, prefix })
+    }
+}
+
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// A SecureOn password, six bytes written like a MAC address or four like an IPv4 address.
+pub fn parse_password(value: &str) -> Result<Vec<u8>, String> {
+    if let Ok(addr) = value.trim().parse::<Ipv4Addr>() {
+        return Ok(addr.octets().to_vec());
+    }
+    parse_mac_addr(value)
+        .map(|x| x.to_vec())
+        .map_err(|_| {
+            format!("Invalid SecureOn password '{value}', expected 6 bytes like a MAC address or 4 like 1.2.3.4")
+        })
+}
+
+pub fn format_password(password: &[u8]) -> String {
+    match password {
+        [a, b, c, d] => Ipv4Addr::new(*a, *b, *c, *d).to_string(),
+        _ => password
+            .iter()
+            .map(|x| format!("{x:02x}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+
+    #[test]
+    fn new_packet_bytes() {
+        let packet = MagicPacket::new(&MAC);
+        let bytes = packet.as_bytes();
+        assert_eq!(bytes.len(), 102);
+        assert_eq!(bytes[..6], [0xff; 6]);
+        for repeat in bytes[6..].chunks(6) {
+            assert_eq!(repeat, MAC);
+        }
+        assert_eq!(packet.mac(), MAC);
+        assert_eq!(packet.password(), None);
+    }
+
+    #[test]
+    fn packet_with_password_bytes() {
+        let packet = MagicPacket::new(&MAC).with_password(&[192, 168, 1, 1]);
+        assert_eq!(packet.as_bytes().len(), 106);
+        assert_eq!(packet.as_bytes()[102..], [192, 168, 1, 1]);
+        assert_eq!(packet.password(), Some(&[192, 168, 1, 1][..]));
+
+        let password = [1, 2, 3, 4, 5, 6];
+        let packet = MagicPacket::new(&MAC).with_password(&password);
+        assert_eq!(packet.as_bytes().len(), 108);
+        assert_eq!(packet.as_bytes()[..102], *MagicPacket::new(&MAC).as_bytes());
+        assert_eq!(packet.password(), Some(&password[..]));
+    }
+
+    #[test]
+    fn parse_accepts_packets_with_and_without_password() {
+        for password in [&[][..], &[1, 2, 3, 4], &[1, 2, 3, 4, 5, 6]] {
+            let sent = MagicPacket::new(&MAC).with_password(password);
+            let received = MagicPacket::parse(sent.as_bytes()).unwrap();
+            assert_eq!(received.mac(), MAC);
+            assert_eq!(received.password().unwrap_or_default(), password);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_other_lengths() {
+        let packet = MagicPacket::new(&MAC).with_password(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        for len in [0, 6, 101, 103, 104, 105, 107, 110] {
+            assert!(
+                MagicPacket::parse(&packet.as_bytes()[..len]).is_none(),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_sync_or_repeats() {
+        let mut bytes = MagicPacket::new(&MAC).as_bytes().to_vec();
+        bytes[0] = 0xfe;
+        assert!(MagicPacket::parse(&bytes).is_none());
+        let mut bytes = MagicPacket::new(&MAC).as_bytes().to_vec();
+        bytes[101] ^= 1;
+        assert!(MagicPacket::parse(&bytes).is_none());
+    }
+
+    #[test]
+    fn interface_broadcast() {
+        let interface = |text: &str| text.parse::<Interface>().unwrap();
+        assert_eq!(
+            interface("192.168.1.10/24").broadcast(),
+            Ipv4Addr::new(192, 168, 1, 255)
+        );
+        assert_eq!(interface("10.1.2.3/0").broadcast(), Ipv4Addr::BROADCAST);
+        assert_eq!(
+            interface("10.1.2.3/32").broadcast(),
+            Ipv4Addr::new(10, 1, 2, 3)
+        );
+        assert_eq!(
+            interface("172.16.5.4/12").broadcast(),
+            Ipv4Addr::new(172, 31, 255, 255)
+        );
+    }
+
+    #[test]
+    fn interface_parse() {
+        let interface = "192.168.1.10/24".parse::<Interface>().unwrap();
+        assert_eq!(interface.to_string(), "192.168.1.10/24");
+        for invalid in ["192.168.1.10", "192.168.1.10/33", "host/24", "::1/64"] {
+            assert!(invalid.parse::<Interface>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn password_forms() {
+        assert_eq!(parse_password("1.2.3.4").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(
+            parse_password("01:02:03:04:05:0a").unwrap(),
+            [1, 2, 3, 4, 5, 10]
+        );
+        assert!(parse_password("1.2.3").is_err());
+        assert_eq!(format_password(&[1, 2, 3, 4]), "1.2.3.4");
+        assert_eq!(format_password(&[1, 2, 3, 4, 5, 10]), "01:02:03:04:05:0a");
+    }
+}
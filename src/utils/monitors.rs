@@ -1,15 +1,73 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(windows)]
 use windows::Win32::Devices::Display::{
-    SDC_APPLY, SDC_TOPOLOGY_EXTERNAL, SDC_TOPOLOGY_INTERNAL, SetDisplayConfig,
+    SDC_APPLY, SDC_TOPOLOGY_CLONE, SDC_TOPOLOGY_EXTEND, SDC_TOPOLOGY_EXTERNAL,
+    SDC_TOPOLOGY_INTERNAL, SetDisplayConfig,
 };
+#[cfg(windows)]
+use windows::Win32::Foundation::WIN32_ERROR;
+
+#[cfg(windows)]
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTopology {
+    Internal,
+    External,
+    Extend,
+    Clone,
+}
+
+impl FromStr for DisplayTopology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "internal" => Ok(DisplayTopology::Internal),
+            "external" => Ok(DisplayTopology::External),
+            "extend" => Ok(DisplayTopology::Extend),
+            "clone" => Ok(DisplayTopology::Clone),
+            _ => Err(format!(
+                "Unknown display topology '{s}', expected internal, external, extend or clone"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DisplayTopology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DisplayTopology::Internal => "internal",
+            DisplayTopology::External => "external",
+            DisplayTopology::Extend => "extend",
+            DisplayTopology::Clone => "clone",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(windows)]
+pub fn set_display_topology(topology: DisplayTopology) -> Result<(), Error> {
+    let topology_flag = match topology {
+        DisplayTopology::Internal => SDC_TOPOLOGY_INTERNAL,
+        DisplayTopology::External => SDC_TOPOLOGY_EXTERNAL,
+        DisplayTopology::Extend => SDC_TOPOLOGY_EXTEND,
+        DisplayTopology::Clone => SDC_TOPOLOGY_CLONE,
+    };
+    let result = unsafe { SetDisplayConfig(None, None, topology_flag | SDC_APPLY) };
+    WIN32_ERROR(result as u32)
+        .ok()
+        .map_err(|err| Error::display(format!("Fail to switch to the {topology} display"), err))
+}
 
-pub fn set_external_display() -> i32 {
-    let flags = SDC_TOPOLOGY_EXTERNAL | SDC_APPLY;
-    let result = unsafe { SetDisplayConfig(None, None, flags) };
-    result
+#[cfg(windows)]
+pub fn set_external_display() -> Result<(), Error> {
+    set_display_topology(DisplayTopology::External)
 }
 
-pub fn set_internal_display() -> i32 {
-    let flags = SDC_TOPOLOGY_INTERNAL | SDC_APPLY;
-    let result = unsafe { SetDisplayConfig(None, None, flags) };
-    result
+#[cfg(windows)]
+pub fn set_internal_display() -> Result<(), Error> {
+    set_display_topology(DisplayTopology::Internal)
 }
@@ -1,73 +1,129 @@
+#[cfg(windows)]
 use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
 use windows::Win32::System::LibraryLoader::GetModuleFileNameW;
+#[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::GWL_USERDATA;
 
 use std::path::PathBuf;
 
+#[cfg(windows)]
 pub const BUFFER_SIZE: usize = 1024;
 
 pub fn get_exe_folder() -> Result<PathBuf, String> {
     let path =
         std::env::current_exe().map_err(|err| format!("Failed to get binary path, {err}"))?;
     path.parent()
-        .ok_or_else(|| format!("Failed to get binary folder"))
+        .ok_or_else(|| "Failed to get binary folder".to_string())
         .map(|v| v.to_path_buf())
 }
 
+#[cfg(windows)]
 pub fn get_exe_path() -> Vec<u16> {
     let mut path = vec![0u16; BUFFER_SIZE];
     let size = unsafe { GetModuleFileNameW(None, &mut path) } as usize;
     path[..size].to_vec()
 }
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(windows, target_arch = "x86_64"))]
 pub fn get_window_ptr(hwnd: HWND) -> isize {
     unsafe { windows::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWL_USERDATA) }
 }
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(windows, target_arch = "x86_64"))]
 pub fn set_window_ptr(hwnd: HWND, ptr: isize) -> isize {
     unsafe { windows::Win32::UI::WindowsAndMessaging::SetWindowLongPtrW(hwnd, GWL_USERDATA, ptr) }
 }
 
+#[cfg(windows)]
 pub fn to_wstring(value: &str) -> Vec<u16> {
     value.encode_utf16().chain(Some(0)).collect::<Vec<u16>>()
 }
 
+/// Accepts `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff`, `aabb.ccdd.eeff` and `aabbccddeeff`.
 pub fn parse_mac_addr(mac: &str) -> Result<[u8; 6], String> {
-    let arr = mac.split(":").collect::<Vec<&str>>();
-    let mut res: [u8; 6] = [0; 6];
-    if arr.len() != 6 {
-        return Err("failed 1".to_string());
+    let invalid = || {
+        format!(
+            "Invalid MAC address '{mac}', expected a form like aa:bb:cc:dd:ee:ff, aa-bb-cc-dd-ee-ff, aabb.ccdd.eeff or aabbccddeeff"
+        )
+    };
+    let mac = mac.trim();
+    let (parts, len) = if mac.contains([':', '-']) {
+        (mac.split([':', '-']).collect::<Vec<_>>(), 2)
+    } else if mac.contains('.') {
+        (mac.split('.').collect(), 4)
+    } else {
+        (vec![mac], 12)
+    };
+    if parts
+        .iter()
+        .any(|x| x.len() != len || !x.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(invalid());
     }
-    for u in 0..6 {
-        match u8::from_str_radix(arr[u], 16) {
-            Ok(val) => {
-                res[u] = val;
-            }
-            Err(_) => {
-                return Err("failed 2".to_string());
-            }
-        }
+    let hex = parts.concat();
+    if hex.len() != 12 {
+        return Err(invalid());
+    }
+    let mut res: [u8; 6] = [0; 6];
+    for (i, byte) in res.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
     }
     Ok(res)
 }
 
-pub fn parse_ip_addr(mac: &str) -> Result<[u8; 4], String> {
-    let arr = mac.split(".").collect::<Vec<&str>>();
-    let mut res: [u8; 4] = [0; 4];
-    if arr.len() != 4 {
-        return Err("failed 1".to_string());
+pub fn format_mac_addr(mac: &[u8; 6]) -> String {
+    let [a, b, c, d, e, f] = mac;
+    format!("{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{f:02x}")
+}
+
+pub fn parse_ip_addr(ip: &str) -> Result<[u8; 4], String> {
+    ip.trim()
+        .parse::<std::net::Ipv4Addr>()
+        .map(|x| x.octets())
+        .map_err(|_| format!("Invalid IPv4 address '{ip}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x1a, 0x2b, 0xcc, 0xdd, 0xef];
+
+    #[test]
+    fn parse_mac_addr_forms() {
+        for text in [
+            "00:1a:2b:cc:dd:ef",
+            "00:1A:2B:CC:DD:EF",
+            "00-1a-2b-cc-dd-ef",
+            "001a.2bcc.ddef",
+            "001a2bccddef",
+            "  00:1a:2b:cc:dd:ef\n",
+        ] {
+            assert_eq!(parse_mac_addr(text), Ok(MAC), "{text}");
+        }
     }
-    for u in 0..4 {
-        match u8::from_str_radix(arr[u], 10) {
-            Ok(val) => {
-                res[u] = val;
-            }
-            Err(_) => {
-                return Err("failed 2".to_string());
-            }
+
+    #[test]
+    fn parse_mac_addr_rejects_invalid() {
+        for text in [
+            "",
+            "00:1a:2b:cc:dd",
+            "00:1a:2b:cc:dd:ef:01",
+            "0:1a:2b:cc:dd:ef",
+            "00:1a:2b:cc:dd:eg",
+            "001a.2bcc.dde",
+            "001a2bccdde",
+            "001a2bccddef0",
+            "+01a2bccddef",
+        ] {
+            assert!(parse_mac_addr(text).is_err(), "{text}");
         }
     }
-    Ok(res)
+
+    #[test]
+    fn format_mac_addr_round_trip() {
+        assert_eq!(format_mac_addr(&MAC), "00:1a:2b:cc:dd:ef");
+        assert_eq!(parse_mac_addr(&format_mac_addr(&MAC)), Ok(MAC));
+    }
 }
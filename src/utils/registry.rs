@@ -1,11 +1,12 @@
 use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
 use windows::Win32::System::Registry::{
     HKEY, HKEY_CURRENT_USER, KEY_ALL_ACCESS, REG_BINARY, REG_VALUE_TYPE, RRF_RT_REG_BINARY,
-    RRF_RT_REG_SZ, RegCloseKey, RegGetValueW, RegOpenKeyExW,
+    RRF_RT_REG_SZ, RegCloseKey, RegGetValueW, RegOpenKeyExW, RegSetValueExW,
 };
 use windows::core::PCWSTR;
 
 use super::others::BUFFER_SIZE;
+use crate::error::Error;
 
 pub struct WrapHKey {
     pub hkey: HKEY,
@@ -17,7 +18,7 @@ impl Drop for WrapHKey {
     }
 }
 
-pub fn get_key(name: PCWSTR) -> Result<WrapHKey, String> {
+pub fn get_key(name: PCWSTR) -> Result<WrapHKey, Error> {
     let mut hkey = HKEY::default();
     let ret = unsafe {
         RegOpenKeyExW(
@@ -28,14 +29,12 @@ pub fn get_key(name: PCWSTR) -> Result<WrapHKey, String> {
             &mut hkey as *mut _,
         )
     };
-    if ret.is_err() {
-        let err = format!("Fail to open reg key, {:?}", ret);
-        return Err(err);
-    }
+    ret.ok()
+        .map_err(|err| Error::registry("Fail to open reg key", err))?;
     Ok(WrapHKey { hkey })
 }
 
-pub fn get_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u16>>, String> {
+pub fn get_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u16>>, Error> {
     let mut buffer: [u16; BUFFER_SIZE] = [0; BUFFER_SIZE];
     let mut size = (BUFFER_SIZE * std::mem::size_of_val(&buffer[0])) as u32;
     let mut kind: REG_VALUE_TYPE = Default::default();
@@ -50,18 +49,16 @@ pub fn get_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u16>>, Stri
             Some(&mut size),
         )
     };
-    if ret.is_err() {
-        if ret == ERROR_FILE_NOT_FOUND.into() {
-            return Ok(None);
-        }
-        let err = format!("Fail to get reg value, {:?}", ret);
-        return Err(err);
+    if ret == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
     }
+    ret.ok()
+        .map_err(|err| Error::registry("Fail to get reg value", err))?;
     let len = (size as usize - 1) / 2;
     Ok(Some(buffer[..len].to_vec()))
 }
 
-pub fn get_raw_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u8>>, String> {
+pub fn get_raw_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u8>>, Error> {
     let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
     let mut size = (BUFFER_SIZE * std::mem::size_of_val(&buffer[0])) as u32;
     let mut kind: REG_VALUE_TYPE = REG_BINARY;
@@ -76,12 +73,16 @@ pub fn get_raw_value(hkey: &HKEY, val_name: PCWSTR) -> Result<Option<Vec<u8>>, S
             Some(&mut size),
         )
     };
-    if ret.is_err() {
-        if ret == ERROR_FILE_NOT_FOUND.into() {
-            return Ok(None);
-        }
-        let err = format!("Fail to get reg value, {:?}", ret);
-        return Err(err);
+    if ret == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
     }
+    ret.ok()
+        .map_err(|err| Error::registry("Fail to get reg value", err))?;
     Ok(Some(buffer[..size as usize].to_vec()))
 }
+
+pub fn set_raw_value(hkey: &HKEY, val_name: PCWSTR, value: &[u8]) -> Result<(), Error> {
+    let ret = unsafe { RegSetValueExW(*hkey, val_name, Some(0), REG_BINARY, Some(value)) };
+    ret.ok()
+        .map_err(|err| Error::registry("Fail to set reg value", err))
+}
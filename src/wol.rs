@@ -0,0 +1,164 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    config::app_config,
+    log,
+    utils::{
+        magic_packet::{MagicPacket, sent_from_here},
+        others::format_mac_addr,
+    },
+};
+
+/// Packets this app just sent are its own when they come back from the same socket.
+const ECHO_WINDOW: Duration = Duration::from_secs(2);
+const RECV_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// Watches `[wol] listen_ports` for magic packets, logging and relaying them.
+/// The ports are bound once, changing them needs a restart.
+pub fn listen() {
+    for port in app_config().wol.listen_ports.iter().copied() {
+        let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)) {
+            Ok(socket) => socket,
+            Err(err) => {
+                log!("Fail to listen for magic packets on port {port}, {err}");
+                continue;
+            }
+        };
+        log!("Listening for magic packets on port {port}");
+        thread::spawn(move || receive(socket));
+    }
+}
+
+fn receive(socket: UdpSocket) {
+    let mut buf = [0u8; 512];
+    loop {
+        match receive_one(&socket, &mut buf) {
+            Ok(_) => {}
+            // Windows reports ICMP port unreachable for earlier sends this way
+            Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+            Err(err) => {
+                log!("Fail to receive magic packet, {err}");
+                thread::sleep(RECV_ERROR_DELAY);
+            }
+        }
+    }
+}
+
+/// What became of a received datagram.
+#[derive(Debug, PartialEq)]
+enum Received {
+    /// Not a magic packet.
+    Ignored,
+    /// A packet this app sent, coming back.
+    Echo,
+    /// A magic packet from another sender, for this MAC.
+    Wake([u8; 6]),
+}
+
+/// Waits for one datagram, then logs it and relays it if it's a magic packet.
+fn receive_one(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<Received> {
+    let (len, from) = socket.recv_from(buf)?;
+    let bytes = &buf[..len];
+    let Some(packet) = MagicPacket::parse(bytes) else {
+        log!("Ignored {len} bytes from {from}, not a magic packet");
+        return Ok(Received::Ignored);
+    };
+    let mac = format_mac_addr(&packet.mac());
+    if sent_from_here(from, bytes, ECHO_WINDOW) {
+        log!("Ignored our own magic packet for {mac} from {from}");
+        return Ok(Received::Echo);
+    }
+    let secure_on = if packet.password().is_some() {
+        " with SecureOn password"
+    } else {
+        ""
+    };
+    log!("Magic packet for {mac} from {from}{secure_on}");
+    if app_config().wol.relay {
+        match packet.send() {
+            Ok(()) => log!("Relayed magic packet for {mac}"),
+            Err(err) => log!("Fail to relay magic packet, {err}"),
+        }
+    }
+    Ok(Received::Wake(packet.mac()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::platform::mock;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+
+    /// A loopback socket that gives up after a while rather than hang the test.
+    fn socket() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+        (socket, addr)
+    }
+
+    fn config(server_ip: &str, port: u16, extra: &str) -> Config {
+        let content = format!(
+            "[server]\nip = \"{server_ip}\"\n\n[wol]\nbroadcast = \"127.0.0.1\"\nports = [{port}]\n{extra}"
+        );
+        Config::from_toml(&content).unwrap()
+    }
+
+    #[test]
+    fn skips_own_packets_only() {
+        let (listener, addr) = socket();
+        for server_ip in ["127.0.0.1", "0.0.0.0"] {
+            let (_guard, _mock) = mock::install(config(server_ip, addr.port(), ""));
+            let mut buf = [0; 512];
+            let packet = MagicPacket::new(&MAC);
+            packet.send().unwrap();
+            assert_eq!(
+                receive_one(&listener, &mut buf).unwrap(),
+                Received::Echo,
+                "{server_ip}"
+            );
+            // the same packet from another sender on this host
+            let (sender, _) = socket();
+            sender.send_to(packet.as_bytes(), addr).unwrap();
+            assert_eq!(
+                receive_one(&listener, &mut buf).unwrap(),
+                Received::Wake(MAC)
+            );
+            sender.send_to(b"hello", addr).unwrap();
+            assert_eq!(receive_one(&listener, &mut buf).unwrap(), Received::Ignored);
+        }
+    }
+
+    #[test]
+    fn relays_packets_without_looping() {
+        // relays to the port it listens on
+        let (listener, addr) = socket();
+        let config = config("127.0.0.1", addr.port(), "relay = true\n");
+        let (_guard, _mock) = mock::install(config);
+        let packet = MagicPacket::new(&MAC).with_password(&[1, 2, 3, 4]);
+        let (sender, _) = socket();
+        sender.send_to(packet.as_bytes(), addr).unwrap();
+        let mut buf = [0; 512];
+        assert_eq!(
+            receive_one(&listener, &mut buf).unwrap(),
+            Received::Wake(MAC)
+        );
+        assert_eq!(receive_one(&listener, &mut buf).unwrap(), Received::Echo);
+        assert_eq!(&buf[..106], packet.as_bytes());
+        listener
+            .set_read_timeout(Some(Duration::from_millis(300)))
+            .unwrap();
+        assert!(receive_one(&listener, &mut buf).is_err());
+    }
+}